@@ -0,0 +1,475 @@
+//! Reading and writing Windows animated cursor (.ani) files.
+//!
+//! These are RIFF files of the "ACON" form, containing an `anih` header, optional `rate` and `seq `
+//! chunks that describe the timing and order of the animation, and a `LIST` of `fram`es,
+//! each of which is a complete .cur file.
+//!
+//! See: https://www.gdgsoft.com/anituner/help/aniformat.htm
+
+use std::fs;
+use std::path::Path;
+
+use crate::animation::Frame;
+use crate::cur::{push_u32, u32_at, CursorFile};
+use crate::error::{Error, WithPath};
+use crate::Result;
+
+/// Animation timings are given in "jiffies", which are 1/60 of a second.
+pub const JIFFIES_PER_SECOND: u32 = 60;
+
+/// How long each step is shown for when no rate is given.
+pub const DEFAULT_JIFFIES: u32 = 6;
+
+/// The `bfAttributes` flag indicating that frames are stored as .cur/.ico data (rather than raw bitmaps).
+const AF_ICON: u32 = 0x1;
+
+/// The `bfAttributes` flag indicating that the file contains a `seq ` chunk.
+const AF_SEQUENCE: u32 = 0x2;
+
+/// The size of the `anih` chunk's data.
+const HEADER_SIZE: u32 = 36;
+
+/// One step of the animation: which frame to show, and for how long.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Step {
+    /// Index into the animation's `frames`.
+    pub frame: usize,
+
+    /// How long this step is shown for, in jiffies.
+    pub jiffies: u32,
+}
+
+/// The contents of an .ani file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimatedCursor {
+    /// The distinct images of the animation, each of which may have several sizes.
+    pub frames: Vec<CursorFile>,
+
+    /// The order the frames are shown in. A frame may be shown several times.
+    pub steps: Vec<Step>,
+
+    pub title: Option<String>,
+    pub author: Option<String>,
+}
+
+impl AnimatedCursor {
+    /// Build an animated cursor from its frames.
+    ///
+    /// The `sequence` lists the frames to show, in order; if it's empty each frame is shown once.
+    /// The `rates` give how long each step is shown for, in jiffies: either one rate for every step,
+    /// one rate per step, or none to use a default.
+    pub fn new(
+        frames: Vec<CursorFile>,
+        sequence: &[usize],
+        rates: &[u32],
+    ) -> Result<AnimatedCursor> {
+        if frames.is_empty() {
+            return Err(invalid("an animated cursor needs at least one frame"));
+        }
+
+        let sequence: Vec<usize> = if sequence.is_empty() {
+            (0..frames.len()).collect()
+        } else {
+            sequence.to_vec()
+        };
+
+        if let Some(frame) = sequence.iter().find(|&&frame| frame >= frames.len()) {
+            return Err(invalid(&format!(
+                "the sequence refers to frame {}, but there are only {} frames",
+                frame,
+                frames.len()
+            )));
+        }
+
+        let rates = match rates.len() {
+            0 => vec![DEFAULT_JIFFIES; sequence.len()],
+            1 => vec![rates[0]; sequence.len()],
+            n if n == sequence.len() => rates.to_vec(),
+            n => {
+                return Err(invalid(&format!(
+                    "got {} rates for {} steps; give one rate, or one for each step",
+                    n,
+                    sequence.len()
+                )))
+            }
+        };
+
+        let steps = sequence
+            .into_iter()
+            .zip(rates)
+            .map(|(frame, jiffies)| Step { frame, jiffies })
+            .collect();
+
+        Ok(AnimatedCursor {
+            frames,
+            steps,
+            title: None,
+            author: None,
+        })
+    }
+
+    /// Build an animated cursor from decoded animation frames (e.g. from PNG images or an animated GIF).
+    ///
+    /// This works like `new`, except that when no `rates` are given, each step uses the delay of its frame.
+    /// The `hotspot` is given in pixels of the frames, which are expected to all be the same size.
+    pub fn from_frames(
+        frames: Vec<Frame>,
+        hotspot: (u32, u32),
+        sequence: &[usize],
+        rates: &[u32],
+    ) -> Result<AnimatedCursor> {
+        let delays: Vec<u32> = frames
+            .iter()
+            .map(|frame| {
+                frame.delay.map_or(DEFAULT_JIFFIES, |delay| {
+                    jiffies_from_millis(delay.as_millis() as u64)
+                })
+            })
+            .collect();
+
+        let has_delays = frames.iter().any(|frame| frame.delay.is_some());
+
+        let cursors = frames
+            .into_iter()
+            .map(|frame| CursorFile::from_images(vec![frame.image], hotspot))
+            .collect::<Result<Vec<_>>>()?;
+
+        let rates: Vec<u32> = if rates.is_empty() && has_delays {
+            if sequence.is_empty() {
+                delays
+            } else {
+                sequence
+                    .iter()
+                    .map(|&frame| delays.get(frame).cloned().unwrap_or(DEFAULT_JIFFIES))
+                    .collect()
+            }
+        } else {
+            rates.to_vec()
+        };
+
+        AnimatedCursor::new(cursors, sequence, &rates)
+    }
+
+    /// Encode and write this cursor to an .ani file at `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        fs::write(&path, self.encode()?).with_path(&path)?;
+
+        Ok(())
+    }
+
+    /// Create a copy of this animation with every frame resampled to each of `sizes`.
+    pub fn resampled(&self, sizes: &[u32]) -> AnimatedCursor {
+        AnimatedCursor {
+            frames: self
+                .frames
+                .iter()
+                .map(|frame| frame.resampled(sizes))
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// The total length of one loop of the animation, in jiffies.
+    pub fn duration(&self) -> u32 {
+        self.steps.iter().map(|step| step.jiffies).sum()
+    }
+
+    /// Decode the contents of an .ani file.
+    pub fn decode(bytes: &[u8]) -> Result<AnimatedCursor> {
+        if bytes.get(0..4) != Some(b"RIFF") || bytes.get(8..12) != Some(b"ACON") {
+            return Err(invalid("not an .ani file"));
+        }
+
+        let mut frames = Vec::new();
+        let mut rates = None;
+        let mut sequence = None;
+        let mut default_jiffies = DEFAULT_JIFFIES;
+        let mut step_count = None;
+        let mut title = None;
+        let mut author = None;
+
+        for (id, data) in chunks(&bytes[12..])? {
+            match id {
+                b"anih" => {
+                    step_count = Some(u32_at(data, 8)? as usize);
+                    default_jiffies = u32_at(data, 28)?;
+
+                    if u32_at(data, 32)? & AF_ICON == 0 {
+                        return Err(invalid("frames stored as raw bitmaps are not supported"));
+                    }
+                }
+                b"rate" => rates = Some(u32_list(data)?),
+                b"seq " => sequence = Some(u32_list(data)?),
+                b"LIST" if data.starts_with(b"fram") => {
+                    for (id, data) in chunks(&data[4..])? {
+                        if id == b"icon" {
+                            frames.push(CursorFile::decode(data)?);
+                        }
+                    }
+                }
+                b"LIST" if data.starts_with(b"INFO") => {
+                    for (id, data) in chunks(&data[4..])? {
+                        match id {
+                            b"INAM" => title = Some(info_string(data)),
+                            b"IART" => author = Some(info_string(data)),
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let step_count = step_count.ok_or_else(|| invalid("missing the anih header"))?;
+
+        let sequence: Vec<usize> = match sequence {
+            Some(sequence) => sequence.into_iter().map(|frame| frame as usize).collect(),
+            None => (0..frames.len()).collect(),
+        };
+
+        let rates = rates.unwrap_or_else(|| vec![default_jiffies]);
+
+        let mut cursor =
+            AnimatedCursor::new(frames, &sequence[..step_count.min(sequence.len())], &rates)?;
+        cursor.title = title;
+        cursor.author = author;
+
+        Ok(cursor)
+    }
+
+    /// Encode this cursor as an .ani file.
+    ///
+    /// The `seq ` and `rate` chunks are only written when the steps aren't simply
+    /// each frame in order, and when the steps don't all have the same rate.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let in_order = self.steps.len() == self.frames.len()
+            && self
+                .steps
+                .iter()
+                .enumerate()
+                .all(|(i, step)| step.frame == i);
+
+        let default_jiffies = self
+            .steps
+            .first()
+            .map_or(DEFAULT_JIFFIES, |step| step.jiffies);
+        let uniform_rate = self
+            .steps
+            .iter()
+            .all(|step| step.jiffies == default_jiffies);
+
+        let mut body = b"ACON".to_vec();
+
+        if self.title.is_some() || self.author.is_some() {
+            let mut info = b"INFO".to_vec();
+            if let Some(title) = &self.title {
+                push_chunk(&mut info, b"INAM", &zero_terminated(title));
+            }
+            if let Some(author) = &self.author {
+                push_chunk(&mut info, b"IART", &zero_terminated(author));
+            }
+            push_chunk(&mut body, b"LIST", &info);
+        }
+
+        let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+        push_u32(&mut header, HEADER_SIZE);
+        push_u32(&mut header, self.frames.len() as u32);
+        push_u32(&mut header, self.steps.len() as u32);
+        push_u32(&mut header, 0); // Width and height, only used for raw bitmaps
+        push_u32(&mut header, 0);
+        push_u32(&mut header, 0); // Bit count and planes, only used for raw bitmaps
+        push_u32(&mut header, 0);
+        push_u32(&mut header, default_jiffies);
+        push_u32(
+            &mut header,
+            if in_order {
+                AF_ICON
+            } else {
+                AF_ICON | AF_SEQUENCE
+            },
+        );
+        push_chunk(&mut body, b"anih", &header);
+
+        if !uniform_rate {
+            let rates: Vec<u32> = self.steps.iter().map(|step| step.jiffies).collect();
+            push_chunk(&mut body, b"rate", &u32_bytes(&rates));
+        }
+
+        if !in_order {
+            let sequence: Vec<u32> = self.steps.iter().map(|step| step.frame as u32).collect();
+            push_chunk(&mut body, b"seq ", &u32_bytes(&sequence));
+        }
+
+        let mut frames = b"fram".to_vec();
+        for frame in &self.frames {
+            push_chunk(&mut frames, b"icon", &frame.encode()?);
+        }
+        push_chunk(&mut body, b"LIST", &frames);
+
+        let mut bytes = Vec::with_capacity(body.len() + 8);
+        push_chunk(&mut bytes, b"RIFF", &body);
+
+        Ok(bytes)
+    }
+}
+
+/// Convert a duration in milliseconds to jiffies, rounding to the nearest one (but at least one).
+pub fn jiffies_from_millis(millis: u64) -> u32 {
+    ((millis * u64::from(JIFFIES_PER_SECOND) + 500) / 1000).max(1) as u32
+}
+
+/// Split RIFF data into its `(id, data)` chunks.
+fn chunks(mut bytes: &[u8]) -> Result<Vec<(&[u8], &[u8])>> {
+    let mut chunks = Vec::new();
+
+    while bytes.len() >= 8 {
+        let id = &bytes[0..4];
+        let length = u32_at(bytes, 4)? as usize;
+        let data = bytes
+            .get(8..8 + length)
+            .ok_or_else(|| invalid("chunk is out of bounds"))?;
+
+        chunks.push((id, data));
+
+        // Chunks are padded to an even length.
+        let next = (8 + length + length % 2).min(bytes.len());
+        bytes = &bytes[next..];
+    }
+
+    Ok(chunks)
+}
+
+fn push_chunk(bytes: &mut Vec<u8>, id: &[u8; 4], data: &[u8]) {
+    bytes.extend_from_slice(id);
+    push_u32(bytes, data.len() as u32);
+    bytes.extend_from_slice(data);
+
+    if data.len() % 2 == 1 {
+        bytes.push(0);
+    }
+}
+
+fn u32_list(data: &[u8]) -> Result<Vec<u32>> {
+    (0..data.len() / 4).map(|i| u32_at(data, i * 4)).collect()
+}
+
+fn u32_bytes(values: &[u32]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(values.len() * 4);
+    for &value in values {
+        push_u32(&mut bytes, value);
+    }
+    bytes
+}
+
+fn info_string(data: &[u8]) -> String {
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).into_owned()
+}
+
+fn zero_terminated(value: &str) -> Vec<u8> {
+    let mut bytes = value.as_bytes().to_vec();
+    bytes.push(0);
+    bytes
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::InvalidCursorData {
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cur::CursorImage;
+    use crate::image::Image;
+
+    /// A frame with one image of each of `sizes`, each filled with a color that tells the frames apart.
+    fn frame(shade: u8, sizes: &[u32], hotspot: (u32, u32)) -> CursorFile {
+        let images = sizes
+            .iter()
+            .map(|&size| {
+                let mut image = Image::new(size, size);
+                for y in 0..size {
+                    for x in 0..size {
+                        image.set_pixel(x, y, [shade, x as u8, y as u8, 255 - shade]);
+                    }
+                }
+
+                CursorImage {
+                    image,
+                    hotspot: (hotspot.0 * size / 32, hotspot.1 * size / 32),
+                }
+            })
+            .collect();
+
+        CursorFile { images }
+    }
+
+    fn round_trip(cursor: &AnimatedCursor) -> AnimatedCursor {
+        AnimatedCursor::decode(&cursor.encode().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn round_trips_frames_rates_and_sequence() {
+        let frames = vec![
+            frame(10, &[32, 48], (0, 0)),
+            frame(100, &[32, 48], (16, 8)),
+            frame(200, &[32, 48], (31, 31)),
+        ];
+        let mut cursor = AnimatedCursor::new(frames, &[0, 2, 1, 2, 0], &[3, 6, 9, 12, 15]).unwrap();
+        cursor.title = Some("Busy".to_string());
+        cursor.author = Some("Someone".to_string());
+
+        let decoded = round_trip(&cursor);
+
+        assert_eq!(decoded, cursor);
+        assert_eq!(decoded.frames[1].images[1].hotspot, (24, 12));
+        assert_eq!(
+            decoded
+                .steps
+                .iter()
+                .map(|step| step.frame)
+                .collect::<Vec<_>>(),
+            [0, 2, 1, 2, 0]
+        );
+        assert_eq!(
+            decoded
+                .steps
+                .iter()
+                .map(|step| step.jiffies)
+                .collect::<Vec<_>>(),
+            [3, 6, 9, 12, 15]
+        );
+    }
+
+    #[test]
+    fn round_trips_without_sequence_or_rate_chunks() {
+        let frames = vec![frame(1, &[32], (2, 3)), frame(2, &[32], (4, 5))];
+        let cursor = AnimatedCursor::new(frames, &[], &[4]).unwrap();
+
+        let bytes = cursor.encode().unwrap();
+        let ids: Vec<&[u8]> = chunks(&bytes[12..])
+            .unwrap()
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(ids, [&b"anih"[..], &b"LIST"[..]]);
+
+        assert_eq!(AnimatedCursor::decode(&bytes).unwrap(), cursor);
+    }
+
+    #[test]
+    fn round_trips_odd_length_chunks() {
+        // The title is 5 bytes with its terminator, so it's padded, which mustn't shift the chunks after it.
+        let mut cursor = AnimatedCursor::new(vec![frame(5, &[24], (1, 1))], &[], &[]).unwrap();
+        cursor.title = Some("Wait".to_string());
+
+        assert_eq!(round_trip(&cursor), cursor);
+    }
+
+    #[test]
+    fn rejects_a_sequence_past_the_last_frame() {
+        assert!(AnimatedCursor::new(vec![frame(0, &[32], (0, 0))], &[0, 1], &[]).is_err());
+    }
+}
@@ -0,0 +1,146 @@
+//! Decoding animated images (GIF and APNG) into complete frames,
+//! with each frame composited on top of the ones before it.
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::error::WithPath;
+use crate::image::{self, Image};
+use crate::Result;
+
+/// A single, fully composited frame of an animation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub image: Image,
+
+    /// How long the frame should be shown for, if the image specifies it.
+    pub delay: Option<Duration>,
+}
+
+/// Read every frame of the GIF or PNG (including APNG) image at `path`.
+pub fn open<P: AsRef<Path>>(path: P) -> Result<Vec<Frame>> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).with_path(path)?;
+
+    let is_gif = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("gif"));
+
+    if is_gif {
+        decode_gif(&bytes)
+    } else {
+        decode_png(&bytes)
+    }
+}
+
+/// Decode every frame of a (possibly animated) GIF image.
+pub fn decode_gif(bytes: &[u8]) -> Result<Vec<Frame>> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::RGBA);
+
+    let mut decoder = options.read_info(bytes)?;
+    let mut canvas = Image::new(u32::from(decoder.width()), u32::from(decoder.height()));
+    let mut frames = Vec::new();
+
+    while let Some(frame) = decoder.read_next_frame()? {
+        let (x, y) = (u32::from(frame.left), u32::from(frame.top));
+        let (width, height) = (u32::from(frame.width), u32::from(frame.height));
+
+        let previous = canvas.clone();
+
+        let mut image = Image::new(width, height);
+        image.pixels.copy_from_slice(&frame.buffer);
+        canvas.draw(&image, x as i32, y as i32);
+
+        frames.push(Frame {
+            image: canvas.clone(),
+            // GIF delays are in hundredths of a second, and zero means "unspecified".
+            delay: match frame.delay {
+                0 => None,
+                delay => Some(Duration::from_millis(u64::from(delay) * 10)),
+            },
+        });
+
+        match frame.dispose {
+            gif::DisposalMethod::Background => canvas.clear(x, y, width, height),
+            gif::DisposalMethod::Previous => canvas = previous,
+            gif::DisposalMethod::Any | gif::DisposalMethod::Keep => {}
+        }
+    }
+
+    Ok(frames)
+}
+
+/// Decode every frame of a PNG image, which may be an animated PNG (APNG).
+/// A regular PNG image decodes to a single frame.
+pub fn decode_png(bytes: &[u8]) -> Result<Vec<Frame>> {
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+    let mut reader = decoder.read_info()?;
+    let (width, height) = (reader.info().width, reader.info().height);
+
+    let frame_count = match reader.info().animation_control {
+        Some(animation) => animation.num_frames as usize,
+        None => {
+            return Ok(vec![Frame {
+                image: Image::decode_png(bytes)?,
+                delay: None,
+            }]);
+        }
+    };
+
+    let mut buffer = vec![0; reader.output_buffer_size()];
+
+    // The default image is only part of the animation if it has its own frame control chunk.
+    if reader.info().frame_control.is_none() {
+        reader.next_frame(&mut buffer)?;
+    }
+
+    let mut canvas = Image::new(width, height);
+    let mut frames = Vec::with_capacity(frame_count);
+
+    while frames.len() < frame_count {
+        let info = reader.next_frame(&mut buffer)?;
+        let control = match reader.info().frame_control {
+            Some(control) => control,
+            None => break,
+        };
+
+        let mut image = Image::new(info.width, info.height);
+        image.pixels = image::to_rgba(&buffer[..info.buffer_size()], info.color_type);
+
+        let (x, y) = (control.x_offset, control.y_offset);
+        let previous = canvas.clone();
+
+        match control.blend_op {
+            png::BlendOp::Source => canvas.copy_from(&image, x as i32, y as i32),
+            png::BlendOp::Over => canvas.draw(&image, x as i32, y as i32),
+        }
+
+        // A zero denominator means hundredths of a second.
+        let denominator = match control.delay_den {
+            0 => 100,
+            denominator => u64::from(denominator),
+        };
+
+        frames.push(Frame {
+            image: canvas.clone(),
+            delay: match control.delay_num {
+                0 => None,
+                numerator => Some(Duration::from_millis(
+                    u64::from(numerator) * 1000 / denominator,
+                )),
+            },
+        });
+
+        match control.dispose_op {
+            png::DisposeOp::Background => canvas.clear(x, y, info.width, info.height),
+            png::DisposeOp::Previous => canvas = previous,
+            png::DisposeOp::None => {}
+        }
+    }
+
+    Ok(frames)
+}
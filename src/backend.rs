@@ -0,0 +1,81 @@
+//! Everything the cursor changer needs from the system: what's under the pointer, and loading and setting cursors.
+//!
+//! On Windows this is `system::SystemBackend`. `trace::FakeBackend` stands in for it anywhere else,
+//! so that the cursor changer's decisions can be replayed and checked without Windows.
+
+use chrono::NaiveDateTime;
+
+use crate::condition::Held;
+use crate::control::Control;
+use crate::trail::Drawing;
+use crate::{Cursor, CursorFiles, Result};
+
+/// Where the pointer is.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Pointer {
+    /// The pointer's position, in screen coordinates.
+    pub position: (i32, i32),
+
+    /// The size, in pixels, that the system uses for cursors on the monitor the pointer is on.
+    pub cursor_size: u32,
+}
+
+/// The window under the pointer.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Window {
+    /// The window's handle, as a number.
+    pub id: usize,
+
+    /// The path of the window's executable, if it could be found.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub executable: Option<String>,
+
+    /// Why the executable couldn't be found.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// What the cursor changer sees on one tick.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Observation {
+    /// The local time.
+    pub time: NaiveDateTime,
+
+    /// Where the pointer is, unless it couldn't be read.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pointer: Option<Pointer>,
+
+    /// The window under the pointer, if there is one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window: Option<Window>,
+
+    /// The modifier keys and mouse buttons that are held down.
+    #[serde(default, skip_serializing_if = "Held::is_empty")]
+    pub held: Held,
+}
+
+pub trait Backend {
+    /// A cursor that has been loaded from a file.
+    type Handle;
+
+    /// Find out where the pointer is, what's under it, and which keys and buttons are held.
+    fn observe(&mut self) -> Observation;
+
+    /// Load one of a cursor's files (its `path`, a variant's, or a role's) at `size` pixels.
+    fn load(&mut self, files: &CursorFiles, path: &str, size: u32) -> Result<Self::Handle>;
+
+    /// Free a cursor that was loaded by `load`, once it's no longer used.
+    fn destroy(&mut self, handle: &Self::Handle);
+
+    /// Use a cursor in place of the system cursors.
+    fn set_cursor(&mut self, cursor: &Cursor<Self::Handle>);
+
+    /// Restore the system cursors.
+    fn restore_cursors(&mut self);
+
+    /// Show a trail on top of everything else, replacing any trail that's shown, or hide it if there's `None`.
+    fn show_trail(&mut self, _trail: Option<&Drawing>) {}
+
+    /// Hear about an instruction that the cursor changer is about to carry out.
+    fn control(&mut self, _control: &Control) {}
+}
@@ -0,0 +1,1271 @@
+//! Command line interface.
+//!
+//! Running without a command starts the cursor changer; the commands are tools for working with cursor files.
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::iter::once;
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDate;
+
+use crate::ani::{self, AnimatedCursor};
+use crate::animation;
+use crate::config::{Config, Sources};
+use crate::control::Control;
+use crate::cur::CursorFile;
+use crate::cursor_data::CursorData;
+use crate::effects::{self, Effect};
+use crate::error::{Error, WithPath};
+use crate::format::Format;
+use crate::history::{self, Action, Decision};
+use crate::inf::Scheme;
+use crate::pack::{Pack, PackInfo};
+use crate::preview;
+use crate::role::CursorRole;
+use crate::stats::{self, Stats};
+use crate::trace;
+use crate::xcursor;
+use crate::Result;
+
+#[derive(StructOpt, Debug)]
+#[structopt(about = "Changes the Windows cursor depending on the application under it.")]
+pub struct Options {
+    /// The configuration file to use, on top of the machine-wide and user files.
+    /// Defaults to cursor.toml (or cursor.json or cursor.yaml) in the current directory, if there is one.
+    #[structopt(long = "config", parse(from_os_str))]
+    pub config: Option<PathBuf>,
+
+    /// The format of the configuration file (toml, json, or yaml). Defaults to the one its extension names.
+    #[structopt(long = "format")]
+    pub format: Option<Format>,
+
+    /// Record a trace of what the cursor changer sees and does to this file, for the replay command.
+    #[structopt(long = "record", parse(from_os_str))]
+    pub record: Option<PathBuf>,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(StructOpt, Debug)]
+pub enum Command {
+    /// Build a .cur file from one or more PNG images.
+    ///
+    /// Each image becomes one size of the cursor, and Windows picks whichever size best fits the display.
+    #[structopt(name = "convert")]
+    Convert {
+        /// PNG images of the cursor, one for each size.
+        #[structopt(required = true, parse(from_os_str))]
+        images: Vec<PathBuf>,
+
+        /// The "hot" pixel where clicks happen, as "x,y" in pixels of the largest image.
+        #[structopt(long = "hotspot", default_value = "0,0", parse(try_from_str = parse_hotspot))]
+        hotspot: (u32, u32),
+
+        /// Instead of using the images as they are, resample them to each of these sizes (e.g. "32,48,64").
+        #[structopt(long = "sizes", use_delimiter = true)]
+        sizes: Vec<u32>,
+
+        /// Where to write the .cur file.
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        output: PathBuf,
+    },
+
+    /// Render cursors to PNG images, to review them without installing them.
+    ///
+    /// Static cursors show each of their sizes, and animated cursors show each of their frames.
+    /// The hotspot is marked with a small magenta cross.
+    #[structopt(name = "preview")]
+    Preview {
+        /// Cursor files (.cur, .ani, or .png) to preview. Defaults to every cursor in the configuration file.
+        #[structopt(parse(from_os_str))]
+        files: Vec<PathBuf>,
+
+        /// The configuration file to read cursors from, on top of the machine-wide and user files.
+        /// Defaults to cursor.toml in the current directory, if there is one.
+        #[structopt(long = "config", parse(from_os_str))]
+        config: Option<PathBuf>,
+
+        /// The directory to write the PNG images into.
+        #[structopt(
+            short = "o",
+            long = "output",
+            default_value = "preview",
+            parse(from_os_str)
+        )]
+        output: PathBuf,
+
+        /// The size to show the frames of animated cursors at.
+        #[structopt(long = "size", default_value = "32")]
+        size: u32,
+
+        /// Also print each cursor to the terminal (requires 24-bit color support).
+        #[structopt(long = "ansi")]
+        ansi: bool,
+    },
+
+    /// Add a cursor scheme from its .inf install file (usually install.inf) to the configuration file.
+    ///
+    /// Each file in the scheme is used for its system cursor role (pointer, text, busy, link, ...).
+    #[structopt(name = "import-scheme")]
+    ImportScheme {
+        /// The scheme's .inf file. The cursor files are expected to be in the same directory.
+        #[structopt(parse(from_os_str))]
+        inf: PathBuf,
+
+        /// The name of the new cursor. Defaults to the name of the scheme.
+        #[structopt(long = "name")]
+        name: Option<String>,
+
+        /// The configuration file to add the cursor to. It's created if it doesn't exist.
+        #[structopt(long = "config", default_value = "cursor.toml", parse(from_os_str))]
+        config: PathBuf,
+
+        /// Print the cursor's definition instead of adding it to the configuration file.
+        #[structopt(long = "print")]
+        print: bool,
+    },
+
+    /// Convert an X11 (Xcursor) cursor theme, as used on Linux, and add it to the configuration file.
+    ///
+    /// The standard cursors (left_ptr, xterm, hand2, watch, ...) are converted to .cur and .ani files
+    /// for their matching system cursor roles.
+    #[structopt(name = "import-xcursor")]
+    ImportXcursor {
+        /// The theme's directory (the one containing `index.theme` or the `cursors` directory).
+        #[structopt(parse(from_os_str))]
+        theme: PathBuf,
+
+        /// The directory to write the converted cursor files into.
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        output: PathBuf,
+
+        /// The name of the new cursor. Defaults to the name of the theme.
+        #[structopt(long = "name")]
+        name: Option<String>,
+
+        /// The configuration file to add the cursor to. It's created if it doesn't exist.
+        #[structopt(long = "config", default_value = "cursor.toml", parse(from_os_str))]
+        config: PathBuf,
+
+        /// Print the cursor's definition instead of adding it to the configuration file.
+        #[structopt(long = "print")]
+        print: bool,
+    },
+
+    /// Work with animated (.ani) cursors.
+    #[structopt(name = "ani")]
+    Ani {
+        #[structopt(subcommand)]
+        command: AniCommand,
+    },
+
+    /// Write a JSON Schema of the configuration file, which editors can use to check it and suggest completions.
+    #[structopt(name = "schema")]
+    Schema {
+        /// Where to write the schema. It's printed if this is left out.
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+
+    /// Show the configuration files that are read, or the configuration they add up to.
+    #[structopt(name = "config")]
+    Config {
+        #[structopt(subcommand)]
+        command: ConfigCommand,
+    },
+
+    /// Show how long the pointer spent over each application and with each cursor, and how often the cursor
+    /// was switched. Statistics are recorded when the configuration has `[stats]` with `enabled = true`.
+    #[structopt(name = "stats")]
+    Stats {
+        /// How many days to show, counting back from today.
+        #[structopt(long = "days", default_value = "7")]
+        days: u32,
+
+        /// List each day's totals too.
+        #[structopt(long = "daily")]
+        daily: bool,
+
+        /// The configuration file to use, on top of the machine-wide and user files.
+        /// Defaults to cursor.toml in the current directory, if there is one.
+        #[structopt(long = "config", parse(from_os_str))]
+        config: Option<PathBuf>,
+    },
+
+    /// Show the running cursor changer's recent decisions: what was under the pointer, which rule matched,
+    /// and which cursor was applied.
+    #[structopt(name = "history")]
+    History {
+        /// How many of the most recent decisions to show.
+        #[structopt(long = "last", default_value = "50")]
+        last: usize,
+
+        /// Read the journal instead, which has every decision if `journal = true` is set in `[history]`.
+        #[structopt(long = "journal")]
+        journal: bool,
+
+        /// Print the decisions as JSON lines, rather than a table.
+        #[structopt(long = "json")]
+        json: bool,
+
+        /// The configuration file to use, on top of the machine-wide and user files.
+        /// Defaults to cursor.toml in the current directory, if there is one.
+        #[structopt(long = "config", parse(from_os_str))]
+        config: Option<PathBuf>,
+    },
+
+    /// Replay a trace recorded with --record, and check that the cursors are set and restored the same way.
+    ///
+    /// This runs without Windows, so a trace from one machine can be checked anywhere.
+    #[structopt(name = "replay")]
+    Replay {
+        /// The trace file.
+        #[structopt(parse(from_os_str))]
+        trace: PathBuf,
+
+        /// The configuration file to use, on top of the machine-wide and user files.
+        /// Defaults to cursor.toml in the current directory, if there is one.
+        #[structopt(long = "config", parse(from_os_str))]
+        config: Option<PathBuf>,
+    },
+
+    /// List profiles, or switch the running cursor changer to one.
+    #[structopt(name = "profile")]
+    Profile {
+        #[structopt(subcommand)]
+        command: ProfileCommand,
+    },
+
+    /// Work with cursor packs: zip archives of cursors with a pack.toml manifest.
+    #[structopt(name = "pack")]
+    Pack {
+        #[structopt(subcommand)]
+        command: PackCommand,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+pub enum ConfigCommand {
+    /// List the configuration files, in the order they're merged (later files override earlier ones).
+    #[structopt(name = "show")]
+    Show {
+        /// Print the merged configuration instead, noting which file each part came from.
+        #[structopt(long = "resolved")]
+        resolved: bool,
+
+        /// The configuration file to use, on top of the machine-wide and user files.
+        /// Defaults to cursor.toml in the current directory, if there is one.
+        #[structopt(long = "config", parse(from_os_str))]
+        config: Option<PathBuf>,
+
+        /// The format of the configuration file (toml, json, or yaml). Defaults to the one its extension names.
+        #[structopt(long = "format")]
+        format: Option<Format>,
+    },
+
+    /// Convert a configuration file between TOML, JSON, and YAML. Files it includes aren't converted.
+    #[structopt(name = "convert")]
+    Convert {
+        /// The configuration file to convert.
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+
+        /// Where to write the converted file. It's printed if this is left out.
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// The format of the input file. Defaults to the one its extension names.
+        #[structopt(long = "from")]
+        from: Option<Format>,
+
+        /// The format to convert to. Defaults to the one the output file's extension names.
+        #[structopt(long = "to")]
+        to: Option<Format>,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+pub enum ProfileCommand {
+    /// List the profiles in the configuration.
+    #[structopt(name = "list")]
+    List {
+        /// The configuration file to use, on top of the machine-wide and user files.
+        /// Defaults to cursor.toml in the current directory, if there is one.
+        #[structopt(long = "config", parse(from_os_str))]
+        config: Option<PathBuf>,
+    },
+
+    /// Switch the running cursor changer to a profile, without restarting it.
+    #[structopt(name = "switch")]
+    Switch {
+        /// The name of the profile. Leave it out to stop using a profile.
+        name: Option<String>,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+pub enum PackCommand {
+    /// Build a pack from cursors in the configuration file, including all of their files.
+    #[structopt(name = "create")]
+    Create {
+        /// Names of the cursors to include. Defaults to every cursor in the configuration file.
+        cursors: Vec<String>,
+
+        /// The configuration file to read cursors from, on top of the machine-wide and user files.
+        /// Defaults to cursor.toml in the current directory, if there is one.
+        #[structopt(long = "config", parse(from_os_str))]
+        config: Option<PathBuf>,
+
+        /// The name of the pack.
+        #[structopt(long = "name")]
+        name: String,
+
+        /// The version of the pack.
+        #[structopt(long = "version")]
+        version: Option<String>,
+
+        /// A short description of the pack.
+        #[structopt(long = "description")]
+        description: Option<String>,
+
+        /// An author of the pack. May be given several times.
+        #[structopt(long = "author", number_of_values = 1)]
+        authors: Vec<String>,
+
+        /// The license the cursors are distributed under.
+        #[structopt(long = "license")]
+        license: Option<String>,
+
+        /// Where to write the pack's .zip file.
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        output: PathBuf,
+    },
+
+    /// Show the contents of a pack's manifest.
+    #[structopt(name = "inspect")]
+    Inspect {
+        #[structopt(parse(from_os_str))]
+        pack: PathBuf,
+    },
+
+    /// Check that every file in a pack is present and matches its checksum.
+    #[structopt(name = "verify")]
+    Verify {
+        #[structopt(parse(from_os_str))]
+        pack: PathBuf,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+pub enum AniCommand {
+    /// Build an .ani file from a list of PNG frames, or from an animated GIF or PNG.
+    #[structopt(name = "build")]
+    Build {
+        /// Frames of the animation, in order. GIF and animated PNG images contribute all of their frames.
+        #[structopt(required = true, parse(from_os_str))]
+        frames: Vec<PathBuf>,
+
+        /// The "hot" pixel where clicks happen, as "x,y".
+        #[structopt(long = "hotspot", default_value = "0,0", parse(try_from_str = parse_hotspot))]
+        hotspot: (u32, u32),
+
+        /// How long each step is shown for, in jiffies (1/60 of a second). Give one rate for every step,
+        /// or one for each step. Defaults to the delays of animated images, or 6 jiffies.
+        #[structopt(long = "rate", use_delimiter = true)]
+        rates: Vec<u32>,
+
+        /// The order to show the frames in, as zero-based frame numbers (e.g. "0,1,2,1").
+        /// Defaults to showing each frame once.
+        #[structopt(long = "sequence", use_delimiter = true)]
+        sequence: Vec<usize>,
+
+        /// Resample every frame to this many pixels.
+        #[structopt(long = "size")]
+        size: Option<u32>,
+
+        /// A title to store in the file.
+        #[structopt(long = "title")]
+        title: Option<String>,
+
+        /// An author to store in the file.
+        #[structopt(long = "author")]
+        author: Option<String>,
+
+        /// Where to write the .ani file.
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        output: PathBuf,
+    },
+}
+
+/// A cursor file to preview, and how to show it.
+struct PreviewSource {
+    name: String,
+    path: String,
+    hotspot: (u32, u32),
+    effects: Vec<Effect>,
+}
+
+/// Run a command.
+pub fn run(command: Command) -> Result<()> {
+    match command {
+        Command::Convert {
+            images,
+            hotspot,
+            sizes,
+            output,
+        } => {
+            let mut cursor = CursorFile::from_pngs(&images, hotspot)?;
+
+            if !sizes.is_empty() {
+                cursor = cursor.resampled(&sizes);
+            }
+
+            cursor.save(&output)?;
+
+            println!(
+                "Wrote {} with {} image(s).",
+                output.display(),
+                cursor.images.len()
+            );
+        }
+        Command::Ani {
+            command:
+                AniCommand::Build {
+                    frames,
+                    hotspot,
+                    rates,
+                    sequence,
+                    size,
+                    title,
+                    author,
+                    output,
+                },
+        } => {
+            let mut decoded = Vec::new();
+            for path in &frames {
+                decoded.extend(animation::open(path)?);
+            }
+
+            let mut cursor = AnimatedCursor::from_frames(decoded, hotspot, &sequence, &rates)?;
+
+            if let Some(size) = size {
+                cursor = cursor.resampled(&[size]);
+            }
+
+            cursor.title = title;
+            cursor.author = author;
+            cursor.save(&output)?;
+
+            println!(
+                "Wrote {} with {} frame(s) lasting {:.2} seconds.",
+                output.display(),
+                cursor.frames.len(),
+                cursor.duration() as f32 / ani::JIFFIES_PER_SECOND as f32
+            );
+        }
+        Command::ImportScheme {
+            inf,
+            name,
+            config,
+            print,
+        } => {
+            let scheme = Scheme::open(&inf)?;
+
+            let name = name
+                .or_else(|| scheme.name.clone())
+                .filter(|name| !name.is_empty())
+                .ok_or_else(|| Error::InvalidArgument {
+                    reason: "the scheme has no name, so one must be given with --name".to_string(),
+                })?;
+
+            for skipped in &scheme.skipped {
+                eprintln!(
+                    "Skipping the {} cursor, which can't be set by this app.",
+                    skipped
+                );
+            }
+            for path in scheme.files.values().filter(|path| !path.exists()) {
+                eprintln!("Warning: {} doesn't exist.", path.display());
+            }
+
+            add_scheme(&name, &scheme.files, &config, print)?;
+        }
+        Command::Schema { output } => {
+            let text = Config::schema()?;
+            match output {
+                Some(output) => {
+                    fs::write(&output, text + "\n").with_path(&output)?;
+                    println!("Wrote {}.", output.display());
+                }
+                None => println!("{}", text),
+            }
+        }
+        Command::Config {
+            command:
+                ConfigCommand::Show {
+                    resolved,
+                    config,
+                    format,
+                },
+        } => {
+            let (config, sources) = Config::load_layers(config.as_deref(), format)?;
+
+            if resolved {
+                print!("{}", resolved_config(&config, &sources)?);
+            } else {
+                println!("Configuration files, lowest priority first:");
+                for (file, found) in &sources.files {
+                    if *found {
+                        println!("  {}", file);
+                    } else {
+                        println!("  {} (not found)", file);
+                    }
+                }
+            }
+        }
+        Command::Config {
+            command:
+                ConfigCommand::Convert {
+                    input,
+                    output,
+                    from,
+                    to,
+                },
+        } => {
+            let from = from.unwrap_or_else(|| Format::of(&input));
+            let to = match (to, &output) {
+                (Some(to), _) => to,
+                (None, Some(output)) => Format::of(output),
+                (None, None) => {
+                    return Err(Error::InvalidArgument {
+                        reason: "give the format to convert to with --to, or an output file"
+                            .to_string(),
+                    })
+                }
+            };
+
+            let contents = fs::read_to_string(&input).with_path(&input)?;
+            let value = from.parse(&input.display().to_string(), &contents)?;
+            let converted = to.write(&value)?;
+
+            match output {
+                Some(output) => {
+                    fs::write(&output, converted).with_path(&output)?;
+                    println!(
+                        "Converted {} from {} to {} in {}.",
+                        input.display(),
+                        from,
+                        to,
+                        output.display()
+                    );
+                }
+                None => print!("{}", converted),
+            }
+        }
+        Command::Stats {
+            days,
+            daily,
+            config,
+        } => {
+            let config = Config::load(config.as_deref(), None)?;
+            let stats_config = config.stats.clone().unwrap_or_default();
+            if !stats_config.enabled {
+                println!("Statistics aren't being recorded. Add `[stats]` with `enabled = true` to the configuration.");
+            }
+
+            // Have the running cursor changer save what it has recorded so far.
+            match send_control(&Control::SaveStats) {
+                Ok(()) | Err(Error::NotRunning) => {}
+                Err(e) => return Err(e),
+            }
+
+            let stats = Stats::load(&stats_config.path(&config.dir))?;
+            let last = chrono::Local::now().date_naive();
+            let first = last - chrono::Duration::days(i64::from(days.max(1)) - 1);
+
+            print!("{}", stats_report(&stats, first, last, daily));
+        }
+        Command::History {
+            last,
+            journal,
+            json,
+            config,
+        } => {
+            let config = Config::load(config.as_deref(), None)?;
+            let path = if journal {
+                config
+                    .history
+                    .clone()
+                    .unwrap_or_default()
+                    .journal_path(&config.dir)
+            } else {
+                // Have the running cursor changer write out its recent decisions.
+                match send_control(&Control::SaveHistory) {
+                    Ok(()) | Err(Error::NotRunning) => {}
+                    Err(e) => return Err(e),
+                }
+                config.dir.join(history::FILE_NAME)
+            };
+
+            if !path.exists() {
+                println!("There's no history yet ({} doesn't exist).", path.display());
+                return Ok(());
+            }
+
+            let decisions = history::load(&path)?;
+            let skip = decisions.len().saturating_sub(last);
+            for decision in &decisions[skip..] {
+                if json {
+                    println!("{}", serde_json::to_string(decision)?);
+                } else {
+                    println!("{}", describe_decision(decision));
+                }
+            }
+        }
+        Command::Replay { trace, config } => {
+            let config = Config::load(config.as_deref(), None)?;
+            let events = trace::load(&trace)?;
+            let replay = trace::replay(config, &events)?;
+
+            if let Some(difference) = replay.first_difference() {
+                return Err(Error::ReplayMismatch { difference });
+            }
+
+            println!(
+                "Replayed {} ticks: the cursor was set or restored {} times, as in the trace.",
+                replay.ticks,
+                replay.actual.len()
+            );
+        }
+        Command::Profile {
+            command: ProfileCommand::List { config },
+        } => {
+            let config = Config::load(config.as_deref(), None)?;
+
+            if config.profile.is_empty() {
+                println!("There are no profiles.");
+            }
+
+            for (name, profile) in &config.profile {
+                let active = match &config.active_profile {
+                    Some(active) if active.get_ref() == name => " (active)",
+                    _ => "",
+                };
+                println!("{}{}", name, active);
+
+                if let Some(hotkey) = &profile.hotkey {
+                    println!("    Hotkey: {}", hotkey);
+                }
+                if let Some(default) = &profile.default {
+                    println!("    Default cursor: {}", default.get_ref());
+                }
+                for application in &profile.application {
+                    match application.when.as_ref().and_then(|when| when.describe()) {
+                        Some(when) => println!(
+                            "    {} {}: {}",
+                            application.path,
+                            when,
+                            application.cursor.get_ref()
+                        ),
+                        None => println!("    {}: {}", application.path, application.cursor.get_ref()),
+                    }
+                }
+            }
+        }
+        Command::Profile {
+            command: ProfileCommand::Switch { name },
+        } => {
+            send_control(&Control::SwitchProfile(name.clone()))?;
+
+            match name {
+                Some(name) => println!("Switched to the profile \"{}\".", name),
+                None => println!("Switched to no profile."),
+            }
+        }
+        Command::Pack {
+            command:
+                PackCommand::Create {
+                    cursors,
+                    config,
+                    name,
+                    version,
+                    description,
+                    authors,
+                    license,
+                    output,
+                },
+        } => {
+            let config = Config::load(config.as_deref(), None)?;
+
+            let mut selected = Vec::new();
+            for cursor in &config.cursor {
+                if cursors.is_empty() || cursors.contains(&cursor.name) {
+                    selected.push(cursor.clone());
+                }
+            }
+            if let Some(missing) = cursors
+                .iter()
+                .find(|name| !config.cursor.iter().any(|cursor| &cursor.name == *name))
+            {
+                return Err(Error::MissingCursorNameError {
+                    name: missing.clone(),
+                });
+            }
+
+            for cursor in selected.iter().filter(|cursor| !cursor.effect.is_empty()) {
+                eprintln!(
+                    "Warning: the effects of the cursor \"{}\" aren't included in the pack.",
+                    cursor.name
+                );
+            }
+
+            let info = PackInfo {
+                name,
+                version,
+                description,
+                authors,
+                license,
+            };
+            let manifest = Pack::create(&output, info, &selected)?;
+
+            println!(
+                "Wrote {} with {} cursor(s) and {} file(s).",
+                output.display(),
+                manifest.cursor.len(),
+                manifest.checksums.len()
+            );
+        }
+        Command::Pack {
+            command: PackCommand::Inspect { pack },
+        } => {
+            let manifest = Pack::open(&pack)?.manifest;
+
+            match &manifest.version {
+                Some(version) => println!("{} {}", manifest.name, version),
+                None => println!("{}", manifest.name),
+            }
+            if let Some(description) = &manifest.description {
+                println!("{}", description);
+            }
+            if !manifest.authors.is_empty() {
+                println!("Authors: {}", manifest.authors.join(", "));
+            }
+            if let Some(license) = &manifest.license {
+                println!("License: {}", license);
+            }
+
+            println!();
+            for cursor in &manifest.cursor {
+                println!("{} ({})", cursor.name, cursor.path);
+                for variant in &cursor.variant {
+                    println!("    {}px: {}", variant.size, variant.path);
+                }
+                for (role, path) in &cursor.roles {
+                    println!("    {}: {}", role.name(), path);
+                }
+                if !cursor.authors.is_empty() {
+                    println!("    Authors: {}", cursor.authors.join(", "));
+                }
+                if let Some(license) = &cursor.license {
+                    println!("    License: {}", license);
+                }
+            }
+
+            println!();
+            println!("{} file(s) with checksums.", manifest.checksums.len());
+        }
+        Command::Pack {
+            command: PackCommand::Verify { pack },
+        } => {
+            let problems = Pack::open(&pack)?.verify()?;
+
+            for problem in &problems {
+                println!("{}", problem);
+            }
+
+            if !problems.is_empty() {
+                return Err(Error::InvalidPack {
+                    pack: pack.display().to_string(),
+                    reason: format!("found {} problem(s)", problems.len()),
+                });
+            }
+
+            println!("{} is OK.", pack.display());
+        }
+        Command::ImportXcursor {
+            theme,
+            output,
+            name,
+            config,
+            print,
+        } => {
+            let name = name
+                .or_else(|| xcursor::theme_name(&theme))
+                .or_else(|| {
+                    let name = theme.file_name()?.to_string_lossy().into_owned();
+                    Some(name)
+                })
+                .ok_or_else(|| Error::InvalidArgument {
+                    reason: "the theme has no name, so one must be given with --name".to_string(),
+                })?;
+
+            let theme_files = xcursor::theme_files(&xcursor::cursors_dir(&theme));
+            if theme_files.is_empty() {
+                return Err(Error::InvalidArgument {
+                    reason: format!(
+                        "{} doesn't contain any of the standard cursors",
+                        theme.display()
+                    ),
+                });
+            }
+
+            fs::create_dir_all(&output).with_path(&output)?;
+
+            let mut files = BTreeMap::new();
+            for (role, path) in theme_files {
+                let cursor = xcursor::open(&path)?;
+                let file = output.join(format!("{}.{}", role.name(), cursor.extension()));
+                fs::write(&file, cursor.encode()?).with_path(&file)?;
+
+                println!("Converted {} to {}.", path.display(), file.display());
+                files.insert(role, file);
+            }
+
+            add_scheme(&name, &files, &config, print)?;
+        }
+        Command::Preview {
+            files,
+            config,
+            output,
+            size,
+            ansi,
+        } => {
+            let cursors: Vec<PreviewSource> = if files.is_empty() {
+                let config = Config::load(config.as_deref(), None)?;
+                for cursor in &config.cursor {
+                    cursor.check_files()?;
+                }
+                config
+                    .cursor
+                    .iter()
+                    .flat_map(|cursor| {
+                        let source = move |name: String, path: &str| PreviewSource {
+                            name,
+                            path: path.to_string(),
+                            hotspot: cursor.hotspot.map_or((0, 0), |[x, y]| (x, y)),
+                            effects: cursor.effect.clone(),
+                        };
+
+                        once(source(cursor.name.clone(), &cursor.path))
+                            .chain(cursor.variant.iter().map(move |variant| {
+                                source(format!("{}-{}", cursor.name, variant.size), &variant.path)
+                            }))
+                            .chain(cursor.roles.iter().map(move |(role, path)| {
+                                source(format!("{}-{}", cursor.name, role.name()), path)
+                            }))
+                    })
+                    .collect()
+            } else {
+                files
+                    .iter()
+                    .map(|path| PreviewSource {
+                        name: path
+                            .file_stem()
+                            .unwrap_or_default()
+                            .to_string_lossy()
+                            .into_owned(),
+                        path: path.to_string_lossy().into_owned(),
+                        hotspot: (0, 0),
+                        effects: Vec::new(),
+                    })
+                    .collect()
+            };
+
+            fs::create_dir_all(&output).with_path(&output)?;
+
+            for PreviewSource {
+                name,
+                path,
+                hotspot,
+                effects: cursor_effects,
+            } in cursors
+            {
+                let cursor = effects::apply(&CursorData::open(&path, hotspot)?, &cursor_effects)?;
+                let images = preview::preview_images(&cursor, size);
+
+                let file_name: String = name
+                    .chars()
+                    .map(|c| {
+                        if c.is_alphanumeric() || c == '-' {
+                            c
+                        } else {
+                            '_'
+                        }
+                    })
+                    .collect();
+                let sheet_path = output.join(format!("{}.png", file_name));
+                fs::write(&sheet_path, preview::contact_sheet(&images).encode_png()?)
+                    .with_path(&sheet_path)?;
+
+                let description = match &cursor {
+                    CursorData::Static(_) => {
+                        let sizes: Vec<String> = images
+                            .iter()
+                            .map(|image| format!("{}x{}", image.image.width, image.image.height))
+                            .collect();
+                        format!("sizes {}", sizes.join(", "))
+                    }
+                    CursorData::Animated(animation) => format!(
+                        "{} frames, {} steps, {:.2} seconds",
+                        animation.frames.len(),
+                        animation.steps.len(),
+                        animation.duration() as f32 / ani::JIFFIES_PER_SECOND as f32
+                    ),
+                };
+
+                println!(
+                    "{} ({}): {} -> {}",
+                    name,
+                    path,
+                    description,
+                    sheet_path.display()
+                );
+
+                if ansi {
+                    if let Some(image) = images.first() {
+                        println!("{}", preview::ansi(image));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Add a `[[cursor]]` that uses each of `files` for its role to the `config` file,
+/// or just print it if `print` is set.
+fn add_scheme(
+    name: &str,
+    files: &BTreeMap<CursorRole, PathBuf>,
+    config: &Path,
+    print: bool,
+) -> Result<()> {
+    // Paths in the configuration file are relative to its directory, rather than the current one.
+    let current_dir = std::env::current_dir()?;
+    let config_dir = current_dir.join(config.parent().unwrap_or_else(|| Path::new("")));
+    let files: BTreeMap<CursorRole, PathBuf> = files
+        .iter()
+        .map(|(role, path)| {
+            let path = current_dir.join(path);
+            let relative = path.strip_prefix(&config_dir).unwrap_or(&path);
+            (*role, relative.to_path_buf())
+        })
+        .collect();
+
+    let cursor = scheme_definition(name, &files)?;
+    let definition = |cursors: Vec<toml::Value>| {
+        let mut root = toml::value::Table::new();
+        root.insert("cursor".to_string(), toml::Value::Array(cursors));
+        toml::Value::Table(root)
+    };
+
+    if print {
+        print!("{}", toml::to_string(&definition(vec![cursor]))?);
+        return Ok(());
+    }
+
+    if config.exists() {
+        let existing = Config::from_file(config)?;
+        if existing.cursor.iter().any(|cursor| cursor.name == name) {
+            return Err(Error::DuplicateCursorName {
+                name: name.to_string(),
+            });
+        }
+    }
+
+    let format = Format::of(config);
+    if format == Format::Toml {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(config)
+            .with_path(config)?;
+        write!(file, "\n{}", toml::to_string(&definition(vec![cursor]))?)?;
+    } else {
+        // Other formats can't be appended to, so the whole file is written again with the cursor added.
+        let mut document = if config.exists() {
+            let contents = fs::read_to_string(config).with_path(config)?;
+            format.parse(&config.display().to_string(), &contents)?
+        } else {
+            definition(Vec::new())
+        };
+        let cursors = document
+            .as_table_mut()
+            .map(|root| {
+                root.entry("cursor")
+                    .or_insert_with(|| toml::Value::Array(Vec::new()))
+            })
+            .and_then(toml::Value::as_array_mut)
+            .ok_or_else(|| Error::InvalidArgument {
+                reason: format!("{} doesn't have a list of cursors", config.display()),
+            })?;
+        cursors.push(cursor);
+
+        fs::write(config, format.write(&document)?).with_path(config)?;
+    }
+
+    println!(
+        "Added the cursor \"{}\" with {} role(s) to {}.",
+        name,
+        files.len(),
+        config.display()
+    );
+
+    Ok(())
+}
+
+/// Make a `[[cursor]]` table for a scheme. The pointer's file (or the first file, if there's no pointer)
+/// is the cursor's `path`, and every other file is listed in its `roles` table.
+fn scheme_definition(name: &str, files: &BTreeMap<CursorRole, PathBuf>) -> Result<toml::Value> {
+    use toml::value::{Table, Value};
+
+    let path_value = |path: &PathBuf| Value::String(path.to_string_lossy().into_owned());
+
+    let (main_role, main_path) = files
+        .get_key_value(&CursorRole::Pointer)
+        .or_else(|| files.iter().next())
+        .ok_or_else(|| Error::InvalidArgument {
+            reason: "the scheme has no cursor files".to_string(),
+        })?;
+
+    let roles: Table = files
+        .iter()
+        .filter(|(role, _)| *role != main_role)
+        .map(|(role, path)| (role.name().to_string(), path_value(path)))
+        .collect();
+
+    let mut cursor = Table::new();
+    cursor.insert("name".to_string(), Value::String(name.to_string()));
+    cursor.insert("path".to_string(), path_value(main_path));
+    if !roles.is_empty() {
+        cursor.insert("roles".to_string(), Value::Table(roles));
+    }
+
+    Ok(Value::Table(cursor))
+}
+
+/// Send an instruction to the running cursor changer.
+#[cfg(windows)]
+fn send_control(control: &Control) -> Result<()> {
+    crate::window::send_control(control)
+}
+
+/// Send an instruction to the running cursor changer, which only runs on Windows.
+#[cfg(not(windows))]
+fn send_control(_control: &Control) -> Result<()> {
+    Err(Error::NotRunning)
+}
+
+/// Write a merged configuration as TOML, with a comment before each part naming the file it came from.
+fn resolved_config(config: &Config, sources: &Sources) -> Result<String> {
+    use toml::value::{Table, Value};
+
+    let mut text = String::from("# Merged from these files, lowest priority first:\n");
+    for (file, _) in sources.files.iter().filter(|(_, found)| *found) {
+        text.push_str(&format!("#   {}\n", file));
+    }
+
+    let setting = |name: &str, value: Option<String>, source: &Option<String>| match (value, source)
+    {
+        (Some(value), Some(source)) => format!("\n# from {}\n{} = {}\n", source, name, value),
+        _ => format!("\n# {} isn't set\n", name),
+    };
+    text.push_str(&setting(
+        "scale",
+        config.scale.map(|scale| Value::Float(scale).to_string()),
+        &sources.scale,
+    ));
+    text.push_str(&setting(
+        "size",
+        config.size.map(|size| size.to_string()),
+        &sources.size,
+    ));
+    text.push_str(&setting(
+        "active_profile",
+        config
+            .active_profile
+            .as_ref()
+            .map(|name| Value::String(name.get_ref().clone()).to_string()),
+        &sources.active_profile,
+    ));
+
+    // Each table is written on its own, so that it can have its own comment.
+    let table = |key: &str, value: Value| -> String {
+        let mut root = Table::new();
+        root.insert(key.to_string(), Value::Array(vec![value]));
+        Value::Table(root).to_string()
+    };
+
+    for cursor in &config.cursor {
+        text.push_str(&format!("\n# from {}\n", sources.cursors[&cursor.name]));
+        text.push_str(&table("cursor", Value::try_from(cursor)?));
+    }
+    for pool in &config.cursor_pool {
+        text.push_str(&format!("\n# from {}\n", sources.pools[pool.name.get_ref()]));
+        text.push_str(&table("cursor_pool", Value::try_from(pool)?));
+    }
+    for application in &config.application {
+        text.push_str(&format!(
+            "\n# from {}\n",
+            sources.applications[&application.rule()]
+        ));
+        text.push_str(&table("application", Value::try_from(application)?));
+    }
+    let settings = [
+        (
+            "log",
+            config.log.as_ref().map(Value::try_from),
+            &sources.log,
+        ),
+        (
+            "stats",
+            config.stats.as_ref().map(Value::try_from),
+            &sources.stats,
+        ),
+        (
+            "history",
+            config.history.as_ref().map(Value::try_from),
+            &sources.history,
+        ),
+        (
+            "switching",
+            config.switching.as_ref().map(Value::try_from),
+            &sources.switching,
+        ),
+        (
+            "trail",
+            config.trail.as_ref().map(Value::try_from),
+            &sources.trail,
+        ),
+    ];
+    for (key, value, source) in settings {
+        if let (Some(value), Some(source)) = (value, source) {
+            text.push_str(&format!("\n# from {}\n", source));
+
+            let mut root = Table::new();
+            root.insert(key.to_string(), value?);
+            text.push_str(&Value::Table(root).to_string());
+        }
+    }
+    for (name, profile) in &config.profile {
+        text.push_str(&format!("\n# from {}\n", sources.profiles[name]));
+
+        let mut profiles = Table::new();
+        profiles.insert(name.clone(), Value::try_from(profile)?);
+        let mut root = Table::new();
+        root.insert("profile".to_string(), Value::Table(profiles));
+        text.push_str(&Value::Table(root).to_string());
+    }
+
+    Ok(text)
+}
+
+/// Describe the statistics from `first` to `last`: the time spent over each application and with each cursor,
+/// longest first, and (if `daily` is set) each day's totals.
+fn stats_report(stats: &Stats, first: NaiveDate, last: NaiveDate, daily: bool) -> String {
+    let total = stats.total(first, last);
+    let tracked = total.tracked();
+
+    let mut text = format!(
+        "From {} to {}: {} tracked, {} cursor switch(es).\n",
+        first,
+        last,
+        stats::format_duration(tracked),
+        total.switches
+    );
+
+    let mut section = |title: &str, times: &BTreeMap<String, i64>| {
+        let mut times: Vec<(&String, &i64)> = times.iter().collect();
+        times.sort_by_key(|(name, time)| (std::cmp::Reverse(**time), name.as_str()));
+
+        text.push_str(&format!("\n{}:\n", title));
+        for (name, time) in times {
+            let share = match tracked {
+                0 => 0,
+                _ => time * 100 / tracked,
+            };
+            text.push_str(&format!(
+                "  {:>8} {:>3}%  {}\n",
+                stats::format_duration(*time),
+                share,
+                name
+            ));
+        }
+    };
+    section("Applications", &total.applications);
+    section("Cursors", &total.cursors);
+
+    if daily {
+        text.push_str("\nBy day:\n");
+        for (date, day) in stats.days.range(first..=last) {
+            text.push_str(&format!(
+                "  {}  {:>8} tracked, {} switch(es)\n",
+                date,
+                stats::format_duration(day.tracked()),
+                day.switches
+            ));
+        }
+    }
+
+    text
+}
+
+/// Describe a decision on one line, like
+/// `2024-05-01 12:00:00.000  (640, 480)  C:\Windows\explorer.exe  application "explorer.exe"  -> big`.
+fn describe_decision(decision: &Decision) -> String {
+    let target = match (&decision.executable, &decision.error, decision.window) {
+        (Some(executable), _, _) => executable.clone(),
+        (None, Some(error), _) => format!("(unknown application: {})", error),
+        (None, None, Some(window)) => format!("(window {:#x})", window),
+        (None, None, None) => "(no window)".to_string(),
+    };
+    let outcome = match &decision.action {
+        Action::Apply { cursor } => format!("-> {}", cursor),
+        Action::Reset => "-> system cursors".to_string(),
+        Action::Keep => "-> unchanged".to_string(),
+        Action::Wait { cursor: Some(cursor) } => format!("-> waiting to switch to {}", cursor),
+        Action::Wait { cursor: None } => "-> waiting to restore system cursors".to_string(),
+    };
+
+    let mut text = format!(
+        "{}  ({}, {})  {}",
+        decision.time.format("%Y-%m-%d %H:%M:%S%.3f"),
+        decision.position.0,
+        decision.position.1,
+        target
+    );
+    if let Some(rule) = &decision.rule {
+        text.push_str(&format!("  {}", rule));
+    }
+    text.push_str(&format!("  {}", outcome));
+
+    text
+}
+
+/// Parse a hotspot given as "x,y".
+fn parse_hotspot(value: &str) -> Result<(u32, u32)> {
+    let invalid = || Error::InvalidArgument {
+        reason: format!("expected a hotspot like \"4,2\", got \"{}\"", value),
+    };
+
+    let mut parts = value.split(',').map(|part| part.trim().parse::<u32>());
+
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(x)), Some(Ok(y)), None) => Ok((x, y)),
+        _ => Err(invalid()),
+    }
+}
@@ -0,0 +1,241 @@
+//! Conditions on the keys and mouse buttons that are held down, so that an application can use another cursor
+//! while, say, Ctrl is held or a mouse button is down.
+//!
+//! The backend reports what's held on each tick, and an application's `when` table says what must be held for it
+//! to match. Applications are checked in order, so the one with a `when` goes before the one without.
+
+use schemars::JsonSchema;
+
+/// A modifier key. Either of a pair (like the left and right Shift keys) counts.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Modifier {
+    Shift,
+    Ctrl,
+    Alt,
+
+    /// The Windows key.
+    Win,
+}
+
+impl Modifier {
+    pub const ALL: [Modifier; 4] = [
+        Modifier::Shift,
+        Modifier::Ctrl,
+        Modifier::Alt,
+        Modifier::Win,
+    ];
+}
+
+/// A mouse button, as the user sees it (so `left` is the primary button, even if the buttons are swapped).
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Button {
+    Left,
+    Right,
+    Middle,
+
+    /// The first side button, usually "back".
+    X1,
+
+    /// The second side button, usually "forward".
+    X2,
+}
+
+impl Button {
+    pub const ALL: [Button; 5] = [
+        Button::Left,
+        Button::Right,
+        Button::Middle,
+        Button::X1,
+        Button::X2,
+    ];
+}
+
+/// The modifier keys and mouse buttons that are held down.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Held {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modifiers: Vec<Modifier>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub buttons: Vec<Button>,
+}
+
+impl Held {
+    /// Get what's held from whether each of `Modifier::ALL` is down, and whether each physical mouse button is
+    /// down, in the order of `Button::ALL`. If the user `swapped` the buttons, the physical right button is their
+    /// primary one, so it counts as `left`.
+    #[cfg(any(windows, test))]
+    pub fn from_state(modifiers: [bool; 4], mut buttons: [bool; 5], swapped: bool) -> Held {
+        if swapped {
+            buttons.swap(0, 1);
+        }
+
+        Held {
+            modifiers: Modifier::ALL
+                .iter()
+                .zip(modifiers)
+                .filter(|(_, down)| *down)
+                .map(|(modifier, _)| *modifier)
+                .collect(),
+            buttons: Button::ALL
+                .iter()
+                .zip(buttons)
+                .filter(|(_, down)| *down)
+                .map(|(button, _)| *button)
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.modifiers.is_empty() && self.buttons.is_empty()
+    }
+}
+
+/// An application's `when` table: what must be held down for it to match.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, JsonSchema)]
+pub struct Condition {
+    /// Modifier keys that must all be held: `shift`, `ctrl`, `alt`, or `win`. Others may be held too.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modifiers: Vec<Modifier>,
+
+    /// Mouse buttons that must all be down: `left`, `right`, `middle`, `x1`, or `x2`. Others may be down too.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub buttons: Vec<Button>,
+}
+
+impl Condition {
+    /// Whether everything the condition needs is `held`.
+    pub fn holds(&self, held: &Held) -> bool {
+        self.modifiers
+            .iter()
+            .all(|modifier| held.modifiers.contains(modifier))
+            && self
+                .buttons
+                .iter()
+                .all(|button| held.buttons.contains(button))
+    }
+
+    /// Describe the condition, like `while ctrl and left button are held`, or `None` if it always holds.
+    /// Conditions that hold at the same times are described the same way, whatever order they list things in.
+    pub fn describe(&self) -> Option<String> {
+        let names: Vec<String> = Modifier::ALL
+            .iter()
+            .filter(|modifier| self.modifiers.contains(modifier))
+            .map(|modifier| format!("{:?}", modifier).to_lowercase())
+            .chain(
+                Button::ALL
+                    .iter()
+                    .filter(|button| self.buttons.contains(button))
+                    .map(|button| format!("{:?} button", button).to_lowercase()),
+            )
+            .collect();
+
+        match names.len() {
+            0 => None,
+            1 => Some(format!("while {} is held", names[0])),
+            _ => Some(format!("while {} are held", names.join(" and "))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn held(modifiers: &[Modifier], buttons: &[Button]) -> Held {
+        Held {
+            modifiers: modifiers.to_vec(),
+            buttons: buttons.to_vec(),
+        }
+    }
+
+    fn condition(modifiers: &[Modifier], buttons: &[Button]) -> Condition {
+        Condition {
+            modifiers: modifiers.to_vec(),
+            buttons: buttons.to_vec(),
+        }
+    }
+
+    #[test]
+    fn holds_while_everything_it_needs_is_held() {
+        let ctrl_left = condition(&[Modifier::Ctrl], &[Button::Left]);
+
+        assert!(ctrl_left.holds(&held(&[Modifier::Ctrl], &[Button::Left])));
+        assert!(!ctrl_left.holds(&held(&[Modifier::Ctrl], &[])));
+        assert!(!ctrl_left.holds(&held(&[], &[Button::Left])));
+        assert!(!ctrl_left.holds(&held(&[Modifier::Shift], &[Button::Right])));
+
+        // Others may be held too.
+        assert!(ctrl_left.holds(&held(
+            &[Modifier::Shift, Modifier::Ctrl],
+            &[Button::Right, Button::Left]
+        )));
+
+        // An empty condition always holds.
+        assert!(Condition::default().holds(&Held::default()));
+        assert!(Condition::default().holds(&held(&[Modifier::Alt], &[Button::X1])));
+    }
+
+    #[test]
+    fn describes_conditions_in_a_fixed_order() {
+        assert_eq!(Condition::default().describe(), None);
+        assert_eq!(
+            condition(&[Modifier::Win], &[]).describe().as_deref(),
+            Some("while win is held")
+        );
+        assert_eq!(
+            condition(&[], &[Button::X2]).describe().as_deref(),
+            Some("while x2 button is held")
+        );
+
+        let expected = Some("while shift and ctrl and left button and middle button are held");
+        assert_eq!(
+            condition(
+                &[Modifier::Shift, Modifier::Ctrl],
+                &[Button::Left, Button::Middle]
+            )
+            .describe()
+            .as_deref(),
+            expected
+        );
+        assert_eq!(
+            condition(
+                &[Modifier::Ctrl, Modifier::Shift],
+                &[Button::Middle, Button::Left]
+            )
+            .describe()
+            .as_deref(),
+            expected
+        );
+    }
+
+    #[test]
+    fn swaps_the_buttons_back() {
+        let modifiers = [false, true, false, true];
+        let buttons = [true, false, true, false, true];
+
+        assert_eq!(
+            Held::from_state(modifiers, buttons, false),
+            held(
+                &[Modifier::Ctrl, Modifier::Win],
+                &[Button::Left, Button::Middle, Button::X2]
+            )
+        );
+
+        // The physical left button is the secondary one.
+        assert_eq!(
+            Held::from_state(modifiers, buttons, true),
+            held(
+                &[Modifier::Ctrl, Modifier::Win],
+                &[Button::Right, Button::Middle, Button::X2]
+            )
+        );
+        assert_eq!(
+            Held::from_state([false; 4], [false, true, false, false, false], true),
+            held(&[], &[Button::Left])
+        );
+        assert!(Held::from_state([false; 4], [false; 5], true).is_empty());
+    }
+}
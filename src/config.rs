@@ -1,44 +1,1059 @@
-use std::fs::File;
-use std::io::prelude::*;
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
 
-use crate::error;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, ObjectValidation, Schema, SchemaObject};
+use schemars::JsonSchema;
+
+use crate::diagnostic::{self, Location};
+use crate::effects::Effect;
+use crate::error::{self, WithPath};
+use crate::format::Format;
+use crate::history::HistoryConfig;
+use crate::pool::PoolConfig;
+use crate::switching::SwitchingConfig;
+use crate::condition::Condition;
+use crate::pulse::Pulse;
+use crate::trail::TrailConfig;
+use crate::transition::Transition;
+use crate::hotkey::Hotkey;
+use crate::logging::LogConfig;
+use crate::pack;
+use crate::role::CursorRole;
+use crate::spanned::Spanned;
+use crate::stats::StatsConfig;
+use crate::Scaling;
 
 type Result<T> = std::result::Result<T, error::Error>;
 
-#[derive(Deserialize)]
+/// The name of the configuration file, in each of the directories it's looked for in.
+pub const FILE_NAME: &str = "cursor.toml";
+
+/// The name of the configuration file without its extension, which can be any `Format`'s.
+const FILE_STEM: &str = "cursor";
+
+/// The directory under the machine-wide and per-user configuration directories that holds this app's file.
+const APP_DIR: &str = "windows-cursor-changer";
+
+#[derive(Deserialize, Default, JsonSchema)]
 pub struct Config {
+    /// Other configuration files to read before this one, relative to this one's directory.
+    /// Anything this file sets overrides what they set.
+    #[serde(default)]
+    #[schemars(with = "Vec<String>")]
+    pub include: Vec<Spanned<String>>,
+
+    /// Resize every cursor by this factor, relative to the system cursor size.
+    pub scale: Option<f64>,
+
+    /// Resize every cursor to this many pixels (at 100% display scaling).
+    /// This takes precedence over `scale`.
+    pub size: Option<u32>,
+
     /// Map of Cursors' `name` identifiers to the Cursor itself
+    #[serde(default)]
     pub cursor: Vec<Cursor>,
 
+    /// Names that stand for one of several cursors, which can be used wherever a rule names a cursor.
+    #[serde(default)]
+    pub cursor_pool: Vec<PoolConfig>,
+
     /// List of monitored applications
+    #[serde(default)]
     pub application: Vec<Application>,
+
+    /// The profile to start with. Without one, only the top-level `[[application]]` rules are used.
+    #[schemars(with = "Option<String>")]
+    pub active_profile: Option<Spanned<String>>,
+
+    /// Named sets of rules that can be switched between while running, by their names.
+    #[serde(default)]
+    pub profile: BTreeMap<String, Profile>,
+
+    /// Where messages from the running cursor changer are written, and which ones.
+    pub log: Option<LogConfig>,
+
+    /// Whether (and where) usage statistics are recorded.
+    pub stats: Option<StatsConfig>,
+
+    /// How many of the cursor changer's recent decisions are kept, and whether they're written to a journal.
+    pub history: Option<HistoryConfig>,
+
+    /// How long to wait before switching cursors, so that they don't flicker as the pointer moves.
+    pub switching: Option<SwitchingConfig>,
+
+    /// How the trail behind the pointer looks, over applications that have one.
+    pub trail: Option<TrailConfig>,
+
+    /// The directory of the highest-priority file this was read from, where the log file goes by default.
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub dir: PathBuf,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, JsonSchema)]
 pub struct Cursor {
     pub name: String,
-    /// Path to the Cursor's .cur/.ani file.
+    /// Path to the Cursor's .cur/.ani file, or a .png image that will be converted to a cursor.
+    /// This is used whenever none of the `variant` files are large enough.
+    /// It may be left out when the cursor has a `pack` or a `base`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub path: String,
+
+    /// A cursor from a pack archive, as "path/to/pack.zip#cursor". The `#cursor` may be left out if
+    /// the pack only has one cursor. Its files, roles, hotspot, and size variants are used unless
+    /// this cursor sets its own.
+    pub pack: Option<String>,
+
+    /// The name of another cursor to start from. Its files, roles, hotspot, size variants, and scaling
+    /// are used unless this cursor sets its own, and its effects are applied before this cursor's.
+    #[schemars(with = "Option<String>")]
+    pub base: Option<Spanned<String>>,
+
+    /// The "hot" pixel of the cursor, for cursors made from .png images.
+    /// Defaults to the top-left corner.
+    pub hotspot: Option<[u32; 2]>,
+
+    /// Alternative files to use at specific cursor sizes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variant: Vec<CursorVariant>,
+
+    /// Resize this cursor by this factor, overriding the global `scale`.
+    pub scale: Option<f64>,
+
+    /// Resize this cursor to this many pixels (at 100% display scaling),
+    /// overriding the global `scale` and `size`.
+    pub size: Option<u32>,
+
+    /// Files to use for specific system cursor roles (like `text` or `busy`) instead of `path`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    #[schemars(schema_with = "roles_schema")]
+    pub roles: BTreeMap<CursorRole, String>,
+
+    /// Effects (tints, outlines, badges, ...) used to generate a new cursor from this one's files.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub effect: Vec<Effect>,
+
+    /// The paths as they were written in the configuration file, by the path each one resolved to.
+    #[serde(skip)]
+    pub configured_paths: BTreeMap<String, String>,
+
+    /// Where this cursor's `name` is in the configuration file, for pointing out mistakes.
+    #[serde(skip)]
+    pub location: Location,
+}
+
+/// Just the names of a file's cursors, read separately to find where each cursor is in the file.
+#[derive(Deserialize)]
+struct CursorNames {
+    #[serde(default)]
+    cursor: Vec<CursorName>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize)]
+struct CursorName {
+    name: Spanned<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, JsonSchema)]
+pub struct CursorVariant {
+    /// The cursor size, in pixels, that this file was drawn for.
+    pub size: u32,
+
+    /// Path to the .cur/.ani/.png file for this size.
+    pub path: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, JsonSchema)]
 pub struct Application {
     /// The Cursor's name
-    pub cursor: String,
+    #[schemars(with = "String")]
+    pub cursor: Spanned<String>,
 
     /// The file path to the executable
     pub path: String,
+
+    /// What must be held down for this application's cursor to be used, like `{ modifiers = ["ctrl"] }`.
+    /// Applications are checked in order, so put this before the same application without a `when`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub when: Option<Condition>,
+
+    /// How long, in milliseconds, the pointer must stay over this application before its cursor is used.
+    /// This overrides `debounce` in `[switching]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debounce: Option<u64>,
+
+    /// How long, in milliseconds, the pointer must stay away from this application (where no custom cursor is
+    /// wanted) before the system cursors are restored. This overrides `reset_delay` in `[switching]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reset_delay: Option<u64>,
+
+    /// How to move between this application's cursor and others. This overrides `transition` in `[switching]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transition: Option<Transition>,
+
+    /// Show a trail behind the pointer while it's over this application, as the `[trail]` table describes.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub trail: bool,
+
+    /// The pulse to show around the pointer when this application's cursor is activated.
+    /// This overrides `pulse` in `[switching]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pulse: Option<Pulse>,
+}
+
+impl Application {
+    /// Describe the rule this application makes, like `application "cad.exe" while ctrl is held`.
+    /// Applications that match at the same times are described the same way.
+    pub fn rule(&self) -> String {
+        match self.when.as_ref().and_then(Condition::describe) {
+            Some(when) => format!("application \"{}\" {}", self.path, when),
+            None => format!("application \"{}\"", self.path),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, JsonSchema)]
+pub struct Profile {
+    /// The name of the cursor to use when none of the rules match.
+    /// Without one, the Windows cursor is used.
+    #[schemars(with = "Option<String>")]
+    pub default: Option<Spanned<String>>,
+
+    /// A keyboard shortcut, like "ctrl+alt+p", that switches to this profile.
+    pub hotkey: Option<Hotkey>,
+
+    /// This profile's monitored applications, which are checked before the top-level ones.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub application: Vec<Application>,
+}
+
+/// Where each part of a merged configuration came from, as the path of the file that set it.
+#[derive(Debug, Default)]
+pub struct Sources {
+    /// Every file that was looked for, lowest priority first, and whether it was found.
+    pub files: Vec<(String, bool)>,
+
+    pub scale: Option<String>,
+    pub size: Option<String>,
+    pub active_profile: Option<String>,
+    pub log: Option<String>,
+    pub stats: Option<String>,
+    pub history: Option<String>,
+    pub switching: Option<String>,
+    pub trail: Option<String>,
+
+    /// The file each cursor came from, by the cursor's name.
+    pub cursors: BTreeMap<String, String>,
+
+    /// The file each cursor pool came from, by the pool's name.
+    pub pools: BTreeMap<String, String>,
+
+    /// The file each application came from, by the application's `rule`.
+    pub applications: BTreeMap<String, String>,
+
+    /// The file each profile came from, by the profile's name.
+    pub profiles: BTreeMap<String, String>,
 }
 
 
+impl Cursor {
+    /// Get the paths of all files used by this Cursor, including every size variant and role,
+    /// and any images its effects use.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.path.as_str())
+            .chain(self.variant.iter().map(|v| v.path.as_str()))
+            .chain(self.roles.values().map(String::as_str))
+            .chain(self.effect.iter().filter_map(Effect::path))
+    }
+
+    /// Check that every file used by this cursor exists.
+    pub fn check_files(&self) -> Result<()> {
+        match self.paths().find(|path| !Path::new(path).exists()) {
+            Some(path) => Err(self.location.wrap(error::Error::MissingCursorFileError {
+                name: self.name.clone(),
+                path: self.configured_path(path).to_string(),
+                resolved: path.to_string(),
+            })),
+            None => Ok(()),
+        }
+    }
+
+    /// Get a resolved path as it was written in the configuration file.
+    pub fn configured_path<'a>(&'a self, path: &'a str) -> &'a str {
+        self.configured_paths
+            .get(path)
+            .map(String::as_str)
+            .unwrap_or(path)
+    }
+
+    /// Get how this cursor should be resized, if at all.
+    pub fn scaling(&self) -> Option<Scaling> {
+        scaling(self.scale, self.size)
+    }
+}
+
 impl Config {
+    /// Get how all cursors should be resized, unless they specify otherwise.
+    pub fn scaling(&self) -> Option<Scaling> {
+        scaling(self.scale, self.size)
+    }
+
+    /// Get a JSON Schema of the configuration file, as pretty-printed JSON.
+    pub fn schema() -> Result<String> {
+        let mut schema = schemars::schema_for!(Config);
+        schema.schema.metadata().title = Some("Windows Cursor Changer configuration".to_string());
+
+        Ok(serde_json::to_string_pretty(&schema)?)
+    }
+
+    /// Read a configuration file and the files it includes. Relative paths in each file are resolved
+    /// from that file's directory.
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Config> {
-        let mut file = File::open(path)?;
-        let mut contents = String::new();
+        let mut config = Config::default();
+        let mut sources = Sources::default();
+        for (source, layer) in Config::read_with_includes(path.as_ref(), None, &mut Vec::new())? {
+            config.merge(layer, &source, &mut sources);
+        }
+
+        config.check_names(&sources)?;
+        config.resolve()?;
+
+        Ok(config)
+    }
+
+    /// Read the configuration from every layer, lowest priority first: the built-in defaults,
+    /// the machine-wide file, the user's file, and `explicit` (or `cursor.toml` in the current directory,
+    /// if there is one). `format` is the format of `explicit`, if it can't be told from the file's extension.
+    pub fn load(explicit: Option<&Path>, format: Option<Format>) -> Result<Config> {
+        let (mut config, _) = Config::load_layers(explicit, format)?;
+        config.resolve()?;
+
+        Ok(config)
+    }
+
+    /// Merge every layer of the configuration (see `load`), keeping track of where each part came from.
+    /// Packs and bases are left as they are written.
+    pub fn load_layers(explicit: Option<&Path>, format: Option<Format>) -> Result<(Config, Sources)> {
+        let mut config = Config::default();
+        let mut sources = Sources::default();
+
+        let mut files: Vec<(PathBuf, Option<Format>)> = shared_config_paths()
+            .into_iter()
+            .map(|path| (path, None))
+            .collect();
+        match explicit {
+            Some(path) => {
+                if !path.is_file() {
+                    return Err(error::Error::MissingConfigFile {
+                        path: path.display().to_string(),
+                    });
+                }
+                files.push((path.to_path_buf(), format));
+            }
+            None => files.push((config_file_in(Path::new("")), None)),
+        }
+
+        for (file, format) in files {
+            if !file.is_file() {
+                sources.files.push((file.display().to_string(), false));
+                continue;
+            }
+
+            for (source, layer) in Config::read_with_includes(&file, format, &mut Vec::new())? {
+                sources.files.push((source.clone(), true));
+                config.merge(layer, &source, &mut sources);
+            }
+        }
+
+        config.check_names(&sources)?;
+
+        Ok((config, sources))
+    }
+
+    /// Read a file and everything it includes (recursively), returning each one with its path,
+    /// in the order they should be merged. The file is read as `format`, or by its extension if that's `None`.
+    /// `chain` holds the files that are already being read, to catch cycles.
+    fn read_with_includes(
+        path: &Path,
+        format: Option<Format>,
+        chain: &mut Vec<PathBuf>,
+    ) -> Result<Vec<(String, Config)>> {
+        if !path.is_file() {
+            return Err(error::Error::MissingConfigFile {
+                path: path.display().to_string(),
+            });
+        }
+
+        let canonical = fs::canonicalize(path).with_path(path)?;
+        if chain.contains(&canonical) {
+            return Err(error::Error::ConfigIncludeCycle {
+                path: path.display().to_string(),
+            });
+        }
+        chain.push(canonical);
+
+        let contents = fs::read_to_string(path).with_path(path)?;
+
+        let dir = env::current_dir()?.join(path.parent().unwrap_or_else(|| Path::new("")));
+
+        let source = path.display().to_string();
+        debug!("Reading the configuration file {}.", source);
+
+        // Values read from other formats don't keep their positions, so later errors about them don't show one.
+        let format = format.unwrap_or_else(|| Format::of(path));
+        let mut config: Config = format.read(&source, &contents)?;
+        let names: CursorNames = format.read(&source, &contents)?;
+        for (cursor, name) in config.cursor.iter_mut().zip(names.cursor) {
+            cursor.location = Location::of(&source, &name.name);
+        }
+
+        config.resolve_paths(&dir)?;
+
+        let mut layers = Vec::new();
+        for include in &config.include {
+            let location = Location::of(&source, include);
+            let included = resolve_path(include.get_ref(), &dir)
+                .and_then(|included| Config::read_with_includes(Path::new(&included), None, chain))
+                .map_err(|e| location.wrap(e))?;
+            layers.extend(included);
+        }
+        layers.push((source, config));
+
+        chain.pop();
 
-        file.read_to_string(&mut contents)?;
+        Ok(layers)
+    }
+
+    /// Merge a higher-priority `layer`, read from `source`, into this configuration.
+    ///
+    /// Its settings replace these ones, and its cursors, cursor pools, and profiles replace any of these with the same name.
+    /// Its applications are checked before these, and replace any of these with the same path and `when` condition.
+    fn merge(&mut self, layer: Config, source: &str, sources: &mut Sources) {
+        if layer.scale.is_some() {
+            self.scale = layer.scale;
+            sources.scale = Some(source.to_string());
+        }
+        if layer.size.is_some() {
+            self.size = layer.size;
+            sources.size = Some(source.to_string());
+        }
+        if layer.active_profile.is_some() {
+            self.active_profile = layer.active_profile;
+            sources.active_profile = Some(source.to_string());
+        }
+        if layer.log.is_some() {
+            self.log = layer.log;
+            sources.log = Some(source.to_string());
+        }
+        if layer.stats.is_some() {
+            self.stats = layer.stats;
+            sources.stats = Some(source.to_string());
+        }
+        if layer.history.is_some() {
+            self.history = layer.history;
+            sources.history = Some(source.to_string());
+        }
+        if layer.switching.is_some() {
+            self.switching = layer.switching;
+            sources.switching = Some(source.to_string());
+        }
+        if layer.trail.is_some() {
+            self.trail = layer.trail;
+            sources.trail = Some(source.to_string());
+        }
+        self.dir = layer.dir;
+        for (name, profile) in layer.profile {
+            sources.profiles.insert(name.clone(), source.to_string());
+            self.profile.insert(name, profile);
+        }
+
+        // Only cursors from earlier layers are replaced, so duplicates within a file are still caught later.
+        let earlier = self.cursor.len();
+        for cursor in layer.cursor {
+            sources
+                .cursors
+                .insert(cursor.name.clone(), source.to_string());
+            match self.cursor[..earlier]
+                .iter_mut()
+                .find(|existing| existing.name == cursor.name)
+            {
+                Some(existing) => *existing = cursor,
+                None => self.cursor.push(cursor),
+            }
+        }
+
+        let earlier = self.cursor_pool.len();
+        for pool in layer.cursor_pool {
+            let name = pool.name.get_ref().clone();
+            sources.pools.insert(name.clone(), source.to_string());
+            match self.cursor_pool[..earlier]
+                .iter_mut()
+                .find(|existing| *existing.name.get_ref() == name)
+            {
+                Some(existing) => *existing = pool,
+                None => self.cursor_pool.push(pool),
+            }
+        }
+
+        let rules: Vec<String> = layer.application.iter().map(Application::rule).collect();
+        for rule in &rules {
+            sources
+                .applications
+                .insert(rule.clone(), source.to_string());
+        }
+
+        let earlier = std::mem::replace(&mut self.application, layer.application);
+        self.application.extend(
+            earlier
+                .into_iter()
+                .filter(|application| !rules.contains(&application.rule())),
+        );
+    }
+
+    /// Fill in everything cursors take from their packs and bases.
+    fn resolve(&mut self) -> Result<()> {
+        self.resolve_packs()?;
+        self.resolve_bases()
+    }
+
+    /// Check that cursor names are unique, and that every cursor and profile that's referred to exists.
+    fn check_names(&self, sources: &Sources) -> Result<()> {
+        let cursor_names = || self.cursor.iter().map(|cursor| cursor.name.as_str());
+        let pool_names = || self.cursor_pool.iter().map(|pool| pool.name.get_ref().as_str());
+
+        // `user` describes what refers to the cursor, like `the application "Code.exe"`.
+        // Rules can use a cursor pool in place of a cursor, if `pools` is set.
+        let check_cursor = |file: &str, name: &Spanned<String>, user: String, pools: bool| -> Result<()> {
+            let names = || cursor_names().chain(pool_names().filter(move |_| pools));
+            let name_str = name.get_ref().as_str();
+            if names().any(|cursor| cursor == name_str) {
+                return Ok(());
+            }
+
+            let message = format!("{} uses the cursor \"{}\", which isn't defined", user, name_str);
+            let help = diagnostic::did_you_mean(name_str, names());
+            Err(error::Error::Config(
+                Location::of(file, name).diagnostic(message).with_help(help),
+            ))
+        };
+
+        for (i, cursor) in self.cursor.iter().enumerate() {
+            if self.cursor[..i].iter().any(|earlier| earlier.name == cursor.name) {
+                return Err(cursor.location.wrap(error::Error::DuplicateCursorName {
+                    name: cursor.name.clone(),
+                }));
+            }
+
+            if let Some(base) = &cursor.base {
+                let user = format!("the cursor \"{}\"", cursor.name);
+                check_cursor(&cursor.location.file, base, user, false)?;
+            }
+        }
+
+        for (i, pool) in self.cursor_pool.iter().enumerate() {
+            let name = pool.name.get_ref();
+            let file = &sources.pools[name];
+            let taken = if self.cursor_pool[..i].iter().any(|earlier| earlier.name.get_ref() == name) {
+                Some("another cursor pool")
+            } else if cursor_names().any(|cursor| cursor == name) {
+                Some("a cursor")
+            } else {
+                None
+            };
+            if let Some(taken) = taken {
+                let message = format!("There is already {} named \"{}\"", taken, name);
+                return Err(error::Error::Config(Location::of(file, &pool.name).diagnostic(message)));
+            }
+
+            if pool.cursors.is_empty() {
+                let message = format!("The cursor pool \"{}\" has no cursors to pick from", name);
+                return Err(error::Error::Config(Location::of(file, &pool.name).diagnostic(message)));
+            }
+            for cursor in &pool.cursors {
+                let user = format!("the cursor pool \"{}\"", name);
+                check_cursor(file, cursor, user, false)?;
+            }
+        }
+
+        for application in &self.application {
+            let file = &sources.applications[&application.rule()];
+            let user = format!("the {}", application.rule());
+            check_cursor(file, &application.cursor, user, true)?;
+        }
+
+        for (name, profile) in &self.profile {
+            let file = &sources.profiles[name];
+            for application in &profile.application {
+                let user = format!(
+                    "the application \"{}\" in the profile \"{}\"",
+                    application.path, name
+                );
+                check_cursor(file, &application.cursor, user, true)?;
+            }
+            if let Some(default) = &profile.default {
+                check_cursor(file, default, format!("the profile \"{}\"", name), true)?;
+            }
+        }
+
+        if let Some(active) = &self.active_profile {
+            if !self.profile.contains_key(active.get_ref()) {
+                let file = sources.active_profile.as_deref().unwrap_or_default();
+                let error = error::Error::UnknownProfile {
+                    name: active.get_ref().clone(),
+                };
+                let help = diagnostic::did_you_mean(
+                    active.get_ref(),
+                    self.profile.keys().map(String::as_str),
+                );
+                return Err(error::Error::Config(
+                    Location::of(file, active)
+                        .diagnostic(error.to_string())
+                        .with_help(help),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Expand environment variables and `~` in every cursor's paths, and make them relative to `dir`.
+    fn resolve_paths(&mut self, dir: &Path) -> Result<()> {
+        for cursor in &mut self.cursor {
+            Config::resolve_cursor_paths(cursor, dir).map_err(|e| cursor.location.wrap(e))?;
+        }
+
+        if let Some(file) = self.log.as_mut().and_then(|log| log.file.as_mut()) {
+            *file = resolve_path(file, dir)?;
+        }
+        if let Some(file) = self.stats.as_mut().and_then(|stats| stats.file.as_mut()) {
+            *file = resolve_path(file, dir)?;
+        }
+        if let Some(file) = self.history.as_mut().and_then(|history| history.file.as_mut()) {
+            *file = resolve_path(file, dir)?;
+        }
+
+        let applications = self
+            .application
+            .iter_mut()
+            .chain(self.profile.values_mut().flat_map(|profile| &mut profile.application));
+        let transitions = applications
+            .filter_map(|application| application.transition.as_mut())
+            .chain(self.switching.as_mut().and_then(|switching| switching.transition.as_mut()));
+        for path in transitions.filter_map(Transition::path_mut) {
+            *path = resolve_path(path, dir)?;
+        }
+        self.dir = dir.to_path_buf();
+
+        Ok(())
+    }
+
+    /// Expand environment variables and `~` in a cursor's paths, and make them relative to `dir`.
+    fn resolve_cursor_paths(cursor: &mut Cursor, dir: &Path) -> Result<()> {
+        let mut configured = BTreeMap::new();
+        let mut resolve = |path: &mut String| -> Result<()> {
+            if !path.is_empty() {
+                let resolved = resolve_path(path, dir)?;
+                configured.insert(resolved.clone(), path.clone());
+                *path = resolved;
+            }
+            Ok(())
+        };
+
+        resolve(&mut cursor.path)?;
+        for variant in &mut cursor.variant {
+            resolve(&mut variant.path)?;
+        }
+        for path in cursor.roles.values_mut() {
+            resolve(path)?;
+        }
+        for path in cursor.effect.iter_mut().filter_map(Effect::path_mut) {
+            resolve(path)?;
+        }
+
+        // Only the archive's path is resolved, not the name of the cursor in it.
+        if let Some(reference) = &mut cursor.pack {
+            let (mut path, name) = match reference.rsplit_once('#') {
+                Some((path, name)) => (path.to_string(), Some(name.to_string())),
+                None => (reference.clone(), None),
+            };
+            resolve(&mut path)?;
+            *reference = match name {
+                Some(name) => format!("{}#{}", path, name),
+                None => path,
+            };
+        }
+
+        cursor.configured_paths = configured;
+
+        Ok(())
+    }
+
+    /// Extract the cursors that come from packs, and fill in what they take from them.
+    fn resolve_packs(&mut self) -> Result<()> {
+        for cursor in &mut self.cursor {
+            let reference = match &cursor.pack {
+                Some(reference) => reference,
+                None => continue,
+            };
+
+            let packed = pack::resolve(reference).map_err(|e| cursor.location.wrap(e))?;
+
+            if cursor.path.is_empty() {
+                cursor.path = packed.path;
+                cursor.variant = packed.variant;
+            }
+            for (role, path) in packed.roles {
+                cursor.roles.entry(role).or_insert(path);
+            }
+            cursor.hotspot = cursor.hotspot.or(packed.hotspot);
+        }
+
+        Ok(())
+    }
+
+    /// Fill in everything that cursors with a `base` inherit from it.
+    fn resolve_bases(&mut self) -> Result<()> {
+        let resolved = self
+            .cursor
+            .iter()
+            .map(|cursor| self.resolve_base(cursor, &mut Vec::new()))
+            .collect::<Result<Vec<Cursor>>>()?;
+
+        self.cursor = resolved;
+
+        Ok(())
+    }
+
+    /// Get `cursor` with everything it inherits from its base (and its base's base, and so on) filled in.
+    /// `chain` holds the names of the cursors that are already being resolved, to catch cycles.
+    fn resolve_base<'a>(&'a self, cursor: &'a Cursor, chain: &mut Vec<&'a str>) -> Result<Cursor> {
+        let base_name = match &cursor.base {
+            Some(base_name) => base_name,
+            None if cursor.path.is_empty() => {
+                return Err(cursor.location.wrap(error::Error::MissingCursorPath {
+                    name: cursor.name.clone(),
+                }))
+            }
+            None => return Ok(cursor.clone()),
+        };
+
+        if chain.contains(&cursor.name.as_str()) {
+            return Err(cursor.location.wrap(error::Error::CursorBaseCycle {
+                name: cursor.name.clone(),
+            }));
+        }
+        chain.push(&cursor.name);
+
+        let base = self
+            .cursor
+            .iter()
+            .find(|c| &c.name == base_name.get_ref())
+            .ok_or_else(|| error::Error::MissingCursorNameError {
+                name: base_name.get_ref().clone(),
+            })?;
+        let base = self.resolve_base(base, chain)?;
+
+        let mut resolved = cursor.clone();
+        if resolved.path.is_empty() {
+            resolved.path = base.path;
+            resolved.variant = base.variant;
+        }
+        for (role, path) in base.roles {
+            resolved.roles.entry(role).or_insert(path);
+        }
+        resolved.hotspot = resolved.hotspot.or(base.hotspot);
+        if resolved.scale.is_none() && resolved.size.is_none() {
+            resolved.scale = base.scale;
+            resolved.size = base.size;
+        }
+        resolved.effect = base.effect.into_iter().chain(resolved.effect).collect();
+        for (path, configured) in base.configured_paths {
+            resolved.configured_paths.entry(path).or_insert(configured);
+        }
+
+        Ok(resolved)
+    }
+}
+
+/// The schema of a cursor's `roles` table, which has a file for any of the roles.
+fn roles_schema(gen: &mut SchemaGenerator) -> Schema {
+    let properties = CursorRole::ALL
+        .iter()
+        .map(|role| (role.name().to_string(), gen.subschema_for::<String>()))
+        .collect();
+
+    SchemaObject {
+        instance_type: Some(InstanceType::Object.into()),
+        object: Some(Box::new(ObjectValidation {
+            properties,
+            additional_properties: Some(Box::new(Schema::Bool(false))),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
+fn scaling(scale: Option<f64>, size: Option<u32>) -> Option<Scaling> {
+    size.map(Scaling::Size).or_else(|| scale.map(Scaling::Factor))
+}
+
+/// Resolve a path from the configuration file: expand environment variables and `~`,
+/// then join it onto `dir` if it's relative.
+fn resolve_path(path: &str, dir: &Path) -> Result<String> {
+    let expanded = PathBuf::from(expand_path(path)?);
+
+    Ok(dir.join(expanded).to_string_lossy().into_owned())
+}
+
+/// Expand a leading `~` to the user's home directory, and environment variables written as `%NAME%`, `${NAME}`,
+/// or `$NAME`. A `$NAME` that isn't set is kept as it is, like any other `$` (as in `cursor$1.cur`).
+pub fn expand_path(path: &str) -> Result<String> {
+    let unknown = |name: &str| error::Error::UnknownEnvironmentVariable {
+        name: name.to_string(),
+        path: path.to_string(),
+    };
+    let var = |name: &str| std::env::var(name).map_err(|_| unknown(name));
+
+    let mut expanded = String::new();
+    let mut rest = path;
+
+    if rest == "~" || rest.starts_with("~/") || rest.starts_with("~\\") {
+        let home = std::env::var("USERPROFILE")
+            .or_else(|_| std::env::var("HOME"))
+            .map_err(|_| unknown("USERPROFILE"))?;
+        expanded.push_str(&home);
+        rest = &rest[1..];
+    }
+
+    while let Some(start) = rest.find(['%', '$']) {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+
+        let (name, remainder) = if rest[start..].starts_with('%') {
+            match after.find('%') {
+                Some(end) if end > 0 => (&after[..end], &after[end + 1..]),
+                // A lone `%` is kept as it is.
+                _ => ("", after),
+            }
+        } else if let Some((braced, end)) = after
+            .strip_prefix('{')
+            .and_then(|braced| Some((braced, braced.find('}')?)))
+        {
+            (&braced[..end], &braced[end + 1..])
+        } else {
+            // A bare `$NAME` is only expanded if it's set, so that a `$` in a file name is kept as it is.
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            let name = &after[..end];
+            let identifier = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_');
+            match std::env::var(name) {
+                Ok(value) if identifier => {
+                    expanded.push_str(&value);
+                    rest = &after[end..];
+                    continue;
+                }
+                _ => ("", after),
+            }
+        };
+
+        if name.is_empty() {
+            expanded.push_str(&rest[start..start + 1]);
+        } else {
+            expanded.push_str(&var(name)?);
+        }
+        rest = remainder;
+    }
+    expanded.push_str(rest);
+
+    Ok(expanded)
+}
+
+/// The machine-wide and per-user configuration files, which are read (when they exist) before any other.
+pub fn shared_config_paths() -> Vec<PathBuf> {
+    let (machine, user) = if cfg!(windows) {
+        (env::var_os("PROGRAMDATA"), env::var_os("APPDATA"))
+    } else {
+        let user = env::var_os("XDG_CONFIG_HOME").or_else(|| {
+            env::var_os("HOME").map(|home| Path::new(&home).join(".config").into_os_string())
+        });
+        (Some("/etc".into()), user)
+    };
+
+    machine
+        .into_iter()
+        .chain(user)
+        .map(|dir| config_file_in(&Path::new(&dir).join(APP_DIR)))
+        .collect()
+}
+
+/// The configuration file in `dir`: the first of `cursor.toml`, `cursor.json`, and `cursor.yaml` that exists,
+/// or `cursor.toml` if none do.
+fn config_file_in(dir: &Path) -> PathBuf {
+    Format::ALL
+        .iter()
+        .map(|format| dir.join(FILE_STEM).with_extension(format.extension()))
+        .chain(std::iter::once(dir.join(FILE_STEM).with_extension("yml")))
+        .find(|path| path.is_file())
+        .unwrap_or_else(|| dir.join(FILE_NAME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn expands_environment_variables() {
+        env::set_var("CURSOR_CHANGER_TEST_DIR", "C:/Cursors");
+
+        assert_eq!(
+            expand_path("%CURSOR_CHANGER_TEST_DIR%/a.cur").unwrap(),
+            "C:/Cursors/a.cur"
+        );
+        assert_eq!(
+            expand_path("${CURSOR_CHANGER_TEST_DIR}/b.cur").unwrap(),
+            "C:/Cursors/b.cur"
+        );
+    }
+
+    #[test]
+    fn keeps_dollar_signs_in_file_names() {
+        env::remove_var("CURSOR_CHANGER_TEST_UNSET");
+
+        for path in &[
+            "cursor$1.cur",
+            "a$",
+            "$",
+            "$-x.cur",
+            "${unclosed.cur",
+            "100% done.cur",
+            "$CURSOR_CHANGER_TEST_UNSET/a.cur",
+        ] {
+            assert_eq!(expand_path(path).unwrap(), *path);
+        }
+    }
+
+    #[test]
+    fn expands_bare_variables_that_are_set() {
+        env::set_var("CURSOR_CHANGER_TEST_HOME", "/home/jo");
+
+        assert_eq!(
+            expand_path("$CURSOR_CHANGER_TEST_HOME/a.cur").unwrap(),
+            "/home/jo/a.cur"
+        );
+        assert_eq!(
+            expand_path("$CURSOR_CHANGER_TEST_HOME$1.cur").unwrap(),
+            "/home/jo$1.cur"
+        );
+        assert_eq!(
+            expand_path("$HOME/a.cur").unwrap(),
+            format!("{}/a.cur", env::var("HOME").unwrap())
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_variables() {
+        assert!(expand_path("%CURSOR_CHANGER_TEST_UNSET%/a.cur").is_err());
+        assert!(expand_path("${CURSOR_CHANGER_TEST_UNSET}/a.cur").is_err());
+    }
+
+    #[test]
+    fn merges_applications_by_path_and_condition() {
+        let lower: Config = toml::from_str(
+            r#"
+            [[application]]
+            path = "cad.exe"
+            cursor = "cross"
+            when = { modifiers = ["ctrl", "shift"] }
+
+            [[application]]
+            path = "cad.exe"
+            cursor = "arrow"
+            "#,
+        )
+        .unwrap();
+        let upper: Config = toml::from_str(
+            r#"
+            [[application]]
+            path = "cad.exe"
+            cursor = "hand"
+            "#,
+        )
+        .unwrap();
+
+        let mut config = Config::default();
+        let mut sources = Sources::default();
+        config.merge(lower, "lower.toml", &mut sources);
+        config.merge(upper, "upper.toml", &mut sources);
+
+        let cursors: Vec<_> = config
+            .application
+            .iter()
+            .map(|application| application.cursor.get_ref().as_str())
+            .collect();
+        assert_eq!(cursors, ["hand", "cross"]);
+        assert_eq!(
+            sources.applications[r#"application "cad.exe" while shift and ctrl are held"#],
+            "lower.toml"
+        );
+        assert_eq!(sources.applications[r#"application "cad.exe""#], "upper.toml");
+    }
+
+    #[test]
+    fn points_at_mistakes_in_toml_files_only() {
+        let dir = env::temp_dir().join(format!("cursor-changer-config-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let arrow = testing::path("resample/arrow.cur").display().to_string();
+        let toml = dir.join("cursor.toml");
+        let json = dir.join("cursor.json");
+        fs::write(
+            &toml,
+            format!(
+                "[[cursor]]\nname = \"arrow\"\npath = '{}'\n\n[[application]]\npath = \"Code.exe\"\ncursor = \"arow\"\n",
+                arrow
+            ),
+        )
+        .unwrap();
+        fs::write(
+            &json,
+            serde_json::json!({
+                "cursor": [{ "name": "arrow", "path": arrow }],
+                "application": [{ "path": "Code.exe", "cursor": "arow" }],
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let toml_error = Config::from_file(&toml).err().unwrap().to_string();
+        let json_error = Config::from_file(&json).err().unwrap().to_string();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let message = r#"the application "Code.exe" uses the cursor "arow", which isn't defined"#;
+        assert_eq!(
+            toml_error,
+            format!(
+                "{}:7:10: {}\n  |\n7 | cursor = \"arow\"\n  |          ^^^^^^\n  = help: did you mean \"arrow\"?",
+                toml.display(),
+                message
+            )
+        );
+        assert_eq!(
+            json_error,
+            format!(
+                "{}: {}\n  = help: did you mean \"arrow\"?",
+                json.display(),
+                message
+            )
+        );
+    }
 
-        Ok(toml::from_str(&contents)?)
+    #[test]
+    fn schema_is_up_to_date() {
+        testing::check_text("cursor.schema.json", &(Config::schema().unwrap() + "\n"));
     }
 }
@@ -0,0 +1,56 @@
+//! Instructions for a running cursor changer, from hotkeys or from other processes (like the `profile switch` command).
+//!
+//! Other processes send them to the cursor changer's window as text, one instruction per message,
+//! and wait for them to be carried out.
+
+#[cfg(windows)]
+use std::sync::mpsc::Sender;
+
+/// An instruction for the running cursor changer.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Control {
+    /// Switch to the named profile, or leave the active profile when there's no name.
+    SwitchProfile(Option<String>),
+
+    /// Save the usage statistics now, so that they're up to date for the `stats` command.
+    SaveStats,
+
+    /// Write the recent decisions to the history file, for the `history` command.
+    SaveHistory,
+}
+
+/// An instruction on its way to the cursor-checking thread.
+#[cfg(windows)]
+pub struct Request {
+    pub control: Control,
+
+    /// Told when the instruction has been carried out, if the sender is waiting for that.
+    pub done: Option<Sender<()>>,
+}
+
+impl Control {
+    /// Write this instruction as a message for another process.
+    pub fn encode(&self) -> String {
+        match self {
+            Control::SwitchProfile(Some(name)) => format!("profile {}", name),
+            Control::SwitchProfile(None) => "profile".to_string(),
+            Control::SaveStats => "save-stats".to_string(),
+            Control::SaveHistory => "save-history".to_string(),
+        }
+    }
+
+    /// Read an instruction from another process's message. Returns `None` for messages that aren't understood.
+    pub fn decode(message: &str) -> Option<Control> {
+        let (command, argument) = match message.split_once(' ') {
+            Some((command, argument)) => (command, Some(argument)),
+            None => (message, None),
+        };
+
+        match command {
+            "profile" => Some(Control::SwitchProfile(argument.map(str::to_string))),
+            "save-stats" => Some(Control::SaveStats),
+            "save-history" => Some(Control::SaveHistory),
+            _ => None,
+        }
+    }
+}
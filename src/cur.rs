@@ -0,0 +1,466 @@
+//! Reading and writing Windows .cur (and .ico) files.
+//!
+//! See: https://docs.microsoft.com/en-us/previous-versions/ms997538(v=msdn.10)
+
+use std::fs;
+use std::path::Path;
+
+use crate::error::{Error, WithPath};
+use crate::image::Image;
+use crate::Result;
+
+/// The `type` value of the file header for icons.
+const TYPE_ICON: u16 = 1;
+
+/// The `type` value of the file header for cursors.
+const TYPE_CURSOR: u16 = 2;
+
+/// The size of the file header, and of each image's directory entry.
+const HEADER_SIZE: usize = 6;
+const ENTRY_SIZE: usize = 16;
+
+/// Images embedded as PNG (supported since Windows Vista) start with this signature.
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// The largest image that can be described by a directory entry.
+pub const MAX_SIZE: u32 = 256;
+
+/// A single image from a cursor file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CursorImage {
+    pub image: Image,
+
+    /// The "hot" pixel of the cursor, which is where the click happens.
+    pub hotspot: (u32, u32),
+}
+
+/// The contents of a .cur file: one or more images of different sizes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CursorFile {
+    pub images: Vec<CursorImage>,
+}
+
+impl CursorImage {
+    /// Resample this image to a new size, scaling the hotspot to match.
+    pub fn resize(&self, width: u32, height: u32) -> CursorImage {
+        let scale = |value: u32, from: u32, to: u32| -> u32 {
+            let scaled =
+                ((f64::from(value) + 0.5) * f64::from(to) / f64::from(from)).floor() as u32;
+            scaled.min(to - 1)
+        };
+
+        CursorImage {
+            image: self.image.resize(width, height),
+            hotspot: (
+                scale(self.hotspot.0, self.image.width, width),
+                scale(self.hotspot.1, self.image.height, height),
+            ),
+        }
+    }
+
+    /// Resample this image so that its larger side is `size` pixels, keeping its aspect ratio.
+    pub fn scale_to(&self, size: u32) -> CursorImage {
+        let longest = self.image.width.max(self.image.height);
+        let scale = |value: u32| -> u32 {
+            ((f64::from(value) * f64::from(size) / f64::from(longest)).round() as u32).max(1)
+        };
+
+        self.resize(scale(self.image.width), scale(self.image.height))
+    }
+}
+
+impl CursorFile {
+    /// Build a cursor from images of the same graphic at different sizes.
+    ///
+    /// The `hotspot` is given in pixels of the largest image, and is scaled to match the others.
+    pub fn from_images(images: Vec<Image>, hotspot: (u32, u32)) -> Result<CursorFile> {
+        let largest = images
+            .iter()
+            .max_by_key(|image| image.width.max(image.height))
+            .ok_or_else(|| invalid("a cursor needs at least one image"))?;
+
+        if hotspot.0 >= largest.width || hotspot.1 >= largest.height {
+            return Err(invalid(&format!(
+                "the hotspot ({}, {}) is outside of the {}x{} image",
+                hotspot.0, hotspot.1, largest.width, largest.height
+            )));
+        }
+
+        let (largest_width, largest_height) = (largest.width, largest.height);
+
+        let images = images
+            .into_iter()
+            .map(|image| CursorImage {
+                hotspot: (
+                    hotspot.0 * image.width / largest_width,
+                    hotspot.1 * image.height / largest_height,
+                ),
+                image,
+            })
+            .collect();
+
+        Ok(CursorFile { images })
+    }
+
+    /// Build a cursor from PNG files of the same graphic at different sizes.
+    /// See `from_images` for how the `hotspot` is applied.
+    pub fn from_pngs<P: AsRef<Path>>(paths: &[P], hotspot: (u32, u32)) -> Result<CursorFile> {
+        let images = paths
+            .iter()
+            .map(|path| Image::decode_png(&fs::read(path).with_path(path)?))
+            .collect::<Result<Vec<_>>>()?;
+
+        CursorFile::from_images(images, hotspot)
+    }
+
+    /// Create a cursor containing one image for each of `sizes`,
+    /// each resampled from the best-fitting image of this cursor.
+    pub fn resampled(&self, sizes: &[u32]) -> CursorFile {
+        let images = sizes
+            .iter()
+            .filter_map(|&size| self.best_image(size).map(|image| image.scale_to(size)))
+            .collect();
+
+        CursorFile { images }
+    }
+
+    /// Encode and write this cursor to a .cur file at `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        fs::write(&path, self.encode()?).with_path(&path)?;
+
+        Ok(())
+    }
+
+    /// Get the image best suited to being shown at `size` pixels:
+    /// the smallest one that is at least that large, or else the largest one.
+    pub fn best_image(&self, size: u32) -> Option<&CursorImage> {
+        let longest = |image: &&CursorImage| image.image.width.max(image.image.height);
+
+        self.images
+            .iter()
+            .filter(|image| longest(image) >= size)
+            .min_by_key(longest)
+            .or_else(|| self.images.iter().max_by_key(longest))
+    }
+
+    /// Decode the contents of a .cur or .ico file.
+    /// Icons don't have a hotspot, so theirs is placed at the top-left corner.
+    pub fn decode(bytes: &[u8]) -> Result<CursorFile> {
+        let kind = u16_at(bytes, 2)?;
+        if u16_at(bytes, 0)? != 0 || (kind != TYPE_CURSOR && kind != TYPE_ICON) {
+            return Err(invalid("not a .cur or .ico file"));
+        }
+
+        let count = u16_at(bytes, 4)? as usize;
+        if count == 0 {
+            return Err(invalid("the file contains no images"));
+        }
+
+        let mut images = Vec::with_capacity(count);
+        for i in 0..count {
+            let entry = HEADER_SIZE + i * ENTRY_SIZE;
+            let length = u32_at(bytes, entry + 8)? as usize;
+            let offset = u32_at(bytes, entry + 12)? as usize;
+
+            let data = bytes
+                .get(offset..offset.saturating_add(length))
+                .ok_or_else(|| invalid("image data is out of bounds"))?;
+
+            let image = if data.starts_with(PNG_SIGNATURE) {
+                Image::decode_png(data)?
+            } else {
+                decode_dib(data)?
+            };
+
+            let hotspot = if kind == TYPE_CURSOR {
+                (
+                    u32::from(u16_at(bytes, entry + 4)?),
+                    u32::from(u16_at(bytes, entry + 6)?),
+                )
+            } else {
+                (0, 0)
+            };
+
+            images.push(CursorImage { image, hotspot });
+        }
+
+        Ok(CursorFile { images })
+    }
+
+    /// Encode this cursor as a .cur file, storing each image as a 32-bit bitmap.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let mut header = Vec::new();
+        push_u16(&mut header, 0);
+        push_u16(&mut header, TYPE_CURSOR);
+        push_u16(&mut header, self.images.len() as u16);
+
+        let mut data = Vec::new();
+        let data_offset = HEADER_SIZE + self.images.len() * ENTRY_SIZE;
+
+        for cursor in &self.images {
+            let image = &cursor.image;
+            if image.width == 0
+                || image.height == 0
+                || image.width > MAX_SIZE
+                || image.height > MAX_SIZE
+            {
+                return Err(invalid(&format!(
+                    "a {}x{} image can't be stored in a cursor file",
+                    image.width, image.height
+                )));
+            }
+
+            let dib = encode_dib(image);
+
+            // A width or height of 256 is stored as 0.
+            header.push(image.width as u8);
+            header.push(image.height as u8);
+            header.push(0); // Number of palette colors
+            header.push(0); // Reserved
+            push_u16(&mut header, cursor.hotspot.0 as u16);
+            push_u16(&mut header, cursor.hotspot.1 as u16);
+            push_u32(&mut header, dib.len() as u32);
+            push_u32(&mut header, (data_offset + data.len()) as u32);
+
+            data.extend(dib);
+        }
+
+        header.extend(data);
+
+        Ok(header)
+    }
+}
+
+/// Decode a device-independent bitmap, as stored in cursor files:
+/// a BITMAPINFOHEADER, an optional palette, the color (XOR) bitmap, and the transparency (AND) mask,
+/// with the rows of both bitmaps stored bottom-up.
+fn decode_dib(data: &[u8]) -> Result<Image> {
+    let header_size = u32_at(data, 0)? as usize;
+    let width = u32_at(data, 4)? as i32;
+    // The height covers both the XOR and the AND bitmaps.
+    let height = (u32_at(data, 8)? as i32) / 2;
+    let bit_count = u32::from(u16_at(data, 14)?);
+    let compression = u32_at(data, 16)?;
+    let colors_used = u32_at(data, 32)? as usize;
+
+    if header_size < 40 || width <= 0 || height <= 0 || width > 1024 || height > 1024 {
+        return Err(invalid("unsupported bitmap header"));
+    }
+
+    // Only uncompressed (BI_RGB) bitmaps are used in cursors.
+    if compression != 0 {
+        return Err(invalid("compressed bitmaps are not supported"));
+    }
+
+    let (width, height) = (width as u32, height as u32);
+
+    let palette_length = match bit_count {
+        1 | 4 | 8 if colors_used == 0 => 1 << bit_count,
+        1 | 4 | 8 => colors_used,
+        24 | 32 => 0,
+        _ => {
+            return Err(invalid(&format!(
+                "{}-bit bitmaps are not supported",
+                bit_count
+            )))
+        }
+    };
+    let palette = data
+        .get(header_size..header_size + palette_length * 4)
+        .ok_or_else(|| invalid("palette is out of bounds"))?;
+
+    let xor_offset = header_size + palette_length * 4;
+    let xor_stride = ((width * bit_count).div_ceil(32) * 4) as usize;
+    let and_offset = xor_offset + xor_stride * height as usize;
+    let and_stride = (width.div_ceil(32) * 4) as usize;
+
+    if data.len() < and_offset {
+        return Err(invalid("bitmap data is truncated"));
+    }
+
+    // Some 32-bit cursors leave out the mask, since the alpha channel makes it redundant.
+    let has_mask = data.len() >= and_offset + and_stride * height as usize;
+
+    let mut image = Image::new(width, height);
+    for y in 0..height {
+        let row = (height - 1 - y) as usize;
+        let xor_row = &data[xor_offset + row * xor_stride..xor_offset + (row + 1) * xor_stride];
+
+        for x in 0..width {
+            let [b, g, r, a] = match bit_count {
+                32 => {
+                    let i = x as usize * 4;
+                    [xor_row[i], xor_row[i + 1], xor_row[i + 2], xor_row[i + 3]]
+                }
+                24 => {
+                    let i = x as usize * 3;
+                    [xor_row[i], xor_row[i + 1], xor_row[i + 2], 255]
+                }
+                _ => {
+                    let bit = (x * bit_count) as usize;
+                    let shift = 8 - bit_count as usize - bit % 8;
+                    let index =
+                        ((xor_row[bit / 8] >> shift) & ((1 << bit_count) - 1) as u8) as usize;
+                    let color = palette
+                        .get(index * 4..index * 4 + 3)
+                        .ok_or_else(|| invalid("palette index is out of bounds"))?;
+                    [color[0], color[1], color[2], 255]
+                }
+            };
+
+            image.set_pixel(x, y, [r, g, b, a]);
+        }
+
+        if has_mask {
+            let and_row = &data[and_offset + row * and_stride..and_offset + (row + 1) * and_stride];
+            for x in 0..width {
+                let transparent = (and_row[x as usize / 8] >> (7 - x % 8)) & 1 == 1;
+                if transparent && bit_count != 32 {
+                    image.set_pixel(x, y, [0, 0, 0, 0]);
+                }
+            }
+        }
+    }
+
+    // 32-bit bitmaps without any alpha information rely on the mask instead.
+    if bit_count == 32 && image.pixels.chunks(4).all(|p| p[3] == 0) {
+        for y in 0..height {
+            let row = (height - 1 - y) as usize;
+            for x in 0..width {
+                let transparent = has_mask
+                    && (data[and_offset + row * and_stride + x as usize / 8] >> (7 - x % 8)) & 1
+                        == 1;
+                let mut pixel = image.pixel(x, y);
+                pixel[3] = if transparent { 0 } else { 255 };
+                image.set_pixel(x, y, pixel);
+            }
+        }
+    }
+
+    Ok(image)
+}
+
+/// Encode an image as a 32-bit device-independent bitmap with an AND mask
+/// that marks the fully transparent pixels, for older consumers that ignore alpha.
+fn encode_dib(image: &Image) -> Vec<u8> {
+    let xor_stride = image.width as usize * 4;
+    let and_stride = (image.width.div_ceil(32) * 4) as usize;
+    let image_size = (xor_stride + and_stride) * image.height as usize;
+
+    let mut data = Vec::with_capacity(40 + image_size);
+
+    // BITMAPINFOHEADER
+    push_u32(&mut data, 40);
+    push_u32(&mut data, image.width);
+    push_u32(&mut data, image.height * 2);
+    push_u16(&mut data, 1); // Planes
+    push_u16(&mut data, 32); // Bits per pixel
+    push_u32(&mut data, 0); // BI_RGB
+    push_u32(&mut data, image_size as u32);
+    push_u32(&mut data, 0); // Horizontal resolution
+    push_u32(&mut data, 0); // Vertical resolution
+    push_u32(&mut data, 0); // Colors used
+    push_u32(&mut data, 0); // Important colors
+
+    for y in (0..image.height).rev() {
+        for x in 0..image.width {
+            let [r, g, b, a] = image.pixel(x, y);
+            data.extend_from_slice(&[b, g, r, a]);
+        }
+    }
+
+    for y in (0..image.height).rev() {
+        let mut row = vec![0u8; and_stride];
+        for x in 0..image.width {
+            if image.pixel(x, y)[3] == 0 {
+                row[x as usize / 8] |= 0x80 >> (x % 8);
+            }
+        }
+        data.extend(row);
+    }
+
+    data
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::InvalidCursorData {
+        reason: reason.to_string(),
+    }
+}
+
+pub(crate) fn u16_at(bytes: &[u8], offset: usize) -> Result<u16> {
+    bytes
+        .get(offset..offset + 2)
+        .map(|b| u16::from(b[0]) | u16::from(b[1]) << 8)
+        .ok_or_else(|| invalid("unexpected end of data"))
+}
+
+pub(crate) fn u32_at(bytes: &[u8], offset: usize) -> Result<u32> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| {
+            u32::from(b[0]) | u32::from(b[1]) << 8 | u32::from(b[2]) << 16 | u32::from(b[3]) << 24
+        })
+        .ok_or_else(|| invalid("unexpected end of data"))
+}
+
+pub(crate) fn push_u16(bytes: &mut Vec<u8>, value: u16) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+pub(crate) fn push_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    /// The sizes Windows uses most, at 100% to 200% scaling.
+    const SIZES: [u32; 4] = [24, 32, 48, 64];
+
+    #[test]
+    fn resamples_a_cur_file() {
+        let cursor = CursorFile::decode(&testing::read("resample/arrow.cur")).unwrap();
+
+        for &size in &SIZES {
+            let resampled = cursor.resampled(&[size]);
+            testing::check_cursor(&format!("resample/arrow-{}.cur", size), &resampled);
+        }
+    }
+
+    #[test]
+    fn resamples_a_png_image() {
+        let cursor =
+            CursorFile::from_pngs(&[testing::path("resample/ring.png")], (32, 32)).unwrap();
+
+        for &size in &SIZES {
+            let resampled = cursor.resampled(&[size]);
+            testing::check_cursor(&format!("resample/ring-{}.cur", size), &resampled);
+        }
+    }
+
+    #[test]
+    fn keeps_the_hotspot_inside_the_resized_image() {
+        let cursor = CursorImage {
+            image: Image::new(32, 32),
+            hotspot: (31, 31),
+        };
+
+        for &size in &SIZES {
+            assert_eq!(cursor.scale_to(size).hotspot, (size - 1, size - 1));
+        }
+    }
+
+    #[test]
+    fn round_trips_through_a_cur_file() {
+        let cursor = CursorFile::decode(&testing::read("resample/arrow.cur")).unwrap();
+        let resampled = cursor.resampled(&SIZES);
+
+        assert_eq!(
+            CursorFile::decode(&resampled.encode().unwrap()).unwrap(),
+            resampled
+        );
+    }
+}
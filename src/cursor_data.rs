@@ -0,0 +1,101 @@
+//! Cursors of any of the supported file types, for code that doesn't care whether a cursor is animated.
+
+use std::fs;
+use std::path::Path;
+
+use crate::ani::AnimatedCursor;
+use crate::cur::CursorFile;
+use crate::error::WithPath;
+use crate::xcursor;
+use crate::Result;
+
+/// A decoded cursor, either static or animated.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CursorData {
+    Static(CursorFile),
+    Animated(AnimatedCursor),
+}
+
+impl CursorData {
+    /// Read a .cur, .ico, .ani, or Xcursor file, or convert a .png image using the given `hotspot`.
+    pub fn open<P: AsRef<Path>>(path: P, hotspot: (u32, u32)) -> Result<CursorData> {
+        let path = path.as_ref();
+
+        if has_extension(path, "png") {
+            return Ok(CursorData::Static(CursorFile::from_pngs(&[path], hotspot)?));
+        }
+
+        let bytes = fs::read(path).with_path(path)?;
+
+        // Animated cursors are RIFF files and Xcursor files have their own magic number;
+        // anything else should be a .cur or .ico file.
+        if bytes.starts_with(b"RIFF") {
+            Ok(CursorData::Animated(AnimatedCursor::decode(&bytes)?))
+        } else if bytes.starts_with(xcursor::MAGIC) {
+            xcursor::to_cursor_data(xcursor::decode(&bytes)?)
+        } else {
+            Ok(CursorData::Static(CursorFile::decode(&bytes)?))
+        }
+    }
+
+    /// The file extension to use when saving this cursor.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            CursorData::Static(_) => "cur",
+            CursorData::Animated(_) => "ani",
+        }
+    }
+
+    /// Encode this cursor as a .cur or .ani file, matching its `extension`.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        match self {
+            CursorData::Static(cursor) => cursor.encode(),
+            CursorData::Animated(cursor) => cursor.encode(),
+        }
+    }
+
+    /// Get the distinct frames of this cursor; a static cursor has exactly one.
+    pub fn frames(&self) -> Vec<&CursorFile> {
+        match self {
+            CursorData::Static(cursor) => vec![cursor],
+            CursorData::Animated(cursor) => cursor.frames.iter().collect(),
+        }
+    }
+
+    /// Create a copy of this cursor with `transform` applied to each of its frames.
+    pub fn map_frames<F: FnMut(&CursorFile) -> CursorFile>(&self, mut transform: F) -> CursorData {
+        match self {
+            CursorData::Static(cursor) => CursorData::Static(transform(cursor)),
+            CursorData::Animated(cursor) => CursorData::Animated(AnimatedCursor {
+                frames: cursor.frames.iter().map(transform).collect(),
+                ..cursor.clone()
+            }),
+        }
+    }
+
+    /// Like `map_frames`, but for transforms that can fail.
+    pub fn try_map_frames<F: FnMut(&CursorFile) -> Result<CursorFile>>(
+        &self,
+        mut transform: F,
+    ) -> Result<CursorData> {
+        match self {
+            CursorData::Static(cursor) => Ok(CursorData::Static(transform(cursor)?)),
+            CursorData::Animated(cursor) => Ok(CursorData::Animated(AnimatedCursor {
+                frames: cursor.frames.iter().map(transform).collect::<Result<_>>()?,
+                ..cursor.clone()
+            })),
+        }
+    }
+
+    /// Create a copy of this cursor with every frame resampled to each of `sizes`.
+    pub fn resampled(&self, sizes: &[u32]) -> CursorData {
+        self.map_frames(|frame| frame.resampled(sizes))
+    }
+}
+
+/// Check whether `path` has the given file `extension`, ignoring case.
+pub fn has_extension<P: AsRef<Path>>(path: P, extension: &str) -> bool {
+    path.as_ref()
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case(extension))
+}
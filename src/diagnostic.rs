@@ -0,0 +1,289 @@
+//! Errors that point at the place in a configuration file that caused them, with a snippet of the file:
+//!
+//! ```text
+//! cursor.toml:7:10: the application "Code.exe" uses the cursor "arow", which isn't defined
+//!   |
+//! 7 | cursor = "arow"
+//!   |          ^^^^^^
+//!   = help: did you mean "arrow"?
+//! ```
+
+use std::fmt;
+use std::fs;
+
+use crate::error::Error;
+use crate::spanned::Spanned;
+
+/// A place in a configuration file, as a range of bytes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Location {
+    pub file: String,
+
+    /// The range of bytes, unless the file's format doesn't keep track of where values are (like JSON and YAML).
+    pub span: Option<(usize, usize)>,
+}
+
+/// A problem at a particular place in a configuration file.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub file: String,
+
+    /// Where the problem is in the file, when that's known.
+    pub position: Option<Position>,
+
+    pub message: String,
+
+    /// A suggestion for fixing the problem.
+    pub help: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Position {
+    /// The line and column of the problem, counting from 1.
+    pub line: usize,
+    pub column: usize,
+
+    /// The line of the file with the problem, with the problem underlined.
+    pub snippet: String,
+}
+
+impl Location {
+    /// Get the location of a value read from `file`.
+    pub fn of<T>(file: &str, value: &Spanned<T>) -> Location {
+        Location {
+            file: file.to_string(),
+            span: value.span(),
+        }
+    }
+
+    /// Describe a problem at this location.
+    pub fn diagnostic<S: Into<String>>(&self, message: S) -> Diagnostic {
+        match self.span {
+            Some((start, end)) => {
+                let source = fs::read_to_string(&self.file).unwrap_or_default();
+                Diagnostic::new(&self.file, &source, start, end, message.into())
+            }
+            None => Diagnostic {
+                file: self.file.clone(),
+                position: None,
+                message: without_line_column(message.into()),
+                help: None,
+            },
+        }
+    }
+
+    /// Attach this location to an error, unless it already has one or this location is unknown.
+    pub fn wrap(&self, error: Error) -> Error {
+        match error {
+            Error::Config(_) => error,
+            error if self.file.is_empty() => error,
+            error => Error::Config(self.diagnostic(error.to_string())),
+        }
+    }
+}
+
+impl Diagnostic {
+    /// Describe a problem with the bytes from `start` to `end` of `source`, which was read from `file`.
+    pub fn new(file: &str, source: &str, start: usize, end: usize, message: String) -> Diagnostic {
+        let start = floor_char_boundary(source, start.min(source.len()));
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |i| start + i);
+
+        let line = source[..start].matches('\n').count() + 1;
+        let column = source[line_start..start].chars().count() + 1;
+
+        // Only the first line of a value that spans several is underlined.
+        let text = source[line_start..line_end].trim_end_matches('\r');
+        let end = floor_char_boundary(source, end.clamp(start, line_end));
+        let width = source[start..end].chars().count().max(1);
+
+        let number = line.to_string();
+        let gutter = " ".repeat(number.len());
+        let snippet = format!(
+            "{} |\n{} | {}\n{} | {}{}",
+            gutter,
+            number,
+            text,
+            gutter,
+            " ".repeat(column - 1),
+            "^".repeat(width)
+        );
+
+        Diagnostic {
+            file: file.to_string(),
+            position: Some(Position {
+                line,
+                column,
+                snippet,
+            }),
+            message: without_line_column(message),
+            help: None,
+        }
+    }
+
+    /// Describe a problem at a `line` and `column` (in characters, both counting from 0) of `source`.
+    pub fn at_line_column(file: &str, source: &str, line: usize, column: usize, message: String) -> Diagnostic {
+        let line_start: usize = source
+            .split_inclusive('\n')
+            .take(line)
+            .map(str::len)
+            .sum();
+        let start = source[line_start.min(source.len())..]
+            .char_indices()
+            .nth(column)
+            .map_or(source.len(), |(i, _)| line_start + i);
+
+        Diagnostic::new(file, source, start, start + 1, message)
+    }
+
+    /// Describe an error from the TOML parser, which knows the line and column of most errors.
+    pub fn from_toml(file: &str, source: &str, error: &toml::de::Error) -> Diagnostic {
+        let (line, column) = error.line_col().unwrap_or((0, 0));
+
+        Diagnostic::at_line_column(file, source, line, column, error.to_string())
+    }
+
+    /// Leave out where the problem is, for when the position isn't a position in `file`.
+    pub fn without_position(mut self) -> Diagnostic {
+        self.position = None;
+        self
+    }
+
+    /// Add a suggestion for fixing the problem.
+    pub fn with_help<S: Into<String>>(mut self, help: Option<S>) -> Diagnostic {
+        self.help = help.map(Into::into);
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let position = match &self.position {
+            Some(position) => position,
+            None => {
+                write!(f, "{}: {}", self.file, self.message)?;
+                if let Some(help) = &self.help {
+                    write!(f, "\n  = help: {}", help)?;
+                }
+                return Ok(());
+            }
+        };
+
+        write!(
+            f,
+            "{}:{}:{}: {}\n{}",
+            self.file, position.line, position.column, self.message, position.snippet
+        )?;
+
+        if let Some(help) = &self.help {
+            let gutter = " ".repeat(position.line.to_string().len());
+            write!(f, "\n{} = help: {}", gutter, help)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Suggest one of `candidates` that `name` may be a misspelling of, as "did you mean ...?".
+pub fn did_you_mean<'a, I: IntoIterator<Item = &'a str>>(name: &str, candidates: I) -> Option<String> {
+    // Allow about one typo for every three characters, but at least two, so short names can have a couple.
+    let max_distance = (name.chars().count() / 3).max(2);
+
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| format!("did you mean \"{}\"?", candidate))
+}
+
+/// Count the single-character insertions, deletions, substitutions, and swaps of two neighbouring characters
+/// needed to turn `a` into `b`, ignoring case. (This is the optimal string alignment distance, so "rde" is one
+/// swap from "red".)
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+
+    // Three rows of the table: for the first `i - 1`, `i`, and `i + 1` characters of `a`.
+    let mut before: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+
+            if i > 0 && j > 0 && *ca == b[j - 1] && a[i - 1] == *cb {
+                current[j + 1] = current[j + 1].min(before[j - 1] + 1);
+            }
+        }
+        before = std::mem::replace(&mut previous, current);
+    }
+
+    previous[b.len()]
+}
+
+/// Remove the "at line 3 column 7" that parsers put in their messages, since the position is shown separately.
+fn without_line_column(message: String) -> String {
+    let start = match message.find(" at line ") {
+        Some(start) => start,
+        None => return message,
+    };
+
+    let rest = &message[start + " at line ".len()..];
+    let rest = rest.trim_start_matches(|c: char| c.is_ascii_digit());
+    let rest = match rest.strip_prefix(" column ") {
+        Some(rest) => rest.trim_start_matches(|c: char| c.is_ascii_digit()),
+        None => rest,
+    };
+
+    format!("{}{}", &message[..start], rest)
+}
+
+/// Move `index` back to the start of the character it's in.
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_edits() {
+        assert_eq!(edit_distance("red", "red"), 0);
+        assert_eq!(edit_distance("Red", "red"), 0);
+        assert_eq!(edit_distance("red", "reed"), 1);
+        assert_eq!(edit_distance("red", "rd"), 1);
+        assert_eq!(edit_distance("red", "rad"), 1);
+        assert_eq!(edit_distance("", "red"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn counts_a_swap_as_one_edit() {
+        assert_eq!(edit_distance("rde", "red"), 1);
+        assert_eq!(edit_distance("hnad", "hand"), 1);
+        assert_eq!(edit_distance("ab", "ba"), 1);
+
+        // Each character is only edited once, so this isn't a swap and an insertion.
+        assert_eq!(edit_distance("ca", "abc"), 3);
+    }
+
+    #[test]
+    fn suggests_close_names() {
+        let names = ["red", "arrow", "hand", "crosshair"];
+        let suggest = |name| did_you_mean(name, names.iter().copied()).unwrap_or_default();
+
+        assert_eq!(suggest("rde"), "did you mean \"red\"?");
+        assert_eq!(suggest("rd"), "did you mean \"red\"?");
+        assert_eq!(suggest("arow"), "did you mean \"arrow\"?");
+        assert_eq!(suggest("crosshiar"), "did you mean \"crosshair\"?");
+        assert_eq!(suggest("pointer"), "");
+    }
+}
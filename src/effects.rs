@@ -0,0 +1,631 @@
+//! Procedural effects that derive a new cursor from an existing one,
+//! like "the same arrow, but red with a badge".
+//!
+//! Effects are applied to every image of every frame, in the order they're listed,
+//! and never change the size of an image or its hotspot.
+
+use std::convert::TryFrom;
+
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject, StringValidation};
+use schemars::JsonSchema;
+
+use crate::cur::{CursorFile, CursorImage};
+use crate::cursor_data::CursorData;
+use crate::error::WithPath;
+use crate::font;
+use crate::image::{self, Image};
+use crate::Result;
+
+/// Pixels at least this opaque count as part of the cursor when drawing outlines.
+const OPAQUE_THRESHOLD: u8 = 128;
+
+/// Badges are this fraction of the cursor's height, but never less than 7 pixels
+/// (the height of one line of text, with padding).
+const BADGE_HEIGHT: f32 = 0.4;
+const MIN_BADGE_HEIGHT: u32 = 7;
+
+/// The widest outline and the largest glow radius, in pixels. Wider ones take much longer to draw,
+/// and would hardly fit around a cursor anyway.
+const MAX_OUTLINE_WIDTH: u32 = 16;
+const MAX_GLOW_RADIUS: u32 = 32;
+
+/// An RGBA color, written in the configuration as `"#rgb"`, `"#rrggbb"`, or `"#rrggbbaa"`.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct Color(pub [u8; 4]);
+
+impl JsonSchema for Color {
+    fn schema_name() -> String {
+        "Color".to_string()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            string: Some(Box::new(StringValidation {
+                pattern: Some(
+                    "^#([0-9a-fA-F]{3}|[0-9a-fA-F]{6}|[0-9a-fA-F]{8})$".to_string(),
+                ),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> String {
+        let [r, g, b, a] = color.0;
+        match a {
+            255 => format!("#{:02x}{:02x}{:02x}", r, g, b),
+            _ => format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a),
+        }
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(text: String) -> std::result::Result<Color, String> {
+        let invalid = || {
+            format!(
+                "invalid color \"{}\", expected \"#rgb\", \"#rrggbb\", or \"#rrggbbaa\"",
+                text
+            )
+        };
+
+        let hex = text.strip_prefix('#').ok_or_else(invalid)?;
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+
+        let digit = |i: usize| u8::from_str_radix(&hex[i..=i], 16).unwrap();
+        let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+
+        match hex.len() {
+            3 => Ok(Color([digit(0) * 17, digit(1) * 17, digit(2) * 17, 255])),
+            6 => Ok(Color([byte(0), byte(2), byte(4), 255])),
+            8 => Ok(Color([byte(0), byte(2), byte(4), byte(6)])),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// The corner of the cursor that a badge is drawn in.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    // The hotspot is usually at the top-left, so the opposite corner is least in the way.
+    #[default]
+    BottomRight,
+}
+
+/// A single transformation of a cursor's images.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, JsonSchema)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Effect {
+    /// Rotate the hue of every pixel by `shift` degrees.
+    Hue { shift: f32 },
+
+    /// Colorize the cursor with `color`, keeping its shading.
+    /// A `strength` of 1 replaces the original colors entirely.
+    Tint {
+        color: Color,
+        #[serde(default = "default_strength")]
+        strength: f32,
+    },
+
+    /// Paint every visible pixel with a single `color`, keeping the cursor's shape.
+    Recolor { color: Color },
+
+    /// Draw a solid outline `width` pixels wide (at most 16) around the cursor.
+    Outline {
+        color: Color,
+        #[serde(default = "default_width")]
+        width: u32,
+    },
+
+    /// Draw a soft glow that fades out over `radius` pixels (at most 32) around the cursor.
+    Glow {
+        color: Color,
+        #[serde(default = "default_radius")]
+        radius: u32,
+    },
+
+    /// Draw a small label (`text`) or image (`icon`, a .png file) in a corner of the cursor.
+    Badge {
+        text: Option<String>,
+        icon: Option<String>,
+        #[serde(default)]
+        corner: Corner,
+        #[serde(default = "default_badge_color")]
+        color: Color,
+        #[serde(default = "default_badge_background")]
+        background: Color,
+    },
+
+    /// Make the cursor partially transparent, from 0 (invisible) to 1 (unchanged).
+    Opacity { opacity: f32 },
+}
+
+fn default_strength() -> f32 {
+    1.0
+}
+
+fn default_width() -> u32 {
+    1
+}
+
+fn default_radius() -> u32 {
+    3
+}
+
+fn default_badge_color() -> Color {
+    Color([255, 255, 255, 255])
+}
+
+fn default_badge_background() -> Color {
+    Color([208, 0, 0, 255])
+}
+
+impl Effect {
+    /// Get the path of any file this effect reads.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Effect::Badge {
+                icon: Some(icon), ..
+            } => Some(icon),
+            _ => None,
+        }
+    }
+
+    /// Get a mutable reference to the path of any file this effect reads, so it can be resolved.
+    pub fn path_mut(&mut self) -> Option<&mut String> {
+        match self {
+            Effect::Badge {
+                icon: Some(icon), ..
+            } => Some(icon),
+            _ => None,
+        }
+    }
+
+    /// Apply this effect to a single image.
+    pub fn apply(&self, cursor: &CursorImage) -> Result<CursorImage> {
+        let image = &cursor.image;
+
+        let image = match self {
+            Effect::Hue { shift } => map_pixels(image, |pixel| rotate_hue(pixel, *shift)),
+            Effect::Tint { color, strength } => {
+                map_pixels(image, |pixel| tint(pixel, color.0, *strength))
+            }
+            Effect::Recolor { color } => map_pixels(image, |pixel| {
+                let alpha = f32::from(pixel[3]) * f32::from(color.0[3]) / 255.0;
+                [color.0[0], color.0[1], color.0[2], to_channel(alpha)]
+            }),
+            Effect::Outline { color, width } => {
+                let width = (*width).min(MAX_OUTLINE_WIDTH);
+                underlay(image, &outline(image, width), color.0)
+            }
+            Effect::Glow { color, radius } => {
+                let radius = (*radius).min(MAX_GLOW_RADIUS);
+                underlay(image, &glow(image, radius), color.0)
+            }
+            Effect::Badge {
+                text,
+                icon,
+                corner,
+                color,
+                background,
+            } => {
+                let badge = match (text, icon) {
+                    (_, Some(icon)) => icon_badge(image, icon)?,
+                    (Some(text), None) => text_badge(image, text, color.0, background.0),
+                    (None, None) => {
+                        return Err(crate::error::Error::InvalidArgument {
+                            reason: "a badge needs either `text` or an `icon`".to_string(),
+                        })
+                    }
+                };
+
+                let mut image = image.clone();
+                let (x, y) = corner_position(&image, &badge, *corner);
+                image.draw(&badge, x, y);
+                image
+            }
+            Effect::Opacity { opacity } => map_pixels(image, |pixel| {
+                let alpha = f32::from(pixel[3]) * opacity.clamp(0.0, 1.0);
+                [pixel[0], pixel[1], pixel[2], to_channel(alpha)]
+            }),
+        };
+
+        Ok(CursorImage {
+            image,
+            hotspot: cursor.hotspot,
+        })
+    }
+}
+
+/// Apply each of `effects`, in order, to every image of `cursor`.
+pub fn apply(cursor: &CursorData, effects: &[Effect]) -> Result<CursorData> {
+    cursor.try_map_frames(|frame| {
+        let images = frame
+            .images
+            .iter()
+            .map(|image| {
+                effects
+                    .iter()
+                    .try_fold(image.clone(), |image, effect| effect.apply(&image))
+            })
+            .collect::<Result<Vec<CursorImage>>>()?;
+
+        Ok(CursorFile { images })
+    })
+}
+
+fn map_pixels<F: Fn([u8; 4]) -> [u8; 4]>(image: &Image, transform: F) -> Image {
+    let mut result = image.clone();
+
+    for pixel in result.pixels.chunks_mut(4) {
+        let transformed = transform([pixel[0], pixel[1], pixel[2], pixel[3]]);
+        pixel.copy_from_slice(&transformed);
+    }
+
+    result
+}
+
+fn to_channel(value: f32) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+/// Rotate the hue of a pixel by `degrees`, keeping its saturation and value.
+fn rotate_hue(pixel: [u8; 4], degrees: f32) -> [u8; 4] {
+    let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(|c| f32::from(c) / 255.0);
+
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let chroma = max - min;
+
+    // Grays have no hue to rotate.
+    if chroma <= 0.0 {
+        return pixel;
+    }
+
+    let hue = if max == r {
+        60.0 * ((g - b) / chroma).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / chroma + 2.0)
+    } else {
+        60.0 * ((r - g) / chroma + 4.0)
+    };
+    let hue = (hue + degrees).rem_euclid(360.0) / 60.0;
+
+    let x = chroma * (1.0 - (hue.rem_euclid(2.0) - 1.0).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+
+    [
+        to_channel((r + min) * 255.0),
+        to_channel((g + min) * 255.0),
+        to_channel((b + min) * 255.0),
+        pixel[3],
+    ]
+}
+
+/// Replace a pixel's color with `color` scaled by the pixel's brightness,
+/// so white becomes `color` and black stays black.
+fn tint(pixel: [u8; 4], color: [u8; 4], strength: f32) -> [u8; 4] {
+    let strength = strength.clamp(0.0, 1.0);
+    let luminance =
+        (0.299 * f32::from(pixel[0]) + 0.587 * f32::from(pixel[1]) + 0.114 * f32::from(pixel[2]))
+            / 255.0;
+
+    let channel = |c: usize| {
+        let tinted = f32::from(color[c]) * luminance;
+        to_channel(f32::from(pixel[c]) * (1.0 - strength) + tinted * strength)
+    };
+
+    [channel(0), channel(1), channel(2), pixel[3]]
+}
+
+/// Get the opacity of each pixel of `image`, from 0 to 1.
+fn alpha_mask(image: &Image) -> Vec<f32> {
+    image
+        .pixels
+        .chunks(4)
+        .map(|pixel| f32::from(pixel[3]) / 255.0)
+        .collect()
+}
+
+/// Get a mask covering every pixel within `width` pixels of the cursor's opaque pixels.
+fn outline(image: &Image, width: u32) -> Vec<f32> {
+    let (w, h) = (image.width as i32, image.height as i32);
+    let radius = width as i32;
+    let mut mask = vec![0.0; (w * h) as usize];
+
+    for y in 0..h {
+        for x in 0..w {
+            if image.pixel(x as u32, y as u32)[3] < OPAQUE_THRESHOLD {
+                continue;
+            }
+
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if nx >= 0
+                        && ny >= 0
+                        && nx < w
+                        && ny < h
+                        && dx * dx + dy * dy <= radius * radius
+                    {
+                        mask[(ny * w + nx) as usize] = 1.0;
+                    }
+                }
+            }
+        }
+    }
+
+    mask
+}
+
+/// Get a soft mask that fades out over `radius` pixels around the cursor,
+/// by blurring its opacity (two box blurs approximate a gaussian).
+fn glow(image: &Image, radius: u32) -> Vec<f32> {
+    let (w, h) = (image.width as usize, image.height as usize);
+    let mut mask = alpha_mask(image);
+
+    let radius = radius.div_ceil(2) as usize;
+    for _ in 0..2 {
+        mask = box_blur(&mask, w, h, radius, true);
+        mask = box_blur(&mask, w, h, radius, false);
+    }
+
+    // Blurring spreads the opacity thin, so boost it to keep the glow visible.
+    mask.iter().map(|alpha| (alpha * 2.0).min(1.0)).collect()
+}
+
+fn box_blur(
+    mask: &[f32],
+    width: usize,
+    height: usize,
+    radius: usize,
+    horizontal: bool,
+) -> Vec<f32> {
+    let mut result = vec![0.0; mask.len()];
+    let (lines, length) = if horizontal {
+        (height, width)
+    } else {
+        (width, height)
+    };
+    let index = |line: usize, i: usize| {
+        if horizontal {
+            line * width + i
+        } else {
+            i * width + line
+        }
+    };
+
+    for line in 0..lines {
+        for i in 0..length {
+            let start = i.saturating_sub(radius);
+            let end = (i + radius).min(length - 1);
+            let sum: f32 = (start..=end).map(|j| mask[index(line, j)]).sum();
+            result[index(line, i)] = sum / (2 * radius + 1) as f32;
+        }
+    }
+
+    result
+}
+
+/// Draw `image` on top of a layer of `color`, with the layer's opacity given by `mask`.
+fn underlay(image: &Image, mask: &[f32], color: [u8; 4]) -> Image {
+    let mut result = image.clone();
+
+    for (i, pixel) in result.pixels.chunks_mut(4).enumerate() {
+        let alpha = mask[i] * f32::from(color[3]);
+        let layer = [color[0], color[1], color[2], to_channel(alpha)];
+        let blended = image::blend_over(layer, [pixel[0], pixel[1], pixel[2], pixel[3]]);
+        pixel.copy_from_slice(&blended);
+    }
+
+    result
+}
+
+fn badge_height(image: &Image) -> u32 {
+    ((image.height as f32 * BADGE_HEIGHT).round() as u32)
+        .max(MIN_BADGE_HEIGHT)
+        .min(image.height)
+}
+
+/// Render `text` on a solid background, sized to fit a corner of `image`.
+fn text_badge(image: &Image, text: &str, color: [u8; 4], background: [u8; 4]) -> Image {
+    // One font pixel of padding on each side of the text.
+    let scale = (badge_height(image) / (font::GLYPH_HEIGHT + 2)).max(1);
+    let (text_width, text_height) = font::measure(text, scale);
+
+    let mut badge = Image::new(text_width + 2 * scale, text_height + 2 * scale);
+    for y in 0..badge.height {
+        for x in 0..badge.width {
+            badge.set_pixel(x, y, background);
+        }
+    }
+
+    font::draw_text(&mut badge, text, scale as i32, scale as i32, scale, color);
+
+    badge
+}
+
+/// Load the .png image at `path`, resized to fit a corner of `image`.
+fn icon_badge(image: &Image, path: &str) -> Result<Image> {
+    let icon = Image::decode_png(&std::fs::read(path).with_path(path)?)?;
+    let size = badge_height(image);
+
+    let icon = CursorImage {
+        image: icon,
+        hotspot: (0, 0),
+    };
+
+    Ok(icon.scale_to(size).image)
+}
+
+fn corner_position(image: &Image, badge: &Image, corner: Corner) -> (i32, i32) {
+    let right = image.width as i32 - badge.width as i32;
+    let bottom = image.height as i32 - badge.height as i32;
+
+    match corner {
+        Corner::TopLeft => (0, 0),
+        Corner::TopRight => (right, 0),
+        Corner::BottomLeft => (0, bottom),
+        Corner::BottomRight => (right, bottom),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn arrow() -> CursorFile {
+        CursorFile::decode(&testing::read("resample/arrow.cur")).unwrap()
+    }
+
+    fn color(text: &str) -> Color {
+        Color::try_from(text.to_string()).unwrap()
+    }
+
+    /// Apply `effect` to the arrow and compare it with `testdata/effects/{name}.cur`.
+    fn check(name: &str, effect: Effect) {
+        let cursor = CursorData::Static(arrow());
+        let applied = match apply(&cursor, &[effect]).unwrap() {
+            CursorData::Static(applied) => applied,
+            CursorData::Animated(_) => unreachable!(),
+        };
+
+        testing::check_cursor(&format!("effects/{}.cur", name), &applied);
+    }
+
+    #[test]
+    fn hue() {
+        check("hue", Effect::Hue { shift: 120.0 });
+    }
+
+    #[test]
+    fn tint() {
+        check(
+            "tint",
+            Effect::Tint {
+                color: color("#3080ff"),
+                strength: 0.75,
+            },
+        );
+    }
+
+    #[test]
+    fn recolor() {
+        check(
+            "recolor",
+            Effect::Recolor {
+                color: color("#00c000c0"),
+            },
+        );
+    }
+
+    #[test]
+    fn outline() {
+        check(
+            "outline",
+            Effect::Outline {
+                color: color("#fff"),
+                width: 2,
+            },
+        );
+    }
+
+    #[test]
+    fn glow() {
+        check(
+            "glow",
+            Effect::Glow {
+                color: color("#ffd000"),
+                radius: 4,
+            },
+        );
+    }
+
+    #[test]
+    fn text_badge() {
+        check(
+            "badge-text",
+            Effect::Badge {
+                text: Some("2".to_string()),
+                icon: None,
+                corner: Corner::BottomRight,
+                color: default_badge_color(),
+                background: default_badge_background(),
+            },
+        );
+    }
+
+    #[test]
+    fn icon_badge() {
+        check(
+            "badge-icon",
+            Effect::Badge {
+                text: None,
+                icon: Some(
+                    testing::path("resample/ring.png")
+                        .to_string_lossy()
+                        .into_owned(),
+                ),
+                corner: Corner::TopRight,
+                color: default_badge_color(),
+                background: default_badge_background(),
+            },
+        );
+    }
+
+    #[test]
+    fn opacity() {
+        check("opacity", Effect::Opacity { opacity: 0.4 });
+    }
+
+    #[test]
+    fn wide_outlines_and_glows_are_capped() {
+        let image = &arrow().images[0];
+        let capped = |effect: Effect, cap: Effect| {
+            assert_eq!(effect.apply(image).unwrap(), cap.apply(image).unwrap());
+        };
+
+        let white = color("#fff");
+        capped(
+            Effect::Outline {
+                color: white,
+                width: u32::MAX,
+            },
+            Effect::Outline {
+                color: white,
+                width: MAX_OUTLINE_WIDTH,
+            },
+        );
+        capped(
+            Effect::Glow {
+                color: white,
+                radius: u32::MAX,
+            },
+            Effect::Glow {
+                color: white,
+                radius: MAX_GLOW_RADIUS,
+            },
+        );
+    }
+}
@@ -1,31 +1,123 @@
+// `failure`'s derive implements its traits from inside a `const` block.
+#![allow(non_local_definitions)]
+
 extern crate failure;
 
 use std::convert::From;
+use std::path::Path;
 
 use failure::Fail;
 
+use crate::diagnostic::Diagnostic;
+
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Fail)]
 pub enum Error {
     #[fail(display = "Error converting from UTF-16")]
     FromUtf16Error(std::string::FromUtf16Error),
 
-    #[fail(display = "IO Error")]
+    #[fail(display = "IO error: {}", _0)]
     IoError(std::io::Error),
 
-    #[fail(display = "Error reading TOML file")]
+    #[fail(display = "{}: {}", path, error)]
+    FileError { path: String, error: std::io::Error },
+
+    #[fail(display = "Error reading TOML file: {}", _0)]
     TomlDeserializationError(toml::de::Error),
 
+    #[fail(display = "{}", _0)]
+    Config(Diagnostic),
+
+    #[fail(display = "Error writing TOML: {}", _0)]
+    TomlSerializationError(toml::ser::Error),
+
+    #[fail(display = "JSON error: {}", _0)]
+    JsonError(serde_json::Error),
+
+    #[fail(display = "YAML error: {}", _0)]
+    YamlError(serde_yaml::Error),
+
     #[fail(
         display = "Failed to find cursor named \"{}\" in the cursor.toml [[cursor]] table",
         name
     )]
     MissingCursorNameError { name: String },
 
-    #[fail(display = "Failed to find the file, {}, for the cursor {}", path, name)]
-    MissingCursorFileError { name: String, path: String },
+    #[fail(
+        display = "Failed to find the file, {} (resolved to {}), for the cursor {}",
+        path, resolved, name
+    )]
+    MissingCursorFileError {
+        name: String,
+        path: String,
+        resolved: String,
+    },
+
+    #[fail(
+        display = "The environment variable {} used in the path {} isn't set",
+        name, path
+    )]
+    UnknownEnvironmentVariable { name: String, path: String },
+
+    #[fail(
+        display = "The cursor \"{}\" needs a `path`, a `pack`, or a `base` cursor",
+        name
+    )]
+    MissingCursorPath { name: String },
+
+    #[fail(display = "The cursor \"{}\" is (indirectly) its own base", name)]
+    CursorBaseCycle { name: String },
+
+    #[fail(display = "Failed to find the configuration file {}", path)]
+    MissingConfigFile { path: String },
+
+    #[fail(display = "The configuration file {} (indirectly) includes itself", path)]
+    ConfigIncludeCycle { path: String },
+
+    #[fail(display = "There is no profile named \"{}\"", name)]
+    UnknownProfile { name: String },
+
+    #[fail(display = "The cursor changer isn't running")]
+    NotRunning,
 
     #[fail(display = "There is already a cursor named \"{}\"!", name)]
     DuplicateCursorName { name: String },
+
+    #[fail(display = "Invalid cursor: {}", reason)]
+    InvalidCursorData { reason: String },
+
+    #[fail(display = "Error decoding PNG image: {}", _0)]
+    PngDecodingError(png::DecodingError),
+
+    #[fail(display = "Error encoding PNG image: {}", _0)]
+    PngEncodingError(png::EncodingError),
+
+    #[fail(display = "Invalid argument: {}", reason)]
+    InvalidArgument { reason: String },
+
+    #[fail(display = "Error decoding GIF image: {}", _0)]
+    GifDecodingError(gif::DecodingError),
+
+    #[fail(display = "Invalid .inf file: {}", reason)]
+    InvalidInf { reason: String },
+
+    #[fail(display = "Invalid Xcursor file: {}", reason)]
+    InvalidXcursor { reason: String },
+
+    #[fail(display = "Error reading zip archive: {}", _0)]
+    ZipError(zip::result::ZipError),
+
+    #[fail(display = "Invalid cursor pack {}: {}", pack, reason)]
+    InvalidPack { pack: String, reason: String },
+
+    #[fail(display = "Invalid statistics file, on line {}: {}", line, reason)]
+    InvalidStats { line: usize, reason: String },
+
+    #[fail(display = "Invalid trace file, on line {}: {}", line, reason)]
+    InvalidTrace { line: usize, reason: String },
+
+    #[fail(display = "The replay doesn't match the trace ({})", difference)]
+    ReplayMismatch { difference: String },
 }
 
 impl From<std::string::FromUtf16Error> for Error {
@@ -34,6 +126,32 @@ impl From<std::string::FromUtf16Error> for Error {
     }
 }
 
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::JsonError(e)
+    }
+}
+
+impl From<serde_yaml::Error> for Error {
+    fn from(e: serde_yaml::Error) -> Error {
+        Error::YamlError(e)
+    }
+}
+
+/// Say which file an IO error happened with, by turning it into an `Error::FileError`.
+pub trait WithPath<T> {
+    fn with_path<P: AsRef<Path>>(self, path: P) -> Result<T, Error>;
+}
+
+impl<T> WithPath<T> for std::io::Result<T> {
+    fn with_path<P: AsRef<Path>>(self, path: P) -> Result<T, Error> {
+        self.map_err(|error| Error::FileError {
+            path: path.as_ref().display().to_string(),
+            error,
+        })
+    }
+}
+
 impl From<std::io::Error> for Error {
     fn from(e: std::io::Error) -> Error {
         Error::IoError(e)
@@ -44,4 +162,50 @@ impl From<toml::de::Error> for Error {
     fn from(e: toml::de::Error) -> Error {
         Error::TomlDeserializationError(e)
     }
-}
\ No newline at end of file
+}
+
+impl From<toml::ser::Error> for Error {
+    fn from(e: toml::ser::Error) -> Error {
+        Error::TomlSerializationError(e)
+    }
+}
+
+impl From<zip::result::ZipError> for Error {
+    fn from(e: zip::result::ZipError) -> Error {
+        Error::ZipError(e)
+    }
+}
+
+impl From<png::DecodingError> for Error {
+    fn from(e: png::DecodingError) -> Error {
+        Error::PngDecodingError(e)
+    }
+}
+
+impl From<png::EncodingError> for Error {
+    fn from(e: png::EncodingError) -> Error {
+        Error::PngEncodingError(e)
+    }
+}
+
+impl From<gif::DecodingError> for Error {
+    fn from(e: gif::DecodingError) -> Error {
+        Error::GifDecodingError(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    #[test]
+    fn io_errors_say_what_went_wrong() {
+        let error = Error::from(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+        assert_eq!(error.to_string(), "IO error: no such file");
+
+        let path = "testdata/missing.cur";
+        let error = std::fs::read(path).with_path(path).unwrap_err();
+        assert!(error.to_string().starts_with("testdata/missing.cur: "));
+    }
+}
@@ -0,0 +1,95 @@
+//! A tiny 3x5 pixel font, for drawing short labels (like badges) onto cursors.
+
+use crate::image::Image;
+
+/// The width and height of each glyph, in pixels.
+pub const GLYPH_WIDTH: u32 = 3;
+pub const GLYPH_HEIGHT: u32 = 5;
+
+/// The space between glyphs, in pixels.
+const SPACING: u32 = 1;
+
+/// Get the rows of a character's glyph, top to bottom, with the leftmost pixel in the highest of the 3 bits.
+/// Lowercase letters are drawn as uppercase, and unsupported characters are drawn as `?`.
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b011, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '*' => [0b101, 0b010, 0b111, 0b010, 0b101],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        _ => [0b111, 0b001, 0b011, 0b000, 0b010],
+    }
+}
+
+/// Get the size of `text` when drawn at `scale` pixels per font pixel.
+pub fn measure(text: &str, scale: u32) -> (u32, u32) {
+    let count = text.chars().count() as u32;
+    let width = (count * (GLYPH_WIDTH + SPACING)).saturating_sub(SPACING);
+
+    (width * scale, GLYPH_HEIGHT * scale)
+}
+
+/// Draw `text` onto `image` with its top-left corner at (`x`, `y`), at `scale` pixels per font pixel.
+pub fn draw_text(image: &mut Image, text: &str, x: i32, y: i32, scale: u32, color: [u8; 4]) {
+    let mut dot = Image::new(scale, scale);
+    for py in 0..scale {
+        for px in 0..scale {
+            dot.set_pixel(px, py, color);
+        }
+    }
+
+    for (i, c) in text.chars().enumerate() {
+        let glyph_x = x + (i as u32 * (GLYPH_WIDTH + SPACING) * scale) as i32;
+
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (0b100 >> column) != 0 {
+                    image.draw(
+                        &dot,
+                        glyph_x + (column * scale) as i32,
+                        y + (row as u32 * scale) as i32,
+                    );
+                }
+            }
+        }
+    }
+}
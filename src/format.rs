@@ -0,0 +1,212 @@
+//! The file formats the configuration can be written in. TOML is the usual one; JSON and YAML are
+//! for configuration generated by other tools.
+
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::de::DeserializeOwned;
+
+use crate::diagnostic::Diagnostic;
+use crate::error::Error;
+use crate::spanned;
+use crate::Result;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Json,
+    Yaml,
+}
+
+impl Format {
+    /// Every format, in the order configuration files are looked for.
+    pub const ALL: [Format; 3] = [Format::Toml, Format::Json, Format::Yaml];
+
+    /// Work out a file's format from its extension. Files with other extensions are taken to be TOML.
+    pub fn of(path: &Path) -> Format {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+
+        match extension.as_str() {
+            "json" => Format::Json,
+            "yaml" | "yml" => Format::Yaml,
+            _ => Format::Toml,
+        }
+    }
+
+    /// The usual extension of files in this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Toml => "toml",
+            Format::Json => "json",
+            Format::Yaml => "yaml",
+        }
+    }
+
+    /// Read a document in this format, which was read from `file`.
+    ///
+    /// Every format is read straight into `T`, so errors point at the place in the file that caused them.
+    /// Values that keep their place in TOML (`Spanned`) don't get one from JSON or YAML.
+    pub fn read<T: DeserializeOwned>(self, file: &str, contents: &str) -> Result<T> {
+        match self {
+            Format::Toml => toml::from_str(contents)
+                .map_err(|e| Error::Config(Diagnostic::from_toml(file, contents, &e))),
+            Format::Json => spanned::without_spans(|| serde_json::from_str(contents))
+                .map_err(|e| json_error(file, contents, e)),
+            Format::Yaml => spanned::without_spans(|| serde_yaml::from_str(contents))
+                .map_err(|e| yaml_error(file, contents, e)),
+        }
+    }
+
+    /// Read a document in this format, which was read from `file`, to convert it to another.
+    /// TOML has no null, so keys that are null in JSON or YAML are left out.
+    pub fn parse(self, file: &str, contents: &str) -> Result<toml::Value> {
+        let value: serde_json::Value = match self {
+            Format::Toml => return self.read(file, contents),
+            Format::Json => self.read(file, contents)?,
+            Format::Yaml => self.read(file, contents)?,
+        };
+
+        Ok(toml::Value::try_from(without_nulls(value))?)
+    }
+
+    /// Write a document in this format.
+    pub fn write(self, value: &toml::Value) -> Result<String> {
+        Ok(match self {
+            Format::Toml => toml::to_string_pretty(value)?,
+            Format::Json => serde_json::to_string_pretty(value)? + "\n",
+            Format::Yaml => serde_yaml::to_string(value)?,
+        })
+    }
+}
+
+fn json_error(file: &str, contents: &str, e: serde_json::Error) -> Error {
+    let line = e.line().saturating_sub(1);
+    let column = e.column().saturating_sub(1);
+    Error::Config(Diagnostic::at_line_column(
+        file,
+        contents,
+        line,
+        column,
+        e.to_string(),
+    ))
+}
+
+fn yaml_error(file: &str, contents: &str, e: serde_yaml::Error) -> Error {
+    match e.location() {
+        Some(location) => Error::Config(Diagnostic::new(
+            file,
+            contents,
+            location.index(),
+            location.index() + 1,
+            e.to_string(),
+        )),
+        None => Error::YamlError(e),
+    }
+}
+
+/// Remove the keys whose values are null, at any depth.
+fn without_nulls(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(object) => serde_json::Value::Object(
+            object
+                .into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key, without_nulls(value)))
+                .collect(),
+        ),
+        serde_json::Value::Array(array) => {
+            serde_json::Value::Array(array.into_iter().map(without_nulls).collect())
+        }
+        value => value,
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(name: &str) -> std::result::Result<Format, String> {
+        match name.to_lowercase().as_str() {
+            "toml" => Ok(Format::Toml),
+            "json" => Ok(Format::Json),
+            "yaml" | "yml" => Ok(Format::Yaml),
+            _ => Err(format!(
+                "unknown format \"{}\" (expected toml, json, or yaml)",
+                name
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    const JSON: &str = r#"{
+  "scale": null,
+  "cursor": [{ "name": "dog", "path": "dog.cur", "base": null }],
+  "application": [{ "cursor": "dog", "path": "powershell.exe", "when": null }]
+}
+"#;
+
+    #[test]
+    fn reads_json_and_yaml_with_nulls() {
+        let yaml = "cursor:\n  - name: dog\n    path: dog.cur\napplication:\n  - cursor: dog\n    path: powershell.exe\n    when: ~\n";
+
+        for config in [
+            Format::Json.read::<Config>("cursor.json", JSON),
+            Format::Yaml.read("cursor.yaml", yaml),
+        ] {
+            let config = config.unwrap();
+            assert_eq!(config.scale, None);
+            assert_eq!(config.cursor[0].name, "dog");
+            assert_eq!(config.application[0].cursor.get_ref(), "dog");
+            assert!(config.application[0].when.is_none());
+        }
+    }
+
+    #[test]
+    fn reports_errors_where_they_are_in_the_file() {
+        let position =
+            |format: Format, contents: &str| match format.read::<Config>("cursor", contents) {
+                Err(Error::Config(diagnostic)) => diagnostic
+                    .position
+                    .map(|position| (position.line, position.column)),
+                other => panic!(
+                    "expected a configuration error, got {:?}",
+                    other.map(|_| ())
+                ),
+            };
+
+        let json = "{\n  \"application\": [{ \"cursor\": \"dog\", \"path\": 5 }]\n}";
+        assert_eq!(position(Format::Json, json), Some((2, 46)));
+
+        let yaml = "application:\n  - cursor: dog\n    path: [1]\n";
+        assert_eq!(position(Format::Yaml, yaml), Some((3, 11)));
+
+        assert_eq!(position(Format::Json, "{} {}"), Some((1, 4)));
+    }
+
+    #[test]
+    fn converting_leaves_out_nulls() {
+        let converted = Format::Toml
+            .write(&Format::Json.parse("cursor.json", JSON).unwrap())
+            .unwrap();
+
+        assert_eq!(
+            converted,
+            "[[application]]\ncursor = 'dog'\npath = 'powershell.exe'\n\n[[cursor]]\nname = 'dog'\npath = 'dog.cur'\n"
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! Generating short animated cursors frame by frame, for effects that are drawn rather than read from a file,
+//! like transitions and pulses.
+//!
+//! An effect implements `Generator` to draw any moment of its animation, and `generate` turns it into an
+//! animated cursor with a frame rate that suits the system.
+
+use std::time::Duration;
+
+use crate::ani::{self, AnimatedCursor};
+use crate::cur::{CursorFile, CursorImage};
+use crate::image::Image;
+use crate::Result;
+
+/// Generated animations show a new frame about this often, in milliseconds (every two jiffies).
+const FRAME_MILLIS: u64 = 33;
+
+/// How many frames a generated animation has at most.
+const MAX_FRAMES: u64 = 15;
+
+/// Draws the frames of a generated animation.
+pub trait Generator {
+    /// The size of the frames, and where their hotspot is. The image is left blank.
+    fn canvas(&self) -> CursorImage;
+
+    /// Draw the frame that is `progress` (from 0 to 1) of the way through the animation, on a blank `canvas`.
+    fn draw(&self, canvas: &CursorImage, progress: f32) -> Image;
+}
+
+/// Generate an animation that plays `generator` over about `duration` milliseconds.
+///
+/// Neither end of the animation is drawn, since they're usually what's shown before and after it.
+pub fn generate<G: Generator + ?Sized>(generator: &G, duration: u64) -> Result<AnimatedCursor> {
+    let count = (duration / FRAME_MILLIS).clamp(1, MAX_FRAMES);
+    let jiffies = ani::jiffies_from_millis(duration / count);
+
+    let canvas = generator.canvas();
+    let frames = (1..=count)
+        .map(|n| {
+            let progress = n as f32 / (count + 1) as f32;
+            let image = generator.draw(&canvas, progress);
+            CursorFile::from_images(vec![image], canvas.hotspot)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    AnimatedCursor::new(frames, &[], &[jiffies])
+}
+
+/// How long one loop of an animation takes.
+pub fn duration(animation: &AnimatedCursor) -> Duration {
+    let jiffies = u64::from(animation.duration());
+    Duration::from_millis(jiffies * 1000 / u64::from(ani::JIFFIES_PER_SECOND))
+}
+
+/// The size, in pixels, to load an animation at: that of its largest image.
+pub fn size(animation: &AnimatedCursor) -> u32 {
+    animation
+        .frames
+        .iter()
+        .flat_map(|frame| &frame.images)
+        .map(|image| image.image.width.max(image.image.height))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Draw `cursor` (if there is one) on a blank copy of `canvas`, scaled by `scale` around its hotspot,
+/// with its hotspot where the canvas's is.
+pub fn placed(canvas: &CursorImage, cursor: Option<&CursorImage>, scale: f32) -> Image {
+    let mut image = Image::new(canvas.image.width, canvas.image.height);
+
+    let cursor = match cursor {
+        Some(cursor) => cursor,
+        None => return image,
+    };
+
+    let scaled = |value: u32| (value as f32 * scale).round() as u32;
+    let (width, height) = (scaled(cursor.image.width), scaled(cursor.image.height));
+    if width == 0 || height == 0 {
+        return image;
+    }
+
+    let resized = cursor.resize(width, height);
+    image.draw(
+        &resized.image,
+        canvas.hotspot.0 as i32 - resized.hotspot.0 as i32,
+        canvas.hotspot.1 as i32 - resized.hotspot.1 as i32,
+    );
+
+    image
+}
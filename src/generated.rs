@@ -0,0 +1,63 @@
+//! Cursors that are generated at load time.
+//!
+//! Windows can only load cursors from files (or from raw bitmaps, which can't be animated),
+//! so generated cursors are written to a cache directory and loaded from there like any other cursor.
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+
+use crate::cursor_data::CursorData;
+use crate::effects::{self, Effect};
+use crate::error::WithPath;
+use crate::Result;
+
+/// Get the directory that generated cursor files are written to.
+pub fn cache_dir() -> PathBuf {
+    std::env::temp_dir().join("windows-cursor-changer")
+}
+
+/// Write a generated cursor file into the cache directory, returning its path.
+///
+/// The file name is derived from `key`, which should uniquely describe how the cursor was generated.
+pub fn write<K: Hash>(key: K, extension: &str, bytes: &[u8]) -> Result<PathBuf> {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+
+    let dir = cache_dir();
+    fs::create_dir_all(&dir).with_path(&dir)?;
+
+    let path = dir.join(format!("{:016x}.{}", hasher.finish(), extension));
+    fs::write(&path, bytes).with_path(&path)?;
+
+    Ok(path)
+}
+
+/// Decode the cursor (or .png image, using `hotspot`) at `path` and write it back out as a .cur or .ani file,
+/// resampled to `size` pixels if one is given, and with `effects` applied to the result.
+pub fn converted_cursor(
+    path: &str,
+    hotspot: (u32, u32),
+    size: Option<u32>,
+    effects: &[Effect],
+) -> Result<PathBuf> {
+    let mut cursor = CursorData::open(path, hotspot)?;
+
+    if let Some(size) = size {
+        cursor = cursor.resampled(&[size]);
+    }
+
+    if !effects.is_empty() {
+        cursor = effects::apply(&cursor, effects)?;
+    }
+
+    // Effects hold floats, which can't be hashed directly, but their debug output describes them exactly.
+    let effects = format!("{:?}", effects);
+
+    write(
+        ("converted", path, hotspot, size, effects),
+        cursor.extension(),
+        &cursor.encode()?,
+    )
+}
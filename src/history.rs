@@ -0,0 +1,343 @@
+//! A record of the decisions the running cursor changer makes: what was under the pointer, which rule
+//! matched, and whether a cursor was applied or the system cursors were restored.
+//!
+//! The most recent decisions are kept in memory, and written to `cursor-history.jsonl` when the `history`
+//! command asks for them (and when the cursor changer exits). Every decision can also be appended to a
+//! journal file as it's made. Both files have one JSON object per line.
+//!
+//! A decision is only recorded when it differs from the previous one (apart from its time and position),
+//! so holding the pointer over one window doesn't fill the history.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDateTime;
+use schemars::JsonSchema;
+
+use crate::error::WithPath;
+use crate::logging::{self, LogFile};
+use crate::Result;
+
+/// The name of the file the recent decisions are written to.
+pub const FILE_NAME: &str = "cursor-history.jsonl";
+
+/// The name of the journal file, when the configuration doesn't give one.
+pub const JOURNAL_FILE_NAME: &str = "cursor-journal.jsonl";
+
+/// How many decisions are kept in memory, when the configuration doesn't say.
+const DEFAULT_SIZE: usize = 1000;
+
+/// The `[history]` table of the configuration.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, JsonSchema)]
+pub struct HistoryConfig {
+    /// How many of the most recent decisions to keep in memory. Defaults to 1000.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<usize>,
+
+    /// Also append every decision to a journal file, which is rotated like the log file.
+    #[serde(default)]
+    pub journal: bool,
+
+    /// The journal file, relative to the configuration file that sets it.
+    /// Defaults to `cursor-journal.jsonl` next to the configuration file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+}
+
+impl HistoryConfig {
+    /// Get the journal file, which is in `dir` unless the configuration says otherwise.
+    pub fn journal_path(&self, dir: &Path) -> PathBuf {
+        match &self.file {
+            Some(file) => PathBuf::from(file),
+            None => dir.join(JOURNAL_FILE_NAME),
+        }
+    }
+}
+
+/// What the cursor changer decided to do.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum Action {
+    /// Use a custom cursor. Nothing changes if it's already in use.
+    Apply { cursor: String },
+
+    /// Restore the system cursors.
+    Reset,
+
+    /// Leave the cursor as it is, because there's no window under the pointer or it couldn't be identified.
+    Keep,
+
+    /// Leave the cursor as it is for now, until the pointer has stayed long enough to switch to `cursor`
+    /// (or to the system cursors, if there's no `cursor`).
+    Wait {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cursor: Option<String>,
+    },
+}
+
+/// One decision, and what it was based on.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Decision {
+    /// When it was made, in local time.
+    pub time: NaiveDateTime,
+
+    /// Where the pointer was, in screen coordinates.
+    pub position: (i32, i32),
+
+    /// The handle of the window under the pointer, if there is one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window: Option<usize>,
+
+    /// The path of the window's executable, if it could be found.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub executable: Option<String>,
+
+    /// Why the executable couldn't be found.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// The rule that matched, like `application "Code.exe"`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+impl Decision {
+    /// Whether this is the same decision as `other`, made at another time or position.
+    fn same_as(&self, other: &Decision) -> bool {
+        self.window == other.window
+            && self.executable == other.executable
+            && self.error == other.error
+            && self.rule == other.rule
+            && self.action == other.action
+    }
+}
+
+/// The most recent decisions, oldest first.
+pub struct History {
+    decisions: VecDeque<Decision>,
+
+    /// How many decisions are kept.
+    size: usize,
+
+    journal: Option<LogFile>,
+
+    /// The previous decision, which may not be kept if `size` is 0.
+    previous: Option<Decision>,
+}
+
+impl History {
+    /// Start a history that keeps `size` decisions, and appends every decision to `journal` if there is one.
+    pub fn new(size: usize, journal: Option<LogFile>) -> History {
+        History {
+            decisions: VecDeque::with_capacity(size.min(DEFAULT_SIZE)),
+            size,
+            journal,
+            previous: None,
+        }
+    }
+
+    /// Start a history as the configuration says. `dir` is where the journal goes if the configuration doesn't say.
+    pub fn from_config(config: &HistoryConfig, dir: &Path) -> Result<History> {
+        let journal = if config.journal {
+            Some(LogFile::open(
+                config.journal_path(dir),
+                logging::DEFAULT_MAX_SIZE,
+                logging::DEFAULT_KEEP,
+            )?)
+        } else {
+            None
+        };
+
+        Ok(History::new(config.size.unwrap_or(DEFAULT_SIZE), journal))
+    }
+
+    /// Record a decision, unless it's the same as the previous one.
+    pub fn record(&mut self, decision: Decision) -> Result<()> {
+        if self
+            .previous
+            .as_ref()
+            .is_some_and(|previous| previous.same_as(&decision))
+        {
+            return Ok(());
+        }
+        self.previous = Some(decision.clone());
+
+        if let Some(journal) = &mut self.journal {
+            journal.write_line(&serde_json::to_string(&decision)?)?;
+        }
+
+        if self.decisions.len() == self.size {
+            self.decisions.pop_front();
+        }
+        if self.size > 0 {
+            self.decisions.push_back(decision);
+        }
+
+        Ok(())
+    }
+
+    /// Write the recorded decisions to a file, replacing it.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut text = String::new();
+        for decision in &self.decisions {
+            text.push_str(&serde_json::to_string(decision)?);
+            text.push('\n');
+        }
+
+        fs::write(path, text).with_path(path)?;
+
+        Ok(())
+    }
+}
+
+/// Read decisions from a file written by `History::save`, or a journal.
+pub fn load(path: &Path) -> Result<Vec<Decision>> {
+    let text = fs::read_to_string(path).with_path(path)?;
+
+    let mut decisions = Vec::new();
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        decisions.push(serde_json::from_str(line)?);
+    }
+
+    Ok(decisions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use std::{env, process};
+
+    fn decision(ms: i64, position: (i32, i32), executable: &str, action: Action) -> Decision {
+        Decision {
+            time: testing::time(ms),
+            position,
+            window: Some(1),
+            executable: Some(executable.to_string()),
+            error: None,
+            rule: None,
+            action,
+        }
+    }
+
+    fn apply(cursor: &str) -> Action {
+        Action::Apply {
+            cursor: cursor.to_string(),
+        }
+    }
+
+    fn times(decisions: &[Decision]) -> Vec<NaiveDateTime> {
+        decisions.iter().map(|decision| decision.time).collect()
+    }
+
+    fn temp_path(test: &str) -> PathBuf {
+        env::temp_dir().join(format!(
+            "cursor-changer-history-{}-{}.jsonl",
+            test,
+            process::id()
+        ))
+    }
+
+    #[test]
+    fn records_only_new_decisions() {
+        let mut history = History::new(10, None);
+        history
+            .record(decision(0, (0, 0), "Code.exe", apply("hand")))
+            .unwrap();
+
+        // The pointer moved, but nothing else changed.
+        history
+            .record(decision(100, (50, 50), "Code.exe", apply("hand")))
+            .unwrap();
+        history
+            .record(decision(200, (0, 0), "notepad.exe", apply("hand")))
+            .unwrap();
+        history
+            .record(decision(300, (0, 0), "notepad.exe", Action::Reset))
+            .unwrap();
+        history
+            .record(decision(400, (0, 0), "notepad.exe", Action::Reset))
+            .unwrap();
+
+        let decisions: Vec<Decision> = history.decisions.iter().cloned().collect();
+        assert_eq!(
+            times(&decisions),
+            [testing::time(0), testing::time(200), testing::time(300)]
+        );
+    }
+
+    #[test]
+    fn keeps_the_most_recent_decisions() {
+        let mut history = History::new(2, None);
+        for (i, executable) in ["a.exe", "b.exe", "c.exe"].iter().enumerate() {
+            history
+                .record(decision(i as i64 * 100, (0, 0), executable, Action::Keep))
+                .unwrap();
+        }
+
+        let decisions: Vec<Decision> = history.decisions.iter().cloned().collect();
+        assert_eq!(times(&decisions), [testing::time(100), testing::time(200)]);
+    }
+
+    #[test]
+    fn saves_and_loads_decisions() {
+        let mut history = History::new(10, None);
+        history
+            .record(Decision {
+                rule: Some("application \"Code.exe\"".to_string()),
+                ..decision(0, (1, 2), "Code.exe", apply("hand"))
+            })
+            .unwrap();
+        history
+            .record(Decision {
+                window: None,
+                executable: None,
+                ..decision(100, (3, 4), "", Action::Wait { cursor: None })
+            })
+            .unwrap();
+
+        let path = temp_path("save");
+        history.save(&path).unwrap();
+        let loaded = load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(times(&loaded), [testing::time(0), testing::time(100)]);
+        assert_eq!(loaded[0].position, (1, 2));
+        assert_eq!(loaded[0].executable.as_deref(), Some("Code.exe"));
+        assert_eq!(loaded[0].rule.as_deref(), Some("application \"Code.exe\""));
+        assert_eq!(loaded[0].action, apply("hand"));
+        assert_eq!(loaded[1].window, None);
+        assert_eq!(loaded[1].action, Action::Wait { cursor: None });
+    }
+
+    #[test]
+    fn writes_every_decision_to_the_journal() {
+        let path = temp_path("journal");
+        let _ = fs::remove_file(&path);
+
+        // Nothing is kept in memory, but the journal still gets every new decision.
+        let journal = LogFile::open(path.clone(), logging::DEFAULT_MAX_SIZE, 1).unwrap();
+        let mut history = History::new(0, Some(journal));
+        for (ms, action) in [
+            (0, apply("hand")),
+            (100, apply("hand")),
+            (200, Action::Reset),
+        ] {
+            history
+                .record(decision(ms, (0, 0), "Code.exe", action))
+                .unwrap();
+        }
+        assert!(history.decisions.is_empty());
+        drop(history);
+
+        let journal = load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(times(&journal), [testing::time(0), testing::time(200)]);
+        assert_eq!(journal[1].action, Action::Reset);
+    }
+}
@@ -0,0 +1,247 @@
+//! Global keyboard shortcuts, written in the configuration like `"ctrl+alt+p"`.
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use schemars::JsonSchema;
+
+/// Modifier flags, as used by `RegisterHotKey`.
+/// See: https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-registerhotkey
+pub const MOD_ALT: u32 = 0x0001;
+pub const MOD_CONTROL: u32 = 0x0002;
+pub const MOD_SHIFT: u32 = 0x0004;
+pub const MOD_WIN: u32 = 0x0008;
+
+/// Names of the keys that aren't a single letter or digit, with their virtual-key codes.
+/// See: https://docs.microsoft.com/en-us/windows/win32/inputdev/virtual-key-codes
+const KEY_NAMES: [(&str, u32); 22] = [
+    ("backspace", 0x08),
+    ("tab", 0x09),
+    ("enter", 0x0d),
+    ("pause", 0x13),
+    ("escape", 0x1b),
+    ("space", 0x20),
+    ("pageup", 0x21),
+    ("pagedown", 0x22),
+    ("end", 0x23),
+    ("home", 0x24),
+    ("left", 0x25),
+    ("up", 0x26),
+    ("right", 0x27),
+    ("down", 0x28),
+    ("insert", 0x2d),
+    ("delete", 0x2e),
+    ("multiply", 0x6a),
+    ("add", 0x6b),
+    ("subtract", 0x6d),
+    ("decimal", 0x6e),
+    ("divide", 0x6f),
+    ("scrolllock", 0x91),
+];
+
+/// A key together with the modifiers that must be held with it.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq, JsonSchema)]
+#[serde(try_from = "String", into = "String")]
+#[schemars(with = "String")]
+pub struct Hotkey {
+    /// A combination of the `MOD_*` flags.
+    pub modifiers: u32,
+
+    /// The virtual-key code of the key.
+    pub key: u32,
+
+    /// The usual name of the key, whichever way the configuration writes it (so `F01` is `f1`).
+    key_name: String,
+}
+
+impl TryFrom<String> for Hotkey {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Hotkey, String> {
+        let mut modifiers = 0;
+        let mut key = None;
+
+        for part in text.split('+').map(|part| part.trim().to_lowercase()) {
+            let modifier = match part.as_str() {
+                "ctrl" | "control" => MOD_CONTROL,
+                "alt" => MOD_ALT,
+                "shift" => MOD_SHIFT,
+                "win" | "super" => MOD_WIN,
+                _ => 0,
+            };
+
+            if modifier != 0 {
+                modifiers |= modifier;
+            } else if key.is_some() {
+                return Err(format!("the hotkey \"{}\" has more than one key", text));
+            } else {
+                let code = key_code(&part).ok_or_else(|| {
+                    format!("unknown key \"{}\" in the hotkey \"{}\"", part, text)
+                })?;
+                key = Some((code, key_name(code)));
+            }
+        }
+
+        let (key, key_name) =
+            key.ok_or_else(|| format!("the hotkey \"{}\" needs a key, not just modifiers", text))?;
+
+        if modifiers == 0 {
+            return Err(format!(
+                "the hotkey \"{}\" needs at least one of ctrl, alt, shift, or win",
+                text
+            ));
+        }
+
+        Ok(Hotkey {
+            modifiers,
+            key,
+            key_name,
+        })
+    }
+}
+
+impl From<Hotkey> for String {
+    fn from(hotkey: Hotkey) -> String {
+        hotkey.to_string()
+    }
+}
+
+impl fmt::Display for Hotkey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names = [
+            (MOD_CONTROL, "ctrl"),
+            (MOD_ALT, "alt"),
+            (MOD_SHIFT, "shift"),
+            (MOD_WIN, "win"),
+        ];
+        for (flag, name) in names.iter() {
+            if self.modifiers & flag != 0 {
+                write!(f, "{}+", name)?;
+            }
+        }
+
+        write!(f, "{}", self.key_name)
+    }
+}
+
+/// Get the virtual-key code for a key name: a letter, a digit, `f1` to `f24`, `numpad0` to `numpad9`,
+/// or one of the names in `KEY_NAMES`.
+fn key_code(name: &str) -> Option<u32> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        // Letters and digits use their (uppercase) ASCII codes.
+        return match c {
+            'a'..='z' | '0'..='9' => Some(c.to_ascii_uppercase() as u32),
+            _ => None,
+        };
+    }
+
+    let numbered = |prefix: &str, first: u32, count: u32| {
+        name.strip_prefix(prefix)
+            .and_then(|n| n.parse::<u32>().ok())
+            .filter(|n| *n < count)
+            .map(|n| first + n)
+    };
+
+    numbered("numpad", 0x60, 10)
+        .or_else(|| {
+            name.strip_prefix('f')
+                .and_then(|n| n.parse::<u32>().ok())
+                .filter(|n| (1..=24).contains(n))
+                .map(|n| 0x70 + n - 1)
+        })
+        .or_else(|| {
+            KEY_NAMES
+                .iter()
+                .find(|(key_name, _)| *key_name == name)
+                .map(|(_, code)| *code)
+        })
+}
+
+/// Get the usual name of a key from a virtual-key code that `key_code` gave.
+fn key_name(code: u32) -> String {
+    match code {
+        0x30..=0x39 | 0x41..=0x5a => char::from(code as u8).to_ascii_lowercase().to_string(),
+        0x60..=0x69 => format!("numpad{}", code - 0x60),
+        0x70..=0x87 => format!("f{}", code - 0x70 + 1),
+        _ => KEY_NAMES
+            .iter()
+            .find(|(_, key_code)| *key_code == code)
+            .map_or_else(String::new, |(name, _)| name.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hotkey(text: &str) -> Result<Hotkey, String> {
+        Hotkey::try_from(text.to_string())
+    }
+
+    #[test]
+    fn reads_modifiers_and_a_key() {
+        let ctrl_alt_p = hotkey("ctrl+alt+p").unwrap();
+        assert_eq!(ctrl_alt_p.modifiers, MOD_CONTROL | MOD_ALT);
+        assert_eq!(ctrl_alt_p.key, 0x50);
+
+        assert_eq!(hotkey("shift+f24").unwrap().key, 0x87);
+        assert_eq!(hotkey("win+numpad9").unwrap().key, 0x69);
+        assert_eq!(hotkey("Control + Shift + Space").unwrap().key, 0x20);
+        assert_eq!(hotkey("super+0").unwrap().modifiers, MOD_WIN);
+    }
+
+    #[test]
+    fn rejects_hotkeys_without_one_key_and_a_modifier() {
+        assert_eq!(
+            hotkey("ctrl+alt").unwrap_err(),
+            "the hotkey \"ctrl+alt\" needs a key, not just modifiers"
+        );
+        assert_eq!(
+            hotkey("p").unwrap_err(),
+            "the hotkey \"p\" needs at least one of ctrl, alt, shift, or win"
+        );
+        assert_eq!(
+            hotkey("ctrl+p+q").unwrap_err(),
+            "the hotkey \"ctrl+p+q\" has more than one key"
+        );
+        assert_eq!(
+            hotkey("ctrl+f25").unwrap_err(),
+            "unknown key \"f25\" in the hotkey \"ctrl+f25\""
+        );
+        assert!(hotkey("ctrl+numpad10").is_err());
+        assert!(hotkey("ctrl+f0").is_err());
+        assert!(hotkey("ctrl+").is_err());
+    }
+
+    #[test]
+    fn displays_hotkeys_the_usual_way() {
+        let cases = [
+            ("ctrl+alt+p", "ctrl+alt+p"),
+            ("Alt+Ctrl+P", "ctrl+alt+p"),
+            ("ctrl+f01", "ctrl+f1"),
+            ("super+numpad09", "win+numpad9"),
+            ("shift+control+PageUp", "ctrl+shift+pageup"),
+            ("win+alt+shift+ctrl+tab", "ctrl+alt+shift+win+tab"),
+        ];
+
+        for (text, expected) in cases.iter() {
+            let parsed = hotkey(text).unwrap();
+            assert_eq!(parsed.to_string(), *expected);
+            assert_eq!(hotkey(&parsed.to_string()).unwrap(), parsed);
+        }
+    }
+
+    #[test]
+    fn names_every_key_it_reads() {
+        for code in 0..=0xff {
+            let name = key_name(code);
+            if !name.is_empty() {
+                assert_eq!(key_code(&name), Some(code), "{}", name);
+            }
+        }
+        for (name, code) in KEY_NAMES.iter() {
+            assert_eq!(key_name(*code), *name);
+        }
+    }
+}
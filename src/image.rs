@@ -0,0 +1,324 @@
+//! A minimal RGBA image type, used for decoding, resampling, and generating cursors
+//! without relying on any Windows APIs.
+
+use crate::Result;
+
+/// An 8-bit RGBA image, stored row by row starting at the top-left corner.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+
+    /// Pixel data: red, green, blue, and (straight, not premultiplied) alpha for each pixel.
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Create a fully transparent image.
+    pub fn new(width: u32, height: u32) -> Image {
+        Image {
+            width,
+            height,
+            pixels: vec![0; (width * height * 4) as usize],
+        }
+    }
+
+    /// Get the RGBA value of the pixel at (`x`, `y`).
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = self.index(x, y);
+        [
+            self.pixels[i],
+            self.pixels[i + 1],
+            self.pixels[i + 2],
+            self.pixels[i + 3],
+        ]
+    }
+
+    /// Set the RGBA value of the pixel at (`x`, `y`).
+    pub fn set_pixel(&mut self, x: u32, y: u32, rgba: [u8; 4]) {
+        let i = self.index(x, y);
+        self.pixels[i..i + 4].copy_from_slice(&rgba);
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        assert!(x < self.width && y < self.height, "pixel out of bounds");
+        ((y * self.width + x) * 4) as usize
+    }
+
+    /// Resample this image to a new size.
+    ///
+    /// This uses a triangle (bilinear) filter that is widened when shrinking, so that
+    /// every source pixel contributes, and works on premultiplied colors so that
+    /// transparent pixels don't bleed dark fringes into the edges of the cursor.
+    pub fn resize(&self, width: u32, height: u32) -> Image {
+        if width == self.width && height == self.height {
+            return self.clone();
+        }
+
+        let premultiplied: Vec<[f32; 4]> = self
+            .pixels
+            .chunks(4)
+            .map(|p| {
+                let alpha = f32::from(p[3]) / 255.0;
+                [
+                    f32::from(p[0]) * alpha,
+                    f32::from(p[1]) * alpha,
+                    f32::from(p[2]) * alpha,
+                    f32::from(p[3]),
+                ]
+            })
+            .collect();
+
+        // Resample the rows first, then the columns of the result.
+        let column_weights = filter_weights(self.width, width);
+        let mut horizontal = Vec::with_capacity((width * self.height) as usize);
+        for y in 0..self.height as usize {
+            let row = &premultiplied[y * self.width as usize..(y + 1) * self.width as usize];
+            for (start, weights) in &column_weights {
+                horizontal.push(weighted_sum(weights, |k| row[start + k]));
+            }
+        }
+
+        let row_weights = filter_weights(self.height, height);
+        let mut resized = Image::new(width, height);
+        for (y, (start, weights)) in row_weights.iter().enumerate() {
+            for x in 0..width as usize {
+                let [r, g, b, a] =
+                    weighted_sum(weights, |k| horizontal[(start + k) * width as usize + x]);
+
+                let pixel = if a <= 0.0 {
+                    [0, 0, 0, 0]
+                } else {
+                    let alpha = a / 255.0;
+                    [
+                        to_channel(r / alpha),
+                        to_channel(g / alpha),
+                        to_channel(b / alpha),
+                        to_channel(a),
+                    ]
+                };
+
+                resized.set_pixel(x as u32, y as u32, pixel);
+            }
+        }
+
+        resized
+    }
+
+    /// Draw `source` on top of this image with its top-left corner at (`x`, `y`),
+    /// blending it with the existing pixels according to its alpha.
+    pub fn draw(&mut self, source: &Image, x: i32, y: i32) {
+        self.draw_with(source, x, y, blend_over);
+    }
+
+    /// Draw `source` like `draw`, but with its alpha multiplied by `opacity` (from 0 to 1).
+    pub fn draw_faded(&mut self, source: &Image, x: i32, y: i32, opacity: f32) {
+        let opacity = opacity.clamp(0.0, 1.0);
+        self.draw_with(source, x, y, |bottom, mut top| {
+            top[3] = (f32::from(top[3]) * opacity).round() as u8;
+            blend_over(bottom, top)
+        });
+    }
+
+    /// Copy `source` into this image with its top-left corner at (`x`, `y`),
+    /// replacing the existing pixels.
+    pub fn copy_from(&mut self, source: &Image, x: i32, y: i32) {
+        self.draw_with(source, x, y, |_, pixel| pixel);
+    }
+
+    fn draw_with<F: Fn([u8; 4], [u8; 4]) -> [u8; 4]>(
+        &mut self,
+        source: &Image,
+        x: i32,
+        y: i32,
+        combine: F,
+    ) {
+        for source_y in 0..source.height {
+            for source_x in 0..source.width {
+                let (target_x, target_y) = (x + source_x as i32, y + source_y as i32);
+                if target_x < 0
+                    || target_y < 0
+                    || target_x >= self.width as i32
+                    || target_y >= self.height as i32
+                {
+                    continue;
+                }
+
+                let (target_x, target_y) = (target_x as u32, target_y as u32);
+                let pixel = combine(
+                    self.pixel(target_x, target_y),
+                    source.pixel(source_x, source_y),
+                );
+                self.set_pixel(target_x, target_y, pixel);
+            }
+        }
+    }
+
+    /// Make a rectangular area of this image fully transparent.
+    pub fn clear(&mut self, x: u32, y: u32, width: u32, height: u32) {
+        for y in y..(y + height).min(self.height) {
+            for x in x..(x + width).min(self.width) {
+                self.set_pixel(x, y, [0, 0, 0, 0]);
+            }
+        }
+    }
+
+    /// Decode a PNG image of any color type into RGBA.
+    pub fn decode_png(bytes: &[u8]) -> Result<Image> {
+        let mut decoder = png::Decoder::new(bytes);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+        buffer.truncate(info.buffer_size());
+
+        Ok(Image {
+            width: info.width,
+            height: info.height,
+            pixels: to_rgba(&buffer, info.color_type),
+        })
+    }
+
+    /// Encode this image as an RGBA PNG.
+    pub fn encode_png(&self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+
+        {
+            let mut encoder = png::Encoder::new(&mut bytes, self.width, self.height);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+
+            let mut writer = encoder.write_header()?;
+            writer.write_image_data(&self.pixels)?;
+        }
+
+        Ok(bytes)
+    }
+}
+
+/// Blend the `top` pixel over the `bottom` one (the "source over" operator).
+pub fn blend_over(bottom: [u8; 4], top: [u8; 4]) -> [u8; 4] {
+    let top_alpha = f32::from(top[3]) / 255.0;
+    let bottom_alpha = f32::from(bottom[3]) / 255.0 * (1.0 - top_alpha);
+    let alpha = top_alpha + bottom_alpha;
+
+    if alpha <= 0.0 {
+        return [0, 0, 0, 0];
+    }
+
+    let channel = |c: usize| {
+        to_channel((f32::from(top[c]) * top_alpha + f32::from(bottom[c]) * bottom_alpha) / alpha)
+    };
+
+    [
+        channel(0),
+        channel(1),
+        channel(2),
+        to_channel(alpha * 255.0),
+    ]
+}
+
+/// Expand 8-bit PNG pixel data of the given color type to RGBA.
+pub fn to_rgba(data: &[u8], color_type: png::ColorType) -> Vec<u8> {
+    use png::ColorType;
+
+    match color_type {
+        ColorType::Rgba => data.to_vec(),
+        ColorType::Rgb => data
+            .chunks(3)
+            .flat_map(|p| vec![p[0], p[1], p[2], 255])
+            .collect(),
+        ColorType::GrayscaleAlpha => data
+            .chunks(2)
+            .flat_map(|p| vec![p[0], p[0], p[0], p[1]])
+            .collect(),
+        // Indexed images are expanded to RGB(A) by `normalize_to_color8`, so this is only grayscale.
+        ColorType::Grayscale | ColorType::Indexed => {
+            data.iter().flat_map(|&v| vec![v, v, v, 255]).collect()
+        }
+    }
+}
+
+/// For each of the `dst` output samples, compute the index of the first source sample that
+/// contributes to it, and the normalized weights of each contributing source sample.
+fn filter_weights(src: u32, dst: u32) -> Vec<(usize, Vec<f32>)> {
+    let scale = dst as f32 / src as f32;
+
+    // When shrinking, widen the filter so that it covers every source sample.
+    let support = if scale < 1.0 { 1.0 / scale } else { 1.0 };
+
+    (0..dst)
+        .map(|i| {
+            let center = (i as f32 + 0.5) / scale;
+            let start = (center - support).floor().max(0.0) as usize;
+            let end = ((center + support).ceil() as usize).min(src as usize);
+
+            let mut weights: Vec<f32> = (start..end)
+                .map(|j| (1.0 - ((j as f32 + 0.5) - center).abs() / support).max(0.0))
+                .collect();
+
+            let total: f32 = weights.iter().sum();
+            if total > 0.0 {
+                weights.iter_mut().for_each(|w| *w /= total);
+                (start, weights)
+            } else {
+                // Fall back to the nearest sample.
+                (center.min(src as f32 - 1.0) as usize, vec![1.0])
+            }
+        })
+        .collect()
+}
+
+fn weighted_sum<F: Fn(usize) -> [f32; 4]>(weights: &[f32], sample: F) -> [f32; 4] {
+    let mut sum = [0.0; 4];
+    for (k, weight) in weights.iter().enumerate() {
+        let value = sample(k);
+        for c in 0..4 {
+            sum[c] += value[c] * weight;
+        }
+    }
+    sum
+}
+
+fn to_channel(value: f32) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resizing_to_the_same_size_changes_nothing() {
+        let mut image = Image::new(3, 2);
+        image.set_pixel(1, 1, [10, 20, 30, 40]);
+
+        assert_eq!(image.resize(3, 2), image);
+    }
+
+    #[test]
+    fn transparent_pixels_dont_darken_the_edges() {
+        // An opaque red pixel next to a transparent black one.
+        let mut image = Image::new(2, 1);
+        image.set_pixel(0, 0, [255, 0, 0, 255]);
+
+        let resized = image.resize(1, 1);
+        assert_eq!(resized.pixel(0, 0), [255, 0, 0, 128]);
+    }
+
+    #[test]
+    fn resizing_keeps_an_even_color() {
+        let mut image = Image::new(5, 3);
+        for y in 0..3 {
+            for x in 0..5 {
+                image.set_pixel(x, y, [100, 150, 200, 255]);
+            }
+        }
+
+        for &(width, height) in &[(2, 1), (8, 7), (3, 9)] {
+            let resized = image.resize(width, height);
+            assert!(resized.pixels.chunks(4).all(|p| p == [100, 150, 200, 255]));
+        }
+    }
+}
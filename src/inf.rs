@@ -0,0 +1,391 @@
+//! Reading cursor schemes from the `install.inf` files that most cursor packs come with.
+//!
+//! An INF file installs a scheme by copying its cursor files and writing registry values.
+//! The files for each role are found in the `[Scheme.Reg]` section, either as one value under
+//! `Control Panel\Cursors\Schemes` listing every file in order, or as one value per role under
+//! `Control Panel\Cursors`. Both usually refer to names defined in the `[Strings]` section.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, WithPath};
+use crate::role::CursorRole;
+use crate::Result;
+
+/// The files of a cursor scheme, by role.
+#[derive(Debug, Default)]
+pub struct Scheme {
+    /// The scheme's name, if the INF file gives one.
+    pub name: Option<String>,
+
+    /// The file for each role, found next to the INF file.
+    pub files: BTreeMap<CursorRole, PathBuf>,
+
+    /// Registry names of roles that were listed but can't be used, like `NWPen`.
+    pub skipped: Vec<String>,
+}
+
+impl Scheme {
+    /// Read the scheme from an INF file. Cursor files are expected to be in the same directory.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Scheme> {
+        let path = path.as_ref();
+        let text = decode_text(&fs::read(path).with_path(path)?)?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+        Scheme::parse(&text, dir)
+    }
+
+    /// Parse the text of an INF file, resolving cursor file names relative to `dir`.
+    pub fn parse(text: &str, dir: &Path) -> Result<Scheme> {
+        let sections = sections(text);
+
+        let strings: HashMap<String, String> = sections
+            .get("strings")
+            .map(|lines| {
+                lines
+                    .iter()
+                    .filter_map(|line| {
+                        let (key, value) = line.split_once('=')?;
+                        Some((key.trim().to_lowercase(), unquote(value.trim())))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let registry = sections
+            .get("scheme.reg")
+            .ok_or_else(|| Error::InvalidInf {
+                reason: "there is no [Scheme.Reg] section".to_string(),
+            })?;
+
+        let mut scheme = Scheme::default();
+
+        for line in registry {
+            // Registry lines are "root, key, value name, flags, value".
+            let fields: Vec<String> = split_fields(line)
+                .iter()
+                .map(|field| substitute(field, &strings))
+                .collect();
+            if fields.len() < 5 {
+                continue;
+            }
+
+            let key = fields[1].trim_end_matches('\\').to_lowercase();
+            let value_name = &fields[2];
+            // An unquoted value that lists several files is split up like any other fields.
+            let value = fields[4..].join(",");
+
+            if key.ends_with(r"control panel\cursors\schemes") {
+                scheme.name = Some(value_name.clone());
+
+                for (file, (name, role)) in value.split(',').zip(CursorRole::scheme_order()) {
+                    scheme.add(role, name, file, dir);
+                }
+            } else if key.ends_with(r"control panel\cursors") {
+                if value_name.is_empty() {
+                    // The default value of the key is the name of the active scheme.
+                    if scheme.name.is_none() {
+                        scheme.name = Some(value.clone());
+                    }
+                } else if CursorRole::is_registry_name(value_name) {
+                    let role = CursorRole::from_registry_name(value_name);
+                    scheme.add(role, value_name, &value, dir);
+                }
+            }
+        }
+
+        if scheme.files.is_empty() {
+            return Err(Error::InvalidInf {
+                reason: "the [Scheme.Reg] section doesn't list any cursor files".to_string(),
+            });
+        }
+
+        Ok(scheme)
+    }
+
+    /// Record the `file` for a `role`, or note that it was skipped if there's no such role.
+    fn add(&mut self, role: Option<CursorRole>, name: &str, file: &str, dir: &Path) {
+        // Installed paths look like "%10%\Cursors\Scheme\arrow.cur"; only the file name matters here.
+        let file_name = file.trim().rsplit(['\\', '/']).next().unwrap_or("");
+        if file_name.is_empty() {
+            return;
+        }
+
+        match role {
+            Some(role) => {
+                self.files
+                    .entry(role)
+                    .or_insert_with(|| dir.join(file_name));
+            }
+            None if !self.skipped.iter().any(|skipped| skipped == name) => {
+                self.skipped.push(name.to_string())
+            }
+            None => {}
+        }
+    }
+}
+
+/// Decode an INF file, which may be UTF-16 (with a byte order mark) or UTF-8.
+fn decode_text(bytes: &[u8]) -> Result<String> {
+    if let Some(utf16) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        let units: Vec<u16> = utf16
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        return Ok(String::from_utf16(&units)?);
+    }
+
+    let bytes = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(bytes);
+    Ok(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Split an INF file into its sections, keyed by lowercase section name.
+/// Comments and blank lines are left out.
+fn sections(text: &str) -> HashMap<String, Vec<String>> {
+    let mut sections: HashMap<String, Vec<String>> = HashMap::new();
+    let mut current = None;
+
+    for line in text.lines() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            let name = line[1..line.len() - 1].trim().to_lowercase();
+            sections.entry(name.clone()).or_default();
+            current = Some(name);
+        } else if let Some(name) = &current {
+            sections.get_mut(name).unwrap().push(line.to_string());
+        }
+    }
+
+    sections
+}
+
+/// Remove a `;` comment from the end of a line, unless it's inside quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+
+    line
+}
+
+/// Split a line into its comma-separated fields, removing quotes and surrounding whitespace.
+fn split_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+
+    for c in line.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field).trim().to_string()),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_string());
+
+    fields
+}
+
+fn unquote(value: &str) -> String {
+    value.trim_matches('"').to_string()
+}
+
+/// Replace `%name%` with the named string, and `%%` with `%`.
+/// Unknown names (like the numbered system directories, `%10%`) are replaced with nothing.
+fn substitute(text: &str, strings: &HashMap<String, String>) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('%') {
+        result.push_str(&rest[..start]);
+
+        let after = &rest[start + 1..];
+        match after.find('%') {
+            Some(0) => result.push('%'),
+            Some(end) => {
+                if let Some(value) = strings.get(&after[..end].to_lowercase()) {
+                    result.push_str(value);
+                }
+            }
+            None => {
+                result.push_str(&rest[start..]);
+                return result;
+            }
+        }
+
+        rest = &after[after.find('%').unwrap() + 1..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    fn files(scheme: &Scheme) -> Vec<(CursorRole, String)> {
+        scheme
+            .files
+            .iter()
+            .map(|(role, path)| (*role, path.to_string_lossy().into_owned()))
+            .collect()
+    }
+
+    fn in_pack(name: &str) -> String {
+        Path::new("pack").join(name).to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn reads_the_list_of_scheme_files() {
+        let text = r#"
+; Installs the "Cool" scheme.
+[Version]
+signature="$CHICAGO$"
+
+[Scheme.Reg]
+HKCU,"Control Panel\Cursors\Schemes","%SCHEME_NAME%, 100%%",0x00020000,"%10%\%CUR_DIR%\%pointer%,%10%\%CUR_DIR%\%help%,%10%\%CUR_DIR%\%work%,,,,%10%\%CUR_DIR%\%pen%,%10%\%CUR_DIR%\%unavailable%" ; every role
+
+[Strings]
+CUR_DIR     = "Cursors\Cool"
+SCHEME_NAME = "Cool; Scheme"
+pointer     = "arrow.cur"
+help        = "help.cur"
+work        = "working.ani"
+pen         = "pen.cur"
+Unavailable = "no.cur"
+"#;
+        let scheme = Scheme::parse(text, Path::new("pack")).unwrap();
+
+        assert_eq!(scheme.name.as_deref(), Some("Cool; Scheme, 100%"));
+        assert_eq!(
+            files(&scheme),
+            [
+                (CursorRole::Pointer, in_pack("arrow.cur")),
+                (CursorRole::Help, in_pack("help.cur")),
+                (CursorRole::Working, in_pack("working.ani")),
+                (CursorRole::Unavailable, in_pack("no.cur")),
+            ]
+        );
+        assert_eq!(scheme.skipped, ["NWPen"]);
+    }
+
+    #[test]
+    fn reads_a_value_for_each_role() {
+        let text = r#"
+[Scheme.Reg]
+HKCU,"Control Panel\Cursors",,0x00020000,"Other Scheme"
+HKCU,"Control Panel\Cursors\",Arrow,0x00020000,"%10%\Cursors\Other\arrow.cur"
+HKCU,"Control Panel\Cursors",hand,0x00020000,%10%\Cursors\Other\link.cur
+HKCU,"Control Panel\Cursors",NWPen,0x00020000,"pen.cur"
+HKCU,"Control Panel\Cursors",Pin,0x00020000,"pin.cur"
+HKCU,"Control Panel\Cursors",NWPen,0x00020000,"pen.cur"
+HKCU,"Control Panel\Cursors",Scheme Source,0x00010001,2
+HKCU,"Control Panel\Desktop",Arrow,0x00020000,"desktop.cur"
+"#;
+        let scheme = Scheme::parse(text, Path::new("pack")).unwrap();
+
+        assert_eq!(scheme.name.as_deref(), Some("Other Scheme"));
+        assert_eq!(
+            files(&scheme),
+            [
+                (CursorRole::Pointer, in_pack("arrow.cur")),
+                (CursorRole::Link, in_pack("link.cur")),
+            ]
+        );
+        assert_eq!(scheme.skipped, ["NWPen", "Pin"]);
+    }
+
+    #[test]
+    fn uses_the_first_file_given_for_a_role() {
+        let text = r#"
+[Scheme.Reg]
+HKCU,"Control Panel\Cursors\Schemes","Both",0x00020000,"listed.cur,help.cur"
+HKCU,"Control Panel\Cursors",Arrow,0x00020000,"value.cur"
+HKCU,"Control Panel\Cursors",IBeam,0x00020000,"text.cur"
+HKCU,"Control Panel\Cursors",,0x00020000,"Not the name"
+"#;
+        let scheme = Scheme::parse(text, Path::new("pack")).unwrap();
+
+        assert_eq!(scheme.name.as_deref(), Some("Both"));
+        assert_eq!(
+            files(&scheme),
+            [
+                (CursorRole::Pointer, in_pack("listed.cur")),
+                (CursorRole::Help, in_pack("help.cur")),
+                (CursorRole::Text, in_pack("text.cur")),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_files_without_a_scheme() {
+        let error = |text: &str| {
+            Scheme::parse(text, Path::new("pack"))
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert_eq!(
+            error("[Strings]\npointer = \"arrow.cur\"\n"),
+            "Invalid .inf file: there is no [Scheme.Reg] section"
+        );
+        assert_eq!(
+            error("[Scheme.Reg]\nHKCU,\"Control Panel\\Cursors\",,0x00020000,\"Empty\"\n"),
+            "Invalid .inf file: the [Scheme.Reg] section doesn't list any cursor files"
+        );
+    }
+
+    #[test]
+    fn decodes_utf8_and_utf16() {
+        let text = "[Strings]\r\nname = \"Flèche\"\r\n";
+        let utf16: Vec<u8> = [0xFF, 0xFE]
+            .iter()
+            .copied()
+            .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        let utf8_with_bom: Vec<u8> = [0xEF, 0xBB, 0xBF]
+            .iter()
+            .chain(text.as_bytes())
+            .copied()
+            .collect();
+
+        assert_eq!(decode_text(&utf16).unwrap(), text);
+        assert_eq!(decode_text(&utf8_with_bom).unwrap(), text);
+        assert_eq!(decode_text(text.as_bytes()).unwrap(), text);
+        assert!(decode_text(&[0xFF, 0xFE, 0x00, 0xD8]).is_err());
+    }
+
+    #[test]
+    fn opens_utf16_files() {
+        let text = "[Scheme.Reg]\r\nHKCU,\"Control Panel\\Cursors\\Schemes\",\"Flèche\",0x00020000,\"%10%\\Cursors\\flèche.cur\"\r\n";
+        let bytes: Vec<u8> = [0xFF, 0xFE]
+            .iter()
+            .copied()
+            .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+
+        let dir = env::temp_dir().join(format!("cursor-changer-inf-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("install.inf");
+        fs::write(&path, bytes).unwrap();
+        let scheme = Scheme::open(&path);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let scheme = scheme.unwrap();
+        assert_eq!(scheme.name.as_deref(), Some("Flèche"));
+        assert_eq!(scheme.files[&CursorRole::Pointer], dir.join("flèche.cur"));
+    }
+}
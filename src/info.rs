@@ -3,7 +3,7 @@ use crate::Result;
 use std::mem;
 use std::ptr::null_mut;
 use winapi::shared::minwindef::DWORD;
-use winapi::shared::windef::POINT;
+use winapi::shared::windef::{HWND, POINT};
 
 /// Wrapper around the winapi POINT type.
 pub struct CursorPosition(POINT);
@@ -22,17 +22,46 @@ impl CursorPosition {
             Some(CursorPosition(point))
         }
     }
+
+    /// Get the position's screen coordinates.
+    pub fn coordinates(&self) -> (i32, i32) {
+        (self.0.x, self.0.y)
+    }
+
+    /// Get the effective DPI of the monitor that this position lies on.
+    pub fn dpi(&self) -> u32 {
+        use winapi::shared::winerror::S_OK;
+        use winapi::um::shellscalingapi::{GetDpiForMonitor, MDT_EFFECTIVE_DPI};
+        use winapi::um::winuser::{MonitorFromPoint, MONITOR_DEFAULTTONEAREST};
+
+        // 96 DPI is 100% scaling, which we'll assume if the monitor can't be queried.
+        let mut dpi_x: u32 = 96;
+        let mut dpi_y: u32 = 96;
+
+        unsafe {
+            let monitor = MonitorFromPoint(self.0, MONITOR_DEFAULTTONEAREST);
+
+            if GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y) != S_OK {
+                return 96;
+            }
+        }
+
+        dpi_x
+    }
 }
 
 /// Represents the Prcoess under the user's cursor.
 /// Wrapper around the winapi process_id of the application under the cursor.
 pub struct Process {
     process_id: DWORD,
+
+    /// The window under the cursor that the process was found from.
+    window: HWND,
 }
 
 impl Process {
     /// Find the Process of the window at the `CursorPostion`.
-    pub fn from_position(position: CursorPosition) -> Option<Self> {
+    pub fn from_position(position: &CursorPosition) -> Option<Self> {
         use winapi::um::winuser::{GetWindowThreadProcessId, WindowFromPoint};
 
         unsafe {
@@ -47,10 +76,15 @@ impl Process {
             let mut process_id: DWORD = mem::uninitialized();
             GetWindowThreadProcessId(window, &mut process_id);
 
-            Some(Process { process_id })
+            Some(Process { process_id, window })
         }
     }
 
+    /// Get the handle of the window the process was found from, as a number.
+    pub fn window_id(&self) -> usize {
+        self.window as usize
+    }
+
     /// Get the full path of the executable corresponding to this Process.
     pub fn executable_path(&self) -> Result<String> {
         use winapi::shared::minwindef::MAX_PATH;
@@ -0,0 +1,454 @@
+//! Logging for the running cursor changer, which has no console of its own in release builds.
+//!
+//! Messages go to a log file (`cursor-changer.log` next to the configuration file, unless the `[log]` table
+//! says otherwise), and to the console the app was started from, if any. The file is rotated when it grows
+//! too large, keeping a few of the previous ones as `cursor-changer.log.1`, `cursor-changer.log.2`, and so on.
+//!
+//! Each message is written on its own line, either as text or as a JSON object.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+#[cfg(windows)]
+use std::path::Path;
+#[cfg(any(windows, test))]
+use std::sync::Mutex;
+
+use log::LevelFilter;
+#[cfg(any(windows, test))]
+use log::{Log, Metadata, Record};
+use schemars::JsonSchema;
+
+/// The name of the log file, when the configuration doesn't give one.
+#[cfg(windows)]
+pub const FILE_NAME: &str = "cursor-changer.log";
+
+/// The log file's size limit, when the configuration doesn't give one.
+pub const DEFAULT_MAX_SIZE: u64 = 1024 * 1024;
+
+/// How many rotated log files are kept, when the configuration doesn't say.
+pub const DEFAULT_KEEP: usize = 3;
+
+/// The `[log]` table of the configuration.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, JsonSchema)]
+pub struct LogConfig {
+    /// The least important messages to record. Defaults to `info`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<Level>,
+
+    /// Levels for particular parts of the app, by module, like `window = "debug"`.
+    /// These override `level` for messages from that module and the modules inside it.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub targets: BTreeMap<String, Level>,
+
+    /// The log file, relative to the configuration file that sets it.
+    /// Defaults to `cursor-changer.log` next to the configuration file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+
+    /// Start a new log file once the current one is larger than this many bytes. Defaults to 1 MiB.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<u64>,
+
+    /// How many of the previous log files to keep. Defaults to 3.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep: Option<usize>,
+
+    /// How each message is written. Defaults to `text`.
+    #[serde(default, skip_serializing_if = "LogFormat::is_text")]
+    pub format: LogFormat,
+}
+
+/// How important a message is, from most to least.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// A line like `2024-05-01 12:00:00.000 INFO  window: message`.
+    #[default]
+    Text,
+
+    /// One JSON object per line, with `time`, `level`, `target`, and `message` fields.
+    Json,
+}
+
+impl From<Level> for LevelFilter {
+    fn from(level: Level) -> LevelFilter {
+        match level {
+            Level::Off => LevelFilter::Off,
+            Level::Error => LevelFilter::Error,
+            Level::Warn => LevelFilter::Warn,
+            Level::Info => LevelFilter::Info,
+            Level::Debug => LevelFilter::Debug,
+            Level::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+impl LogFormat {
+    fn is_text(&self) -> bool {
+        *self == LogFormat::Text
+    }
+}
+
+/// Start logging as `config` says. `dir` is where the log file goes if the configuration doesn't say.
+///
+/// If the log file can't be opened, messages still go to the console, and the error is returned.
+#[cfg(windows)]
+pub fn init(config: &LogConfig, dir: &Path) -> io::Result<()> {
+    let path = match &config.file {
+        Some(file) => PathBuf::from(file),
+        None => dir.join(FILE_NAME),
+    };
+    let file = LogFile::open(
+        path,
+        config.max_size.unwrap_or(DEFAULT_MAX_SIZE),
+        config.keep.unwrap_or(DEFAULT_KEEP),
+    );
+
+    let (file, error) = match file {
+        Ok(file) => (Some(file), None),
+        Err(e) => (None, Some(e)),
+    };
+
+    let logger = Logger::new(config, file);
+    let max_level = logger.max_level();
+
+    // Logging can only be started once; later calls keep the first logger.
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(max_level);
+    }
+
+    match error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Modules are written without the crate's name in the configuration, like `window`.
+#[cfg(any(windows, test))]
+fn qualified_target(target: &str) -> String {
+    let crate_name = module_path!().split("::").next().unwrap_or_default();
+
+    if target == crate_name || target.starts_with(&format!("{}::", crate_name)) {
+        target.to_string()
+    } else {
+        format!("{}::{}", crate_name, target)
+    }
+}
+
+#[cfg(any(windows, test))]
+struct Logger {
+    level: LevelFilter,
+
+    /// Levels for modules, most specific first.
+    targets: Vec<(String, LevelFilter)>,
+
+    format: LogFormat,
+
+    /// The log file, if it could be opened.
+    file: Mutex<Option<LogFile>>,
+}
+
+#[cfg(any(windows, test))]
+impl Logger {
+    fn new(config: &LogConfig, file: Option<LogFile>) -> Logger {
+        // The most specific (longest) module comes first, so it's the one that's used.
+        let mut targets: Vec<(String, LevelFilter)> = config
+            .targets
+            .iter()
+            .map(|(target, level)| (qualified_target(target), LevelFilter::from(*level)))
+            .collect();
+        targets.sort_by_key(|(target, _)| std::cmp::Reverse(target.len()));
+
+        Logger {
+            level: config.level.map_or(LevelFilter::Info, LevelFilter::from),
+            targets,
+            format: config.format,
+            file: Mutex::new(file),
+        }
+    }
+
+    /// The level of the least important messages recorded from any module.
+    fn max_level(&self) -> LevelFilter {
+        self.targets
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.level, std::cmp::max)
+    }
+
+    /// Get the level for messages from `target`: that of the most specific module that contains it.
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .find(|(module, _)| {
+                target == module
+                    || (target.starts_with(module.as_str()) && target[module.len()..].starts_with("::"))
+            })
+            .map_or(self.level, |(_, level)| *level)
+    }
+
+    fn format(&self, record: &Record) -> String {
+        let time = chrono::Local::now();
+        let target = record
+            .target()
+            .split_once("::")
+            .map_or(record.target(), |(_, module)| module);
+
+        match self.format {
+            LogFormat::Text => format!(
+                "{} {:<5} {}: {}",
+                time.format("%Y-%m-%d %H:%M:%S%.3f"),
+                record.level(),
+                target,
+                record.args()
+            ),
+            LogFormat::Json => serde_json::json!({
+                "time": time.to_rfc3339_opts(chrono::SecondsFormat::Millis, false),
+                "level": record.level().as_str(),
+                "target": target,
+                "message": record.args().to_string(),
+            })
+            .to_string(),
+        }
+    }
+}
+
+#[cfg(any(windows, test))]
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = self.format(record);
+        eprintln!("{}", line);
+
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(log_file) = file.as_mut() {
+                // There's nowhere left to report a failure to write the log, so stop trying.
+                if let Err(e) = log_file.write_line(&line) {
+                    eprintln!("ERROR: Failed to write to {}: {}", log_file.path.display(), e);
+                    *file = None;
+                }
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(file) = file.as_mut().and_then(|log_file| log_file.file.as_mut()) {
+                let _ = file.flush();
+            }
+        }
+    }
+}
+
+/// A log file that's rotated once it's larger than `max_size`.
+pub struct LogFile {
+    path: PathBuf,
+
+    /// The open file. It's only closed while the files are being renamed.
+    file: Option<File>,
+
+    /// The size of the current file, in bytes.
+    size: u64,
+
+    max_size: u64,
+
+    /// How many previous files are kept.
+    keep: usize,
+}
+
+impl LogFile {
+    pub fn open(path: PathBuf, max_size: u64, keep: usize) -> io::Result<LogFile> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+
+        Ok(LogFile {
+            path,
+            file: Some(file),
+            size,
+            max_size,
+            keep,
+        })
+    }
+
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let length = line.len() as u64 + 1;
+        if self.size > 0 && self.size + length > self.max_size {
+            self.rotate()?;
+        }
+
+        if let Some(file) = self.file.as_mut() {
+            writeln!(file, "{}", line)?;
+            self.size += length;
+        }
+
+        Ok(())
+    }
+
+    /// Move each previous file up a number (dropping the oldest), and start a new file.
+    fn rotate(&mut self) -> io::Result<()> {
+        // Open files can't be renamed on Windows.
+        self.file = None;
+
+        let numbered = |n: usize| {
+            let mut name = self.path.clone().into_os_string();
+            name.push(format!(".{}", n));
+            PathBuf::from(name)
+        };
+
+        if self.keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for n in (1..self.keep).rev() {
+                if numbered(n).exists() {
+                    fs::rename(numbered(n), numbered(n + 1))?;
+                }
+            }
+            fs::rename(&self.path, numbered(1))?;
+        }
+
+        self.file = Some(OpenOptions::new().create(true).append(true).open(&self.path)?);
+        self.size = 0;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    fn logger(config: &str) -> Logger {
+        Logger::new(&toml::from_str(config).unwrap(), None)
+    }
+
+    fn format(logger: &Logger, target: &str) -> String {
+        logger.format(
+            &Record::builder()
+                .level(log::Level::Warn)
+                .target(target)
+                .args(format_args!("the \"cursor\" is missing"))
+                .build(),
+        )
+    }
+
+    #[test]
+    fn uses_the_level_of_the_most_specific_module() {
+        let logger = logger(
+            r#"
+            level = "warn"
+
+            [targets]
+            window = "debug"
+            "window::info" = "error"
+            windows_cursor_changer = "info"
+            "#,
+        );
+
+        let level = |module: &str| logger.level_for(&format!("windows_cursor_changer::{}", module));
+        assert_eq!(level("window"), LevelFilter::Debug);
+        assert_eq!(level("window::info"), LevelFilter::Error);
+        assert_eq!(level("window::info::details"), LevelFilter::Error);
+        assert_eq!(level("window::other"), LevelFilter::Debug);
+        assert_eq!(level("windows"), LevelFilter::Info);
+        assert_eq!(level("config"), LevelFilter::Info);
+        assert_eq!(logger.level_for("some_dependency"), LevelFilter::Warn);
+        assert_eq!(logger.max_level(), LevelFilter::Debug);
+
+        assert_eq!(self::logger("").level_for("anything"), LevelFilter::Info);
+        assert_eq!(self::logger("").max_level(), LevelFilter::Info);
+    }
+
+    #[test]
+    fn writes_text_and_json_lines() {
+        let text = format(&logger(""), "windows_cursor_changer::window");
+        let (time, message) = text.split_at(23);
+        assert!(
+            chrono::NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S%.3f").is_ok(),
+            "{}",
+            text
+        );
+        assert_eq!(message, " WARN  window: the \"cursor\" is missing");
+
+        let json_logger = logger("format = \"json\"");
+        let line = format(&json_logger, "windows_cursor_changer::window");
+        assert!(!line.contains('\n'));
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        let object = json.as_object().unwrap();
+        assert_eq!(
+            object.keys().collect::<Vec<_>>(),
+            ["level", "message", "target", "time"]
+        );
+        assert_eq!(json["level"], "WARN");
+        assert_eq!(json["target"], "window");
+        assert_eq!(json["message"], "the \"cursor\" is missing");
+        assert!(chrono::DateTime::parse_from_rfc3339(json["time"].as_str().unwrap()).is_ok());
+
+        // Messages from other crates keep their whole target.
+        let line = format(&json_logger, "some_dependency");
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["target"], "some_dependency");
+    }
+
+    #[test]
+    fn rotates_files_once_they_are_too_large() {
+        let dir = env::temp_dir().join(format!("cursor-changer-logging-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("logs").join("test.log");
+        let read = |suffix: &str| fs::read_to_string(format!("{}{}", path.display(), suffix)).ok();
+
+        // Each line is 7 bytes, so two fit in a file.
+        let mut file = LogFile::open(path.clone(), 20, 2).unwrap();
+        for n in 1..=7 {
+            file.write_line(&format!("line {}", n)).unwrap();
+        }
+        drop(file);
+        assert_eq!(read("").as_deref(), Some("line 7\n"));
+        assert_eq!(read(".1").as_deref(), Some("line 5\nline 6\n"));
+        assert_eq!(read(".2").as_deref(), Some("line 3\nline 4\n"));
+        assert_eq!(read(".3"), None);
+
+        // Reopening a file counts what's already in it.
+        let mut file = LogFile::open(path.clone(), 20, 2).unwrap();
+        file.write_line("line 8").unwrap();
+        file.write_line("line 9").unwrap();
+        drop(file);
+        assert_eq!(read("").as_deref(), Some("line 9\n"));
+        assert_eq!(read(".1").as_deref(), Some("line 7\nline 8\n"));
+        assert_eq!(read(".2").as_deref(), Some("line 5\nline 6\n"));
+
+        // A line that's too large on its own still goes in a file.
+        let long = "x".repeat(30);
+        let mut file = LogFile::open(path.clone(), 20, 0).unwrap();
+        file.write_line(&long).unwrap();
+        file.write_line("line 10").unwrap();
+        drop(file);
+        assert_eq!(read("").as_deref(), Some("line 10\n"));
+        assert_eq!(read(".1").as_deref(), Some("line 7\nline 8\n"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
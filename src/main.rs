@@ -1,5 +1,5 @@
-// Let's put this so that it won't open console
-// comment this out to see the print logs. 
+// Let's put this so that it won't open console.
+// Messages are written to a log file instead (see logging.rs).
 #![windows_subsystem = "windows"]
 
 extern crate serde;
@@ -7,118 +7,523 @@ extern crate toml;
 #[cfg(windows)]
 extern crate winapi;
 #[macro_use]
+extern crate log;
+#[macro_use]
 extern crate serde_derive;
+#[macro_use]
+extern crate structopt;
 
 
 // https://docs.rs/winapi/*/x86_64-pc-windows-msvc/winapi/um/libloaderapi/index.html?search=winuser
 
+mod ani;
+mod animation;
+mod backend;
+mod cli;
+mod condition;
 mod config;
+mod control;
+mod cur;
+mod cursor_data;
+mod diagnostic;
+mod effects;
 mod error;
+mod font;
+mod format;
+mod frames;
+mod generated;
+mod history;
+mod hotkey;
+mod image;
+mod inf;
+mod logging;
+#[cfg(windows)]
 mod info;
+#[cfg(windows)]
+mod overlay;
+mod pack;
+mod pool;
+mod preview;
+mod pulse;
+mod role;
+mod spanned;
+mod stats;
+mod switching;
+#[cfg(windows)]
 mod system;
+#[cfg(test)]
+mod testing;
+mod trace;
+mod trail;
+mod transition;
+#[cfg(windows)]
 mod window;
+mod xcursor;
 
-use std::sync::{Arc, Mutex};
+#[cfg(windows)]
+use std::sync::{mpsc, Arc, Mutex};
+#[cfg(windows)]
 use std::{thread, time};
 
+#[cfg(windows)]
 use winapi::shared::windef::HCURSOR;
 
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDateTime;
+
+use structopt::StructOpt;
+
+use crate::backend::Backend;
+use crate::control::Control;
+use crate::format::Format;
 
 pub type Result<T> = std::result::Result<T, error::Error>;
 
 /// Wrapper around the HCURSOR winapi type
+#[cfg(windows)]
 #[derive(Debug)]
 pub struct CursorHandle(HCURSOR);
 
 type CursorId = u32;
 
+/// A loaded cursor's handle, and its handle for each role that uses another file.
+type Handles<H> = (H, Vec<(role::CursorRole, H)>);
+
+/// How a cursor's image should be resized before it's loaded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scaling {
+    /// Multiply the system cursor size by this factor.
+    Factor(f64),
+
+    /// Use this many pixels at 100% display scaling, growing with the display's DPI.
+    Size(u32),
+}
+
+impl Scaling {
+    /// Get the size to load a cursor at, given the size Windows uses for cursors on the current monitor.
+    pub fn apply(self, system_size: u32) -> u32 {
+        let size = match self {
+            Scaling::Factor(factor) => (f64::from(system_size) * factor).round() as u32,
+            // Windows' cursor size at 100% scaling is 32 pixels. Huge sizes are worked out without overflowing.
+            Scaling::Size(size) => {
+                (u64::from(size) * u64::from(system_size) / 32).min(u64::from(cur::MAX_SIZE)) as u32
+            }
+        };
+
+        size.clamp(1, cur::MAX_SIZE)
+    }
+}
+
 /// Cursor struct
 #[derive(Debug)]
-pub struct Cursor {
+pub struct Cursor<H> {
     /// A unique integer identifer for this Cursor
     id: CursorId,
 
     /// Unique identifer for this Cursor
     name: String,
 
+    /// The files the cursor is loaded from.
+    files: CursorFiles,
+
+    /// Handle to the Cursor loaded by the backend.
+    handle: H,
+
+    /// Handles to the files in `roles`, loaded by the backend.
+    role_handles: Vec<(role::CursorRole, H)>,
+}
+
+/// A cursor's files, and how to turn them into cursors.
+#[derive(Debug)]
+pub struct CursorFiles {
     /// Path to this Cursor's .cur or .ani file.
     path: String,
 
-    /// Handle to the Cursor loaded by Windows.
-    handle: CursorHandle,
+    /// Alternative files for specific cursor sizes, sorted by ascending size.
+    variants: Vec<config::CursorVariant>,
+
+    /// How the cursor's image should be resized, if at all.
+    scaling: Option<Scaling>,
+
+    /// The hotspot for cursors that are converted from .png images.
+    hotspot: (u32, u32),
+
+    /// Effects used to generate this cursor from its files.
+    effects: Vec<effects::Effect>,
+
+    /// Files for system cursor roles that don't use `path`.
+    roles: Vec<(role::CursorRole, String)>,
 }
 
-impl Cursor {
-    /// Create a cursor and load it to acquire a usable handle to it.
-    pub fn new(id: CursorId, name: String, path: String) -> Cursor {
-        Cursor {
-            id: id,
-            name: name,
-            handle: system::get_cursor(&path),
-            path: path,
+impl<H> Cursor<H> {
+    /// Create a cursor and load it at the given `size` to acquire a usable handle to it.
+    pub fn new<B: Backend<Handle = H>>(
+        id: CursorId,
+        config: config::Cursor,
+        scaling: Option<Scaling>,
+        size: u32,
+        backend: &mut B,
+    ) -> Result<Cursor<H>> {
+        let mut variants = config.variant;
+        variants.sort_by_key(|variant| variant.size);
+
+        let hotspot = config.hotspot.map_or((0, 0), |[x, y]| (x, y));
+
+        let files = CursorFiles {
+            path: config.path,
+            variants,
+            scaling,
+            hotspot,
+            effects: config.effect,
+            roles: config.roles.into_iter().collect(),
+        };
+
+        let (handle, role_handles) = files.load(size, backend)?;
+
+        Ok(Cursor {
+            id,
+            name: config.name,
+            files,
+            handle,
+            role_handles,
+        })
+    }
+
+    /// Get the name of this Cursor.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Replace the loaded handles with ones that are loaded at a new `size`.
+    pub fn reload<B: Backend<Handle = H>>(&mut self, size: u32, backend: &mut B) -> Result<()> {
+        let (handle, role_handles) = self.files.load(size, backend)?;
+
+        backend.destroy(&self.handle);
+        for (_, role_handle) in &self.role_handles {
+            backend.destroy(role_handle);
         }
+
+        self.handle = handle;
+        self.role_handles = role_handles;
+
+        Ok(())
     }
+}
 
+impl CursorFiles {
     /// Get the Path to this Cursor's image file.
     pub fn path(&self) -> &Path {
         Path::new(&self.path)
     }
+
+    /// Whether a file needs to be converted to a .cur file before it's loaded:
+    /// if it's a .png image or an Xcursor file, if the cursor is scaled, or if it has effects.
+    pub fn needs_converting(&self, path: &str) -> bool {
+        self.scaling.is_some()
+            || !self.effects.is_empty()
+            || cursor_data::has_extension(path, "png")
+            || xcursor::is_xcursor_file(path)
+    }
+
+    /// Generate a .cur file from `path` at `size` pixels, with the cursor's hotspot and effects.
+    pub fn convert(&self, path: &str, size: u32) -> Result<PathBuf> {
+        generated::converted_cursor(
+            path,
+            self.hotspot,
+            self.scaling.map(|_| size),
+            &self.effects,
+        )
+    }
+
+    /// Decode the image that's shown at `size` pixels, as it would be loaded: scaled, and with its effects.
+    /// An animated cursor's first frame is used.
+    pub fn image(&self, size: u32) -> Result<cur::CursorImage> {
+        let size = self.scaling.map_or(size, |scaling| scaling.apply(size));
+        let path = Self::path_for_size(&self.path, &self.variants, size);
+
+        let mut cursor = cursor_data::CursorData::open(path, self.hotspot)?.resampled(&[size]);
+        if !self.effects.is_empty() {
+            cursor = effects::apply(&cursor, &self.effects)?;
+        }
+
+        cursor
+            .frames()
+            .first()
+            .and_then(|frame| frame.best_image(size))
+            .cloned()
+            .ok_or_else(|| error::Error::InvalidCursorData {
+                reason: format!("{} has no images", path),
+            })
+    }
+
+    /// Load the file best suited to `size`, and the file for each role.
+    fn load<B: Backend>(&self, size: u32, backend: &mut B) -> Result<Handles<B::Handle>> {
+        let size = self.scaling.map_or(size, |scaling| scaling.apply(size));
+        let path = Self::path_for_size(&self.path, &self.variants, size);
+
+        let handle = backend.load(self, path, size)?;
+        let role_handles: Vec<(role::CursorRole, B::Handle)> = self
+            .roles
+            .iter()
+            .map(|(role, path)| Ok((*role, backend.load(self, path, size)?)))
+            .collect::<Result<_>>()?;
+
+        Ok((handle, role_handles))
+    }
+
+    /// Pick the smallest variant that is at least `size` pixels,
+    /// falling back to the main `path` if none of them are large enough.
+    fn path_for_size<'a>(path: &'a str, variants: &'a [config::CursorVariant], size: u32) -> &'a str {
+        variants
+            .iter()
+            .find(|variant| variant.size >= size)
+            .map(|variant| variant.path.as_str())
+            .unwrap_or(path)
+    }
+}
+
+/// The cursor a rule uses: one cursor, or a cursor picked from a pool.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Choice {
+    Cursor(CursorId),
+
+    /// The index of the pool in `CursorChanger::pools`.
+    Pool(usize),
 }
 
 #[derive(Debug)]
 pub struct Application {
-    /// The ID of the Cursor to use when the mouse is over this Application.
-    cursor_id: CursorId,
+    /// The Cursor (or cursor pool) to use when the mouse is over this Application.
+    cursor: Choice,
 
     /// The path (or partial path) that will be used to identify this Application.
     /// Comparison will be done by checking if the full path of the executable
     /// under the cursor `ends_with` this `path`, so this may be a full absolute path,
     /// or just the exe name or partial path.
     path: String,
+
+    /// The rule this Application makes, for the history and for pools to tell when it's entered.
+    rule: String,
+
+    /// What must be held down for this Application to match, if anything.
+    when: Option<condition::Condition>,
+
+    /// How long the pointer must stay over this Application before its cursor is used, if not the usual time.
+    debounce: Option<chrono::Duration>,
+
+    /// How long the pointer must stay away from this Application before the system cursors are restored,
+    /// if not the usual time.
+    reset_delay: Option<chrono::Duration>,
+
+    /// How to move between this Application's cursor and others, if not the usual way.
+    transition: Option<transition::Transition>,
+
+    /// Whether to show a trail behind the pointer over this Application.
+    trail: bool,
+
+    /// The pulse to show around the pointer when this Application's cursor is activated, if not the usual one.
+    pulse: Option<pulse::Pulse>,
 }
 
 impl Application {
-    pub fn new(cursor: CursorId, path: String) -> Self {
+    fn new(cursor: Choice, config: &config::Application) -> Self {
         Application {
-            cursor_id: cursor,
-            path: path,
+            cursor,
+            path: config.path.clone(),
+            rule: config.rule(),
+            when: config.when.clone(),
+            debounce: config.debounce.map(switching::milliseconds),
+            reset_delay: config.reset_delay.map(switching::milliseconds),
+            transition: config.transition.clone(),
+            trail: config.trail,
+            pulse: config.pulse.clone(),
         }
     }
 }
 
-#[derive(Debug)]
-pub struct CursorChanger {
+/// The rules for choosing a cursor, built from the top-level configuration and the active profile.
+/// They're replaced all at once when switching profiles, so they're never partly from each.
+#[derive(Debug, Default)]
+struct Rules {
+    /// The name of the profile these rules are from, if any.
+    profile: Option<String>,
+
+    /// Monitored applications: the profile's, and then the top-level ones.
+    applications: Vec<Application>,
+
+    /// The cursor (or cursor pool) to use when no application matches, or `None` to use the Windows cursor.
+    default_cursor: Option<Choice>,
+}
+
+impl Rules {
+    fn application_matching(&self, exe_path: &str, held: &condition::Held) -> Option<&Application> {
+        self.applications.iter().find(|app| {
+            exe_path.ends_with(&app.path) && app.when.as_ref().is_none_or(|when| when.holds(held))
+        })
+    }
+}
+
+/// A generated animation (a transition or a pulse) that's being shown, until the active cursor takes its place.
+struct Playing<H> {
+    /// The animation, loaded as a cursor.
+    cursor: Cursor<H>,
+
+    /// When the animation is over.
+    until: NaiveDateTime,
+
+    /// The pulse to show once the animation is over, before the active cursor.
+    then: Option<pulse::Pulse>,
+}
+
+pub struct CursorChanger<B: Backend> {
+    /// Where the pointer is read from, and the cursors are loaded and set.
+    backend: B,
+
     /// Lookup map to associate the cursor `name` with a unique numerical CursorId
     cursor_ids: HashMap<String, CursorId>,
 
     /// Map that associates a cursor's unique `name` with the cursor itself.
-    cursors: HashMap<CursorId, Cursor>,
+    cursors: HashMap<CursorId, Cursor<B::Handle>>,
 
-    /// Monitored applications
-    applications: Vec<Application>,
+    /// Every cursor pool, and the index of each one by its name.
+    pools: Vec<pool::Pool<CursorId>>,
+    pool_ids: HashMap<String, usize>,
+
+    /// The top-level monitored applications, which every profile's are added to.
+    config_applications: Vec<config::Application>,
+
+    /// Every profile, by name.
+    profiles: BTreeMap<String, config::Profile>,
+
+    /// Run-time state: The rules in use, from the active profile.
+    rules: Rules,
 
     /// Run-time state: Which custom cursor is currently active, or is it the Windows system cursor (`None`).
     active_cursor: Option<CursorId>,
+
+    /// Run-time state: The rule that matched on the last tick, so that pools can tell when their rule is entered.
+    last_rule: Option<String>,
+
+    /// How long to wait before switching cursors, unless an application says otherwise.
+    switching: switching::SwitchingConfig,
+
+    /// Run-time state: A switch that's waiting until the pointer has stayed long enough.
+    debouncer: switching::Debouncer<Option<CursorId>>,
+
+    /// Run-time state: How long to wait before restoring the system cursors, if the application whose cursor
+    /// is active says.
+    active_reset_delay: Option<chrono::Duration>,
+
+    /// Run-time state: How to move away from the active cursor, if the application whose cursor is active says.
+    active_transition: Option<transition::Transition>,
+
+    /// Run-time state: The transition or pulse that's showing in place of the active cursor, if one is.
+    playing: Option<Playing<B::Handle>>,
+
+    /// How the trail behind the pointer looks, over applications that have one.
+    trail: trail::TrailConfig,
+
+    /// Run-time state: Where the pointer has been recently, for the trail.
+    motion: trail::Motion,
+
+    /// Run-time state: The image the trail is drawn with, which is the active cursor's at the cursor size
+    /// (or `None` if it couldn't be read).
+    trail_image: Option<(CursorId, u32, Option<cur::CursorImage>)>,
+
+    /// Run-time state: When the trail was last drawn, while it's shown.
+    trail_drawn: Option<NaiveDateTime>,
+
+    /// Run-time state: The size, in pixels, at which the cursors are currently loaded.
+    /// This follows the DPI of the monitor under the pointer.
+    cursor_size: u32,
+
+    /// Run-time state: The last error from looking up the window under the pointer, which is only logged
+    /// when it changes, since the same error usually happens on every tick until the pointer moves away.
+    last_error: Option<String>,
+
+    /// Usage statistics, if they're being recorded.
+    stats: Option<stats::Recorder>,
+
+    /// The most recent decisions, and where they're written when asked for.
+    history: history::History,
+    history_path: PathBuf,
 }
 
 
-impl CursorChanger {
-    fn from_config(config: config::Config) -> Result<CursorChanger> {
-        let mut changer = CursorChanger::new();
-        changer.add_cursors(config.cursor)?;
-        changer.add_applications(config.application)?;
+impl<B: Backend> CursorChanger<B> {
+    fn from_config(config: config::Config, backend: B) -> Result<CursorChanger<B>> {
+        let mut changer = CursorChanger::new(backend);
+        let scaling = config.scaling();
+        changer.add_cursors(config.cursor, scaling)?;
+        changer.add_pools(&config.cursor_pool)?;
+        changer.config_applications = config.application;
+        changer.profiles = config.profile;
+        changer.switching = config.switching.unwrap_or_default();
+        changer.trail = config.trail.unwrap_or_default();
+
+        let history_config = config.history.unwrap_or_default();
+        changer.history = match history::History::from_config(&history_config, &config.dir) {
+            Ok(history) => history,
+            Err(e) => {
+                error!("Failed to open the history journal: {}", e);
+                history::History::from_config(&history::HistoryConfig::default(), &config.dir)?
+            }
+        };
+        changer.history_path = config.dir.join(history::FILE_NAME);
+
+        let stats_config = config.stats.unwrap_or_default();
+        if stats_config.enabled {
+            // Not being able to record statistics shouldn't stop the cursors from working.
+            match stats::Recorder::open(stats_config.path(&config.dir)) {
+                Ok(recorder) => changer.stats = Some(recorder),
+                Err(e) => error!("Failed to read the usage statistics: {}", e),
+            }
+        }
+
+        // Check every profile now, rather than finding a mistake when switching to it.
+        for name in changer.profiles.keys() {
+            changer.build_rules(Some(name.as_str()))?;
+        }
+
+        changer.switch_profile(config.active_profile.map(|name| name.into_inner()))?;
 
         Ok(changer)
     }
 
-    fn new() -> CursorChanger {
+    fn new(mut backend: B) -> CursorChanger<B> {
+        // Windows' cursor size at 100% scaling is 32 pixels, if the pointer can't be found.
+        let cursor_size = backend
+            .observe()
+            .pointer
+            .map_or(32, |pointer| pointer.cursor_size);
+
         CursorChanger {
+            backend,
             cursor_ids: HashMap::new(),
             cursors: HashMap::new(),
-            applications: Vec::new(),
+            pools: Vec::new(),
+            pool_ids: HashMap::new(),
+            config_applications: Vec::new(),
+            profiles: BTreeMap::new(),
+            rules: Rules::default(),
             active_cursor: None,
+            last_rule: None,
+            switching: switching::SwitchingConfig::default(),
+            debouncer: switching::Debouncer::default(),
+            active_reset_delay: None,
+            active_transition: None,
+            playing: None,
+            trail: trail::TrailConfig::default(),
+            motion: trail::Motion::default(),
+            trail_image: None,
+            trail_drawn: None,
+            cursor_size,
+            last_error: None,
+            stats: None,
+            history: history::History::new(0, None),
+            history_path: PathBuf::from(history::FILE_NAME),
         }
     }
 
@@ -127,30 +532,31 @@ impl CursorChanger {
     }
 
     /// Copy configuration details for Cursors into the configuration `cursors` map.
-    fn add_cursors(&mut self, cursors: Vec<config::Cursor>) -> Result<()> {
+    /// Cursors that don't specify their own scaling use the global `scaling`.
+    fn add_cursors(&mut self, cursors: Vec<config::Cursor>, scaling: Option<Scaling>) -> Result<()> {
         // Find the max existing ID, or default to zero if there are no existing IDs.
         let max_id = self.cursor_ids.values().max().unwrap_or(&0);
 
-        // This will keep track of the next ID to assign to each new cursor.
-        let mut next_id = max_id + 1;
-
-        for config_cursor in cursors.into_iter() {
-            let cursor = Cursor::new(next_id, config_cursor.name, config_cursor.path);
-
+        // Each new cursor is given the next ID.
+        for (next_id, config_cursor) in (max_id + 1..).zip(cursors) {
             // Check to make sure there isn't already a cursor using this unique `name`.
-            if self.cursor_ids.contains_key(&cursor.name) {
+            if self.cursor_ids.contains_key(&config_cursor.name) {
                 return Err(error::Error::DuplicateCursorName {
-                    name: cursor.name.clone(),
+                    name: config_cursor.name.clone(),
                 });
             }
 
-            // Check to make sure the file specified by the `path` exists.
-            if !cursor.path().exists() {
-                return Err(error::Error::MissingCursorFileError {
-                    name: cursor.name.clone(),
-                    path: cursor.path.clone(),
-                });
-            }
+            // Check to make sure the files specified by the `path` and any variants exist.
+            config_cursor.check_files()?;
+
+            let cursor_scaling = config_cursor.scaling().or(scaling);
+            let cursor = Cursor::new(
+                next_id,
+                config_cursor,
+                cursor_scaling,
+                self.cursor_size,
+                &mut self.backend,
+            )?;
 
             let _existing = self.cursor_ids.insert(cursor.name.clone(), cursor.id);
 
@@ -159,79 +565,509 @@ impl CursorChanger {
 
             // Insert it into the map for easy lookup by `name`.
             self.cursors.insert(cursor.id, cursor);
+        }
+
+        Ok(())
+    }
 
-            // Increment the new Cursor ID.
-            next_id += 1;
+    /// Set up the cursor pools, whose cursors must all have been added already.
+    fn add_pools(&mut self, pools: &[pool::PoolConfig]) -> Result<()> {
+        for config_pool in pools {
+            let name = config_pool.name.get_ref();
+            if self.cursor_ids.contains_key(name) || self.pool_ids.contains_key(name) {
+                return Err(error::Error::DuplicateCursorName { name: name.clone() });
+            }
+
+            let cursors = config_pool
+                .cursors
+                .iter()
+                .map(|cursor| self.cursor_id(cursor.get_ref()))
+                .collect::<Result<Vec<_>>>()?;
+
+            self.pool_ids.insert(name.clone(), self.pools.len());
+            self.pools.push(pool::Pool::new(config_pool, cursors));
         }
 
         Ok(())
     }
 
-    /// Insert tracked applications into the Config `applications` map.
-    /// This will check to make sure that there exists a Cursor identified
-    /// by the Application's `cursor` name.
-    fn add_applications(&mut self, applications: Vec<config::Application>) -> Result<()> {
-        for config_application in applications.into_iter() {
-            // Try to find the ID of the cursor, given the cursor's name.
-            let cursor_id = match self.cursor_ids.get(&config_application.cursor) {
-                // If we found it, use that ID.
-                Some(id) => id,
-                // If the name did not return an ID, quit with an error.
-                None => {
-                    return Err(error::Error::MissingCursorNameError {
-                        name: config_application.cursor.clone(),
-                    });
+    fn cursor_id(&self, name: &str) -> Result<CursorId> {
+        self.cursor_ids
+            .get(name)
+            .copied()
+            .ok_or_else(|| error::Error::MissingCursorNameError {
+                name: name.to_string(),
+            })
+    }
+
+    /// Find the cursor or cursor pool that a rule uses by its name.
+    fn choice(&self, name: &str) -> Result<Choice> {
+        match self.pool_ids.get(name) {
+            Some(&pool) => Ok(Choice::Pool(pool)),
+            None => self.cursor_id(name).map(Choice::Cursor),
+        }
+    }
+
+    /// Build the rules for a profile (or for no profile), checking that every cursor they use exists.
+    fn build_rules(&self, profile_name: Option<&str>) -> Result<Rules> {
+        let profile = match profile_name {
+            Some(name) => Some(self.profiles.get(name).ok_or_else(|| {
+                error::Error::UnknownProfile {
+                    name: name.to_string(),
                 }
-            };
+            })?),
+            None => None,
+        };
+
+        let mut applications = Vec::new();
+        let profile_applications = profile.iter().flat_map(|profile| &profile.application);
+        for config_application in profile_applications.chain(&self.config_applications) {
+            let choice = self.choice(config_application.cursor.get_ref())?;
+            applications.push(Application::new(choice, config_application));
+        }
+
+        let default_name = profile.and_then(|profile| profile.default.as_ref());
+        let default_cursor = match default_name.map(|name| name.get_ref().as_str()) {
+            Some(name) => Some(self.choice(name)?),
+            None => None,
+        };
+
+        Ok(Rules {
+            profile: profile_name.map(str::to_string),
+            applications,
+            default_cursor,
+        })
+    }
 
-            let application = Application::new(*cursor_id, config_application.path);
+    /// Switch to a profile, or to no profile. The current rules are kept if the profile's can't be built.
+    fn switch_profile(&mut self, profile_name: Option<String>) -> Result<()> {
+        self.rules = self.build_rules(profile_name.as_deref())?;
 
-            self.applications.push(application);
+        match &self.rules.profile {
+            Some(name) => info!("Switched to the profile \"{}\".", name),
+            None => info!("Not using a profile."),
         }
 
         Ok(())
     }
 
+    /// Carry out an instruction from a hotkey or another process.
+    fn handle_control(&mut self, control: Control) {
+        self.backend.control(&control);
+
+        match control {
+            Control::SwitchProfile(name) => {
+                if let Err(e) = self.switch_profile(name) {
+                    error!("{}", e);
+                }
+            }
+            Control::SaveStats => self.save_stats(),
+            Control::SaveHistory => self.save_history(),
+        }
+    }
+
+    /// Record what's under the pointer (`application`, if it's over a window) at `now` in the usage statistics.
+    fn record_stats(&mut self, now: NaiveDateTime, application: Option<&str>) {
+        let cursors = &self.cursors;
+        let cursor = self
+            .active_cursor
+            .and_then(|cursor_id| cursors.get(&cursor_id))
+            .map(|cursor| cursor.name.as_str());
+
+        if let Some(recorder) = &mut self.stats {
+            if let Err(e) = recorder.observe(now, application, cursor) {
+                error!("Failed to save the usage statistics: {}", e);
+            }
+        }
+    }
+
+    fn save_history(&self) {
+        if let Err(e) = self.history.save(&self.history_path) {
+            error!("Failed to save the history: {}", e);
+        }
+    }
+
+    fn save_stats(&self) {
+        if let Some(recorder) = &self.stats {
+            if let Err(e) = recorder.save() {
+                error!("Failed to save the usage statistics: {}", e);
+            }
+        }
+    }
+
     pub fn tick(&mut self) {
-        // Get the full path to the executable of the window under the cursor (if any).
-        match Self::get_process_under_cursor() {
-            Ok(Some(exe_path)) => {
-                // Get the ID of the cursor to use for this application (or None)
-                let new_cursor_id = match self.application_matching(&exe_path) {
-                    Some(application) => Some(application.cursor_id),
+        use history::{Action, Decision};
+
+        let observation = self.backend.observe();
+
+        self.finish_playing(observation.time);
+
+        // If the pointer couldn't be found, there's nothing to do but hide the trail.
+        let pointer = match observation.pointer {
+            Some(pointer) => pointer,
+            None => {
+                self.update_trail(false, observation.time);
+                return;
+            }
+        };
+
+        // Reload the cursors if the pointer moved onto a monitor with a different DPI.
+        self.set_cursor_size(pointer.cursor_size);
+
+        let now = observation.time;
+        self.motion.record(now, pointer.position, self.trail.span());
+
+        // The window under the pointer (if any), and the full path to its executable.
+        let window = observation.window.as_ref().map(|window| window.id);
+        let (exe_path, error) = match observation.window {
+            Some(window) => (window.executable, window.error),
+            None => (None, None),
+        };
+        match &error {
+            Some(message) => {
+                if self.last_error.as_ref() != Some(message) {
+                    warn!("Failed to find the application under the pointer: {}", message);
+                    self.last_error = Some(message.clone());
+                }
+            }
+            None => self.last_error = None,
+        }
+
+        let mut trail = false;
+        let (rule, action) = match &exe_path {
+            Some(exe_path) => {
+                trace!("The pointer is over {}.", exe_path);
+
+                // Get the cursor to use for this application (or None), and the rule that chose it.
+                let application = self.rules.application_matching(exe_path, &observation.held);
+                let (rule, choice) = match application {
+                    Some(application) => (Some(application.rule.clone()), Some(application.cursor)),
+                    None => {
+                        let rule = match (&self.rules.profile, self.rules.default_cursor) {
+                            (Some(profile), Some(_)) => {
+                                Some(format!("default cursor of the profile \"{}\"", profile))
+                            }
+                            _ => None,
+                        };
+                        (rule, self.rules.default_cursor)
+                    }
+                };
+
+                // A pool picks a cursor when the rule that uses it is entered, and may pick another as time passes.
+                let entered = rule != self.last_rule;
+                self.last_rule = rule.clone();
+                let new_cursor_id = match choice {
+                    Some(Choice::Cursor(cursor_id)) => Some(cursor_id),
+                    Some(Choice::Pool(pool)) => {
+                        let pool = &mut self.pools[pool];
+                        let previous = pool.current();
+                        let picked = pool.pick(entered, now);
+                        if let Some(cursor_id) = picked.filter(|&picked| previous != Some(picked)) {
+                            info!(
+                                "The cursor pool \"{}\" picked \"{}\".",
+                                pool.name, self.cursors[&cursor_id].name
+                            );
+                        }
+                        picked
+                    }
                     None => None,
                 };
 
-                // If there was a matching application, set the cursor for it.
-                // Note: this was broken into two `match` blocks to alleviate "cannot borrow `*self` as mutable more than once" errors.
-                match new_cursor_id {
-                    Some(cursor_id) => self.set_cursor(cursor_id),
-                    None => self.reset_to_default_cursor(),
+                trail = application.is_some_and(|application| application.trail);
+                let reset_delay = application.and_then(|application| application.reset_delay);
+                // Entering an application uses its transition, and leaving one uses the one it was entered with.
+                let entering = application.and_then(|application| application.transition.clone());
+                let pulse = application
+                    .and_then(|application| application.pulse.clone())
+                    .or_else(|| self.switching.pulse.clone());
+
+                // Wait until the pointer has wanted the new cursor for long enough, so it doesn't flicker.
+                let delay = match new_cursor_id {
+                    Some(_) => application
+                        .and_then(|application| application.debounce)
+                        .unwrap_or_else(|| self.switching.debounce()),
+                    None => self
+                        .active_reset_delay
+                        .unwrap_or_else(|| self.switching.reset_delay()),
+                };
+                let ready = self
+                    .debouncer
+                    .ready(&self.active_cursor, new_cursor_id, delay, now);
+
+                let transition = entering
+                    .clone()
+                    .or_else(|| self.active_transition.clone())
+                    .or_else(|| self.switching.transition.clone());
+
+                // Once the debouncer is ready, switch to the application's cursor, or back to the system cursors.
+                let action = match (ready, new_cursor_id) {
+                    (true, Some(cursor_id)) => {
+                        self.switch_to(Some(cursor_id), transition.as_ref(), pulse.as_ref(), now);
+                        self.active_reset_delay = reset_delay;
+                        self.active_transition = entering;
+                        Action::Apply {
+                            cursor: self.cursors[&cursor_id].name.clone(),
+                        }
+                    }
+                    (true, None) => {
+                        self.switch_to(None, transition.as_ref(), None, now);
+                        self.active_reset_delay = None;
+                        self.active_transition = None;
+                        Action::Reset
+                    }
+                    (false, cursor_id) => Action::Wait {
+                        cursor: cursor_id.map(|cursor_id| self.cursors[&cursor_id].name.clone()),
+                    },
+                };
+
+                (rule, action)
+            }
+            // No window under the cursor, or it couldn't be identified.
+            None => (None, Action::Keep),
+        };
+
+        self.update_trail(trail, now);
+
+        let decision = Decision {
+            time: now,
+            position: pointer.position,
+            window,
+            executable: exe_path.clone(),
+            error,
+            rule,
+            action,
+        };
+        if let Err(e) = self.history.record(decision) {
+            error!("Failed to write to the history journal: {}", e);
+        }
+
+        self.record_stats(now, exe_path.as_deref());
+    }
+
+    /// Reload all cursors at a new `size`, if it differs from the current one.
+    fn set_cursor_size(&mut self, size: u32) {
+        if self.cursor_size == size {
+            return;
+        }
+
+        info!("Cursor size changed to {}px, reloading cursors.", size);
+
+        self.cursor_size = size;
+
+        for cursor in self.cursors.values_mut() {
+            // Keep using the previous handle if the cursor can't be reloaded.
+            if let Err(e) = cursor.reload(size, &mut self.backend) {
+                error!("Failed to reload cursor \"{}\": {}", cursor.name, e);
+            }
+        }
+
+        // The system cursors are copies of the old handles, so the active cursor must be applied again.
+        if let Some(cursor_id) = self.active_cursor.take() {
+            self.set_cursor(cursor_id);
+        }
+    }
+
+    /// Draw the trail behind the pointer at `now` if it's `enabled` (and a custom cursor is active), or hide it.
+    fn update_trail(&mut self, enabled: bool, now: NaiveDateTime) {
+        let echoes = match (enabled, self.active_cursor) {
+            (true, Some(_)) => self.trail.echoes(&self.motion, now),
+            _ => Vec::new(),
+        };
+
+        // Ticks come faster than the screen is redrawn, so the trail is only drawn on some of them.
+        if !echoes.is_empty()
+            && self
+                .trail_drawn
+                .is_some_and(|drawn| drawn <= now && now - drawn < trail::frame_time())
+        {
+            return;
+        }
+
+        let drawing = if echoes.is_empty() {
+            None
+        } else {
+            self.trail_image().and_then(|image| trail::draw(&echoes, image))
+        };
+        match drawing {
+            Some(drawing) => {
+                self.backend.show_trail(Some(&drawing));
+                self.trail_drawn = Some(now);
+            }
+            None => {
+                if self.trail_drawn.take().is_some() {
+                    self.backend.show_trail(None);
                 }
             }
-            // No window under the cursor
-            Ok(None) => {}
-            Err(e) => println!("ERROR: {}", e),
         }
     }
 
-    fn get_process_under_cursor() -> Result<Option<String>> {
-        use info::{CursorPosition, Process};
+    /// Get the image to draw the trail with, reading it again if the active cursor or the cursor size has changed.
+    fn trail_image(&mut self) -> Option<&cur::CursorImage> {
+        let cursor_id = self.active_cursor?;
+        let size = self.cursor_size;
+
+        let current = matches!(&self.trail_image, Some((id, loaded, _)) if *id == cursor_id && *loaded == size);
+        if !current {
+            let cursor = &self.cursors[&cursor_id];
+            let image = match cursor.files.image(size) {
+                Ok(image) => Some(image),
+                Err(e) => {
+                    error!("Failed to read cursor \"{}\" to draw its trail: {}", cursor.name, e);
+                    None
+                }
+            };
+            self.trail_image = Some((cursor_id, size, image));
+        }
 
-        // Read the position of the cursor
-        CursorPosition::try_read()
-            // Get the process that is under the cursor at that position
-            .and_then(Process::from_position)
-            // Get the full path to that process's executable
-            .map(|p| p.executable_path())
-            // Convert the Option<Result<_>> type to Result<Option<_>>
-            .transpose()
+        self.trail_image.as_ref().and_then(|(_, _, image)| image.as_ref())
     }
 
-    fn application_matching(&self, exe_path: &str) -> Option<&Application> {
-        self.applications
-            .iter()
-            .find(|app| exe_path.ends_with(&app.path))
+    /// Switch to a cursor, or to the system cursors if `cursor_id` is `None`, showing `transition` first if
+    /// there is one, and then `pulse` if there is one. If either can't be made, it's left out.
+    fn switch_to(
+        &mut self,
+        cursor_id: Option<CursorId>,
+        transition: Option<&transition::Transition>,
+        pulse: Option<&pulse::Pulse>,
+        now: NaiveDateTime,
+    ) {
+        if self.active_cursor == cursor_id {
+            return;
+        }
+
+        if let Some(transition) = transition {
+            match self.start_transition(transition, cursor_id, pulse.cloned(), now) {
+                Ok(()) => return,
+                Err(e) => error!("Failed to make a transition, so switching without one: {}", e),
+            }
+        }
+
+        match (cursor_id, pulse) {
+            (Some(cursor_id), Some(pulse)) => {
+                if let Err(e) = self.start_pulse(pulse, cursor_id, now) {
+                    error!("Failed to make a pulse, so switching without one: {}", e);
+                    self.set_cursor(cursor_id);
+                }
+            }
+            (Some(cursor_id), None) => self.set_cursor(cursor_id),
+            (None, _) => self.reset_to_default_cursor(),
+        }
+    }
+
+    /// Generate a transition from the active cursor to `cursor_id` (or the system cursors) and show it,
+    /// making `cursor_id` the active cursor once it's over, after `then` if there is one.
+    fn start_transition(
+        &mut self,
+        transition: &transition::Transition,
+        cursor_id: Option<CursorId>,
+        then: Option<pulse::Pulse>,
+        now: NaiveDateTime,
+    ) -> Result<()> {
+        let size = self.cursor_size;
+        let image = |cursor_id: Option<CursorId>| {
+            cursor_id
+                .map(|cursor_id| self.cursors[&cursor_id].files.image(size))
+                .transpose()
+        };
+        let from = image(self.active_cursor)?;
+        let to = image(cursor_id)?;
+
+        let animation = transition.animate(from.as_ref(), to.as_ref(), size)?;
+        let name = match cursor_id {
+            Some(cursor_id) => format!("transition to {}", self.cursors[&cursor_id].name),
+            None => "transition to the system cursors".to_string(),
+        };
+
+        // A pulse is only shown around a cursor.
+        let then = then.filter(|_| cursor_id.is_some());
+        self.play("transition", name, &animation, cursor_id, then, now)
+    }
+
+    /// Generate a pulse around `cursor_id` and show it, making `cursor_id` the active cursor once it's over.
+    fn start_pulse(&mut self, pulse: &pulse::Pulse, cursor_id: CursorId, now: NaiveDateTime) -> Result<()> {
+        let size = self.cursor_size;
+        let cursor = &self.cursors[&cursor_id];
+        let animation = pulse.animate(&cursor.files.image(size)?, size)?;
+        let name = format!("pulse of {}", cursor.name);
+
+        self.play("pulse", name, &animation, Some(cursor_id), None, now)
+    }
+
+    /// Show a generated `animation` of the given `kind`, making `cursor_id` the active cursor once it's over.
+    fn play(
+        &mut self,
+        kind: &str,
+        name: String,
+        animation: &ani::AnimatedCursor,
+        cursor_id: Option<CursorId>,
+        then: Option<pulse::Pulse>,
+        now: NaiveDateTime,
+    ) -> Result<()> {
+        let bytes = animation.encode()?;
+        let path = generated::write((kind, &bytes), "ani", &bytes)?;
+
+        let files = CursorFiles {
+            path: path.to_string_lossy().into_owned(),
+            variants: Vec::new(),
+            scaling: None,
+            hotspot: (0, 0),
+            effects: Vec::new(),
+            roles: Vec::new(),
+        };
+        let (handle, role_handles) = files.load(frames::size(animation), &mut self.backend)?;
+
+        // Generated cursors aren't configured, so they don't have an ID of their own.
+        let cursor = Cursor {
+            id: 0,
+            name,
+            files,
+            handle,
+            role_handles,
+        };
+
+        info!("Showing the {}.", cursor.name);
+
+        self.backend.set_cursor(&cursor);
+        self.end_playing();
+
+        let duration = chrono::Duration::from_std(frames::duration(animation))
+            .unwrap_or_else(|_| chrono::Duration::zero());
+        self.playing = Some(Playing {
+            cursor,
+            until: now + duration,
+            then,
+        });
+        self.active_cursor = cursor_id;
+
+        Ok(())
+    }
+
+    /// Replace the transition or pulse that's showing with what comes after it, if it's over by `now`.
+    fn finish_playing(&mut self, now: NaiveDateTime) {
+        let then = match &mut self.playing {
+            Some(playing) if now >= playing.until => playing.then.take(),
+            _ => return,
+        };
+
+        if let (Some(pulse), Some(cursor_id)) = (then, self.active_cursor) {
+            match self.start_pulse(&pulse, cursor_id, now) {
+                Ok(()) => return,
+                Err(e) => error!("Failed to make a pulse, so going on without one: {}", e),
+            }
+        }
+
+        match self.active_cursor {
+            Some(cursor_id) => self.backend.set_cursor(&self.cursors[&cursor_id]),
+            None => self.backend.restore_cursors(),
+        }
+        self.end_playing();
+    }
+
+    /// Free the transition or pulse that was showing, if any, once another cursor has taken its place.
+    fn end_playing(&mut self) {
+        if let Some(playing) = self.playing.take() {
+            self.backend.destroy(&playing.cursor.handle);
+        }
     }
 
     fn set_cursor(&mut self, cursor_id: CursorId) {
@@ -247,13 +1083,14 @@ impl CursorChanger {
             .get(&cursor_id)
             .expect("Failed to find requested cursor!");
 
-        println!("Activating cursor \"{}\" ({}).", cursor.name, cursor.id);
+        info!("Activating cursor \"{}\" ({}).", cursor.name, cursor.id);
 
         // Activate the requested cursor
-        system::set_system_cursor(&cursor.handle);
+        self.backend.set_cursor(cursor);
 
         // Mark this cursor as the active one.
         self.active_cursor = Some(cursor.id);
+        self.end_playing();
     }
 
     fn reset_to_default_cursor(&mut self) {
@@ -262,9 +1099,10 @@ impl CursorChanger {
             return;
         }
 
-        println!("Resetting cursor to default.");
+        info!("Resetting cursor to default.");
 
-        system::restore_original_cursors();
+        self.backend.restore_cursors();
+        self.end_playing();
 
         // Save the state of there being no custom cursor active.
         self.active_cursor = None;
@@ -272,44 +1110,87 @@ impl CursorChanger {
 }
 
 
-#[cfg(windows)]
 fn main() {
-    let config = config::Config::from_file("cursor.toml").unwrap();
-
-    // This will be used to notify the cursor-checking thread to exit when the main window is closed.
-    let exit = Arc::new(Mutex::new(false));
-    let thread_exit = Arc::clone(&exit);
+    // We don't have a console of our own, so borrow the one we were started from (if any)
+    // so that command output and errors are visible.
+    #[cfg(windows)]
+    system::attach_console();
+
+    let options = cli::Options::from_args();
+
+    match options.command {
+        Some(command) => {
+            if let Err(e) = cli::run(command) {
+                eprintln!("ERROR: {}", e);
+                std::process::exit(1);
+            }
+        }
+        None => run_cursor_changer(options.config.as_deref(), options.format, options.record),
+    }
+}
 
-    let child = thread::spawn(move || {
-        let mut cursor_changer = CursorChanger::from_config(config).unwrap();
+#[cfg(not(windows))]
+fn run_cursor_changer(_config: Option<&Path>, _format: Option<Format>, _record: Option<PathBuf>) {
+    eprintln!("The cursor changer only runs on Windows. Use --help to see the commands that work here.");
+    std::process::exit(1);
+}
 
-        loop {
-            cursor_changer.tick();
+#[cfg(windows)]
+fn run_cursor_changer(config: Option<&Path>, format: Option<Format>, record: Option<PathBuf>) {
+    // This must happen before any windows are created or cursors are loaded.
+    system::enable_dpi_awareness();
+
+    let config = match config::Config::load(config, format) {
+        Ok(config) => config,
+        Err(e) => {
+            // There's no configuration to say where to log, so use the defaults.
+            let _ = logging::init(&logging::LogConfig::default(), Path::new(""));
+            error!("Failed to load the configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
 
-            // Not sleeping just results in ~30% CPU usage.
-            // Even 200 FPS would be 5ms, so this is still a generous poll rate.
-            let sleep_time = time::Duration::from_millis(5);
-            thread::sleep(sleep_time);
+    let log_config = config.log.clone().unwrap_or_default();
+    if let Err(e) = logging::init(&log_config, &config.dir) {
+        error!("Failed to open the log file: {}", e);
+    }
 
-            // read the mutex to see if the thread should quit
-            let should_exit = *thread_exit.lock().unwrap();
+    // Each profile's hotkey switches to it.
+    let hotkeys: Vec<(hotkey::Hotkey, Control)> = config
+        .profile
+        .iter()
+        .filter_map(|(name, profile)| {
+            let control = Control::SwitchProfile(Some(name.clone()));
+            profile.hotkey.clone().map(|hotkey| (hotkey, control))
+        })
+        .collect();
 
-            // Kill the loop when notified by the main thread.
-            if should_exit {
-                break;
-            }
-        }
+    // Instructions from hotkeys and other processes arrive at the window, and are carried out by the cursor-checking thread.
+    let (controls, received_controls) = mpsc::channel();
 
-        println!("Exiting gracefully...");
+    // This will be used to notify the cursor-checking thread to exit when the main window is closed.
+    let exit = Arc::new(Mutex::new(false));
+    let thread_exit = Arc::clone(&exit);
 
-        // Restore the cursors so you're not stuck with your wacky cursor forever.
-        system::restore_original_cursors();
+    let child = thread::spawn(move || {
+        let backend = system::SystemBackend::default();
+
+        match record {
+            Some(path) => match trace::Recording::create(&path, backend) {
+                Ok(recording) => check_cursors(config, recording, received_controls, thread_exit),
+                Err(e) => {
+                    error!("Failed to create the trace file {}: {}", path.display(), e);
+                    std::process::exit(1);
+                }
+            },
+            None => check_cursors(config, backend, received_controls, thread_exit),
+        }
     });
 
     // Create a window
-    window::create_window_and_block();
+    window::create_window_and_block(&hotkeys, controls);
 
-    println!("Notifying thread to exit");
+    debug!("Notifying thread to exit");
 
     {
         let mut signal_exit = exit.lock().unwrap();
@@ -319,4 +1200,72 @@ fn main() {
 
     // some work here
     let _res = child.join();
-}
\ No newline at end of file
+}
+
+/// Check what's under the pointer and change the cursor to match, carrying out `controls` as they arrive,
+/// until told to `exit`.
+#[cfg(windows)]
+fn check_cursors<B: Backend>(
+    config: config::Config,
+    backend: B,
+    controls: mpsc::Receiver<control::Request>,
+    exit: Arc<Mutex<bool>>,
+) {
+    let mut cursor_changer = match CursorChanger::from_config(config, backend) {
+        Ok(cursor_changer) => cursor_changer,
+        Err(e) => {
+            error!("Failed to load the cursors: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    loop {
+        while let Ok(request) = controls.try_recv() {
+            cursor_changer.handle_control(request.control);
+            if let Some(done) = request.done {
+                let _ = done.send(());
+            }
+        }
+
+        cursor_changer.tick();
+
+        // Not sleeping just results in ~30% CPU usage.
+        // Even 200 FPS would be 5ms, so this is still a generous poll rate.
+        let sleep_time = time::Duration::from_millis(5);
+        thread::sleep(sleep_time);
+
+        // read the mutex to see if the thread should quit
+        let should_exit = *exit.lock().unwrap();
+
+        // Kill the loop when notified by the main thread.
+        if should_exit {
+            break;
+        }
+    }
+
+    info!("Exiting gracefully...");
+
+    cursor_changer.save_stats();
+    cursor_changer.save_history();
+
+    // Restore the cursors so you're not stuck with your wacky cursor forever.
+    system::restore_original_cursors();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scales_within_the_cursor_sizes() {
+        assert_eq!(Scaling::Size(48).apply(32), 48);
+        assert_eq!(Scaling::Size(48).apply(64), 96);
+        assert_eq!(Scaling::Factor(1.5).apply(32), 48);
+
+        assert_eq!(Scaling::Size(u32::MAX).apply(u32::MAX), cur::MAX_SIZE);
+        assert_eq!(Scaling::Size(1 << 30).apply(64), cur::MAX_SIZE);
+        assert_eq!(Scaling::Factor(1e12).apply(32), cur::MAX_SIZE);
+        assert_eq!(Scaling::Size(0).apply(32), 1);
+        assert_eq!(Scaling::Factor(-1.0).apply(32), 1);
+    }
+}
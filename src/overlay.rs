@@ -0,0 +1,206 @@
+//! The window the trail is drawn in: a layered window on top of everything else, which clicks pass through.
+//!
+//! The trail is drawn in `trail`; this only puts the finished image on the screen.
+
+use std::io::Error as IoError;
+use std::mem;
+use std::ptr::null_mut;
+
+use winapi::ctypes::c_void;
+use winapi::shared::minwindef::DWORD;
+use winapi::shared::windef::{HGDIOBJ, HWND, POINT, SIZE};
+use winapi::um::libloaderapi::GetModuleHandleW;
+use winapi::um::wingdi::{
+    CreateCompatibleDC, CreateDIBSection, DeleteDC, DeleteObject, SelectObject, AC_SRC_ALPHA,
+    AC_SRC_OVER, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, BLENDFUNCTION, DIB_RGB_COLORS,
+};
+use winapi::um::winuser::{
+    CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetDC, PeekMessageW,
+    RegisterClassW, ReleaseDC, ShowWindow, UpdateLayeredWindow, MSG, PM_REMOVE, SW_HIDE,
+    SW_SHOWNOACTIVATE, ULW_ALPHA, WNDCLASSW, WS_EX_LAYERED, WS_EX_NOACTIVATE, WS_EX_TOOLWINDOW,
+    WS_EX_TOPMOST, WS_EX_TRANSPARENT, WS_POPUP,
+};
+
+use crate::system::win32_string;
+use crate::trail::Drawing;
+use crate::Result;
+
+/// The overlay window's class name.
+const CLASS_NAME: &str = "cursor_changer_trail";
+
+#[derive(Debug)]
+pub struct Overlay {
+    handle: HWND,
+
+    /// Whether the window is on the screen.
+    shown: bool,
+}
+
+impl Overlay {
+    /// Create the window, hidden. Its messages are handled whenever it's shown or hidden,
+    /// so it must be used on the thread that created it.
+    pub fn create() -> Result<Overlay> {
+        let name = win32_string(CLASS_NAME);
+
+        let handle = unsafe {
+            let hinstance = GetModuleHandleW(null_mut());
+
+            let class = WNDCLASSW {
+                style: 0,
+                lpfnWndProc: Some(DefWindowProcW),
+                hInstance: hinstance,
+                lpszClassName: name.as_ptr(),
+                cbClsExtra: 0,
+                cbWndExtra: 0,
+                hIcon: null_mut(),
+                hCursor: null_mut(),
+                hbrBackground: null_mut(),
+                lpszMenuName: null_mut(),
+            };
+            RegisterClassW(&class);
+
+            // Layered and transparent, so it's drawn with per-pixel alpha and clicks go to the windows under it.
+            CreateWindowExW(
+                WS_EX_LAYERED | WS_EX_TRANSPARENT | WS_EX_TOPMOST | WS_EX_TOOLWINDOW | WS_EX_NOACTIVATE,
+                name.as_ptr(),
+                name.as_ptr(),
+                WS_POPUP,
+                0,
+                0,
+                0,
+                0,
+                null_mut(),
+                null_mut(),
+                hinstance,
+                null_mut(),
+            )
+        };
+
+        if handle.is_null() {
+            return Err(IoError::last_os_error().into());
+        }
+
+        Ok(Overlay {
+            handle,
+            shown: false,
+        })
+    }
+
+    /// Show `drawing` where it goes on the screen, in place of whatever was shown before.
+    pub fn show(&mut self, drawing: &Drawing) -> Result<()> {
+        let (width, height) = (drawing.image.width as i32, drawing.image.height as i32);
+        if width == 0 || height == 0 {
+            self.hide();
+            return Ok(());
+        }
+
+        let updated = unsafe {
+            let screen = GetDC(null_mut());
+            let memory = CreateCompatibleDC(screen);
+
+            // A negative height makes the bitmap's rows go from the top down, like the image's.
+            let info = BITMAPINFO {
+                bmiHeader: BITMAPINFOHEADER {
+                    biSize: mem::size_of::<BITMAPINFOHEADER>() as DWORD,
+                    biWidth: width,
+                    biHeight: -height,
+                    biPlanes: 1,
+                    biBitCount: 32,
+                    biCompression: BI_RGB,
+                    ..mem::zeroed()
+                },
+                ..mem::zeroed()
+            };
+            let mut bits: *mut c_void = null_mut();
+            let bitmap = CreateDIBSection(memory, &info, DIB_RGB_COLORS, &mut bits, null_mut(), 0);
+
+            let updated = if bitmap.is_null() || bits.is_null() {
+                Err(IoError::last_os_error())
+            } else {
+                // Layered windows take blue, green, red, and alpha, with the colors multiplied by the alpha.
+                let pixels = std::slice::from_raw_parts_mut(bits as *mut u8, drawing.image.pixels.len());
+                for (target, source) in pixels.chunks_mut(4).zip(drawing.image.pixels.chunks(4)) {
+                    let premultiplied = |c: u8| (u16::from(c) * u16::from(source[3]) / 255) as u8;
+                    target[0] = premultiplied(source[2]);
+                    target[1] = premultiplied(source[1]);
+                    target[2] = premultiplied(source[0]);
+                    target[3] = source[3];
+                }
+
+                let previous = SelectObject(memory, bitmap as HGDIOBJ);
+                let mut position = POINT {
+                    x: drawing.origin.0,
+                    y: drawing.origin.1,
+                };
+                let mut size = SIZE {
+                    cx: width,
+                    cy: height,
+                };
+                let mut source = POINT { x: 0, y: 0 };
+                let mut blend = BLENDFUNCTION {
+                    BlendOp: AC_SRC_OVER,
+                    BlendFlags: 0,
+                    SourceConstantAlpha: 255,
+                    AlphaFormat: AC_SRC_ALPHA,
+                };
+                let updated = UpdateLayeredWindow(
+                    self.handle,
+                    screen,
+                    &mut position,
+                    &mut size,
+                    memory,
+                    &mut source,
+                    0,
+                    &mut blend,
+                    ULW_ALPHA,
+                );
+                let updated = match updated {
+                    0 => Err(IoError::last_os_error()),
+                    _ => Ok(()),
+                };
+
+                SelectObject(memory, previous);
+                DeleteObject(bitmap as HGDIOBJ);
+                updated
+            };
+
+            DeleteDC(memory);
+            ReleaseDC(null_mut(), screen);
+            updated
+        };
+        updated?;
+
+        if !self.shown {
+            unsafe { ShowWindow(self.handle, SW_SHOWNOACTIVATE) };
+            self.shown = true;
+        }
+        self.handle_messages();
+
+        Ok(())
+    }
+
+    /// Take the window off the screen.
+    pub fn hide(&mut self) {
+        if self.shown {
+            unsafe { ShowWindow(self.handle, SW_HIDE) };
+            self.shown = false;
+        }
+        self.handle_messages();
+    }
+
+    /// Handle the window's messages, since the thread it belongs to doesn't wait for them.
+    fn handle_messages(&self) {
+        unsafe {
+            let mut message: MSG = mem::zeroed();
+            while PeekMessageW(&mut message, self.handle, 0, 0, PM_REMOVE) != 0 {
+                DispatchMessageW(&message);
+            }
+        }
+    }
+}
+
+impl Drop for Overlay {
+    fn drop(&mut self) {
+        unsafe { DestroyWindow(self.handle) };
+    }
+}
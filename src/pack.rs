@@ -0,0 +1,583 @@
+//! Cursor packs: zip archives holding cursor files and a `pack.toml` manifest that describes them.
+//!
+//! A `[[cursor]]` refers to a cursor in a pack with `pack = "name.zip#cursor"`. Its files are checked
+//! against the manifest's checksums and extracted into the cache directory before they're loaded.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::config::{self, CursorVariant};
+use crate::error::{Error, WithPath};
+use crate::generated;
+use crate::role::CursorRole;
+use crate::Result;
+
+/// The name of the manifest file, at the root of the archive.
+pub const MANIFEST: &str = "pack.toml";
+
+/// The contents of a pack's `pack.toml`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Manifest {
+    pub name: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+
+    /// The cursors in the pack.
+    #[serde(default)]
+    pub cursor: Vec<PackCursor>,
+
+    /// The SHA-256 hash (as lowercase hex) of every file in the pack, by its path in the archive.
+    #[serde(default)]
+    pub checksums: BTreeMap<String, String>,
+}
+
+/// A cursor in a pack. Paths are relative to the root of the archive.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PackCursor {
+    pub name: String,
+    pub path: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hotspot: Option<[u32; 2]>,
+
+    /// Authors of this cursor, if they differ from the pack's.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<String>,
+
+    /// The license of this cursor, if it differs from the pack's.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variant: Vec<CursorVariant>,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub roles: BTreeMap<CursorRole, String>,
+}
+
+/// An open pack archive.
+pub struct Pack {
+    path: PathBuf,
+    archive: ZipArchive<File>,
+    pub manifest: Manifest,
+}
+
+/// Details of a new pack, for `Pack::create`.
+pub struct PackInfo {
+    pub name: String,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    pub authors: Vec<String>,
+    pub license: Option<String>,
+}
+
+impl PackCursor {
+    /// Get the paths of all files used by this cursor.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.path.as_str())
+            .chain(self.variant.iter().map(|v| v.path.as_str()))
+            .chain(self.roles.values().map(String::as_str))
+    }
+
+    /// Get a copy of this cursor with `map` applied to each of its paths.
+    fn map_paths<F: Fn(&str) -> String>(&self, map: F) -> PackCursor {
+        PackCursor {
+            path: map(&self.path),
+            variant: self
+                .variant
+                .iter()
+                .map(|variant| CursorVariant {
+                    size: variant.size,
+                    path: map(&variant.path),
+                })
+                .collect(),
+            roles: self
+                .roles
+                .iter()
+                .map(|(role, path)| (*role, map(path)))
+                .collect(),
+            ..self.clone()
+        }
+    }
+}
+
+impl Pack {
+    /// Open a pack and read its manifest.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Pack> {
+        let path = path.as_ref().to_path_buf();
+        let mut archive = ZipArchive::new(File::open(&path).with_path(&path)?)?;
+
+        let mut text = String::new();
+        match archive.by_name(MANIFEST) {
+            Ok(mut file) => file.read_to_string(&mut text)?,
+            Err(zip::result::ZipError::FileNotFound) => {
+                return Err(invalid(&path, &format!("it has no {}", MANIFEST)))
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let manifest: Manifest = toml::from_str(&text)?;
+
+        // Paths are joined onto the extraction directory, so they must stay inside of it.
+        for cursor in &manifest.cursor {
+            if let Some(bad) = cursor.paths().find(|p| !is_relative_path(p)) {
+                return Err(invalid(
+                    &path,
+                    &format!(
+                        "the cursor \"{}\" has an invalid path, {}",
+                        cursor.name, bad
+                    ),
+                ));
+            }
+        }
+
+        Ok(Pack {
+            path,
+            archive,
+            manifest,
+        })
+    }
+
+    /// Find a cursor by name. If no name is given, the pack must contain exactly one cursor.
+    pub fn cursor(&self, name: Option<&str>) -> Result<&PackCursor> {
+        let cursors = &self.manifest.cursor;
+
+        let found = match name {
+            Some(name) => cursors.iter().find(|cursor| cursor.name == name),
+            None if cursors.len() == 1 => cursors.first(),
+            None => None,
+        };
+
+        found.ok_or_else(|| {
+            let names: Vec<&str> = cursors.iter().map(|cursor| cursor.name.as_str()).collect();
+            let reason = match name {
+                Some(name) => format!("it has no cursor named \"{}\"", name),
+                None => "it has several cursors, so one must be named after a '#'".to_string(),
+            };
+            invalid(
+                &self.path,
+                &format!("{} (it has: {})", reason, names.join(", ")),
+            )
+        })
+    }
+
+    /// Read a file from the archive, checking it against the manifest's checksum.
+    pub fn read_file(&mut self, name: &str) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        match self.archive.by_name(name) {
+            Ok(mut file) => file.read_to_end(&mut bytes)?,
+            Err(zip::result::ZipError::FileNotFound) => {
+                return Err(invalid(&self.path, &format!("{} is missing", name)))
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        match self.manifest.checksums.get(name) {
+            Some(expected) if expected.eq_ignore_ascii_case(&sha256_hex(&bytes)) => Ok(bytes),
+            Some(_) => Err(invalid(
+                &self.path,
+                &format!("{} doesn't match its checksum", name),
+            )),
+            None => Err(invalid(
+                &self.path,
+                &format!("{} has no checksum in the manifest", name),
+            )),
+        }
+    }
+
+    /// Check every file in the pack, returning a description of each problem found.
+    pub fn verify(&mut self) -> Result<Vec<String>> {
+        let mut problems = Vec::new();
+
+        let mut referenced: Vec<String> = Vec::new();
+        for cursor in &self.manifest.cursor {
+            referenced.extend(cursor.paths().map(str::to_string));
+        }
+
+        let mut names: Vec<String> = self.manifest.checksums.keys().cloned().collect();
+        names.extend(referenced.iter().cloned());
+        names.sort();
+        names.dedup();
+
+        for name in names {
+            if let Err(e) = self.read_file(&name) {
+                problems.push(match e {
+                    Error::InvalidPack { reason, .. } => reason,
+                    e => format!("{}: {}", name, e),
+                });
+            }
+        }
+
+        let mut archived: Vec<String> = self
+            .archive
+            .file_names()
+            .filter(|name| *name != MANIFEST && !name.ends_with('/'))
+            .map(str::to_string)
+            .collect();
+        archived.sort();
+        for name in archived {
+            if !self.manifest.checksums.contains_key(&name) {
+                problems.push(format!("{} isn't listed in the manifest", name));
+            }
+        }
+
+        Ok(problems)
+    }
+
+    /// Extract the files of a cursor into the cache directory, returning the cursor with its paths
+    /// pointing at the extracted files.
+    pub fn extract(&mut self, name: Option<&str>) -> Result<PackCursor> {
+        let cursor = self.cursor(name)?.clone();
+
+        // The directory is specific to this pack and its contents, so different versions don't mix.
+        let mut hasher = DefaultHasher::new();
+        fs::canonicalize(&self.path)?.hash(&mut hasher);
+        self.manifest.checksums.hash(&mut hasher);
+        let dir = generated::cache_dir()
+            .join("packs")
+            .join(format!("{:016x}", hasher.finish()));
+
+        for name in cursor.paths().map(str::to_string).collect::<Vec<_>>() {
+            let bytes = self.read_file(&name)?;
+            let target = dir.join(&name);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent).with_path(parent)?;
+            }
+            fs::write(&target, bytes).with_path(&target)?;
+        }
+
+        Ok(cursor.map_paths(|path| dir.join(path).to_string_lossy().into_owned()))
+    }
+
+    /// Build a pack from cursors in the configuration, copying in all of their files.
+    pub fn create<P: AsRef<Path>>(
+        output: P,
+        info: PackInfo,
+        cursors: &[config::Cursor],
+    ) -> Result<Manifest> {
+        let mut manifest = Manifest {
+            name: info.name,
+            version: info.version,
+            description: info.description,
+            authors: info.authors,
+            license: info.license,
+            cursor: Vec::new(),
+            checksums: BTreeMap::new(),
+        };
+
+        // Where each source file is stored in the archive, so files shared by cursors are only stored once.
+        let mut stored: BTreeMap<String, String> = BTreeMap::new();
+        let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+
+        for cursor in cursors {
+            let packed = PackCursor {
+                name: cursor.name.clone(),
+                path: cursor.path.clone(),
+                hotspot: cursor.hotspot,
+                authors: Vec::new(),
+                license: None,
+                variant: cursor.variant.clone(),
+                roles: cursor.roles.clone(),
+            };
+
+            for source in packed.paths() {
+                if stored.contains_key(source) {
+                    continue;
+                }
+
+                let file_name = Path::new(source)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let mut name = format!("{}/{}", archive_dir_name(&cursor.name), file_name);
+                let mut n = 2;
+                while manifest.checksums.contains_key(&name) {
+                    name = format!("{}/{}-{}", archive_dir_name(&cursor.name), n, file_name);
+                    n += 1;
+                }
+
+                let bytes = fs::read(source).with_path(source)?;
+                manifest.checksums.insert(name.clone(), sha256_hex(&bytes));
+                stored.insert(source.to_string(), name.clone());
+                files.push((name, bytes));
+            }
+
+            manifest
+                .cursor
+                .push(packed.map_paths(|path| stored[path].clone()));
+        }
+
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        let mut writer = ZipWriter::new(File::create(&output).with_path(&output)?);
+
+        writer.start_file(MANIFEST, options)?;
+        writer.write_all(toml::to_string(&manifest)?.as_bytes())?;
+
+        for (name, bytes) in files {
+            writer.start_file(name, options)?;
+            writer.write_all(&bytes)?;
+        }
+
+        writer.finish()?;
+
+        Ok(manifest)
+    }
+}
+
+/// Extract the cursor referred to by a `pack` setting: a path to a pack, optionally followed by `#`
+/// and the name of a cursor in it.
+pub fn resolve(reference: &str) -> Result<PackCursor> {
+    let (path, name) = match reference.rsplit_once('#') {
+        Some((path, name)) => (path, Some(name)),
+        None => (reference, None),
+    };
+
+    Pack::open(path)?.extract(name)
+}
+
+/// Get the SHA-256 hash of `bytes`, as lowercase hex.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Check that a path from a manifest is relative and doesn't climb out of the archive.
+fn is_relative_path(path: &str) -> bool {
+    !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+/// Turn a cursor name into something safe to use as a directory name.
+fn archive_dir_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn invalid(pack: &Path, reason: &str) -> Error {
+    Error::InvalidPack {
+        pack: pack.display().to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use std::{env, process};
+
+    /// A new, empty directory for a test's packs.
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("cursor-changer-pack-{}-{}", test, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn cursors() -> Vec<config::Cursor> {
+        let text = format!(
+            r#"
+            [[cursor]]
+            name = "arrow"
+            path = '{}'
+            variant = [{{ size = 48, path = '{}' }}]
+
+            [[cursor]]
+            name = "ring"
+            path = '{}'
+            hotspot = [32, 32]
+            "#,
+            testing::path("resample/arrow.cur").display(),
+            testing::path("resample/arrow-48.cur").display(),
+            testing::path("resample/ring.png").display(),
+        );
+
+        #[derive(Deserialize)]
+        struct Cursors {
+            cursor: Vec<config::Cursor>,
+        }
+        toml::from_str::<Cursors>(&text).unwrap().cursor
+    }
+
+    fn info() -> PackInfo {
+        PackInfo {
+            name: "Test".to_string(),
+            version: Some("1.0".to_string()),
+            description: None,
+            authors: vec!["Someone".to_string()],
+            license: None,
+        }
+    }
+
+    /// Write a pack by hand, with `files` that may not match the manifest.
+    fn write(path: &Path, manifest: &Manifest, files: &[(&str, &[u8])]) {
+        let mut writer = ZipWriter::new(File::create(path).unwrap());
+        writer.start_file(MANIFEST, FileOptions::default()).unwrap();
+        writer
+            .write_all(toml::to_string(manifest).unwrap().as_bytes())
+            .unwrap();
+        for (name, bytes) in files {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(bytes).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn extracts_what_it_creates() {
+        let dir = temp_dir("round-trip");
+        let path = dir.join("test.zip");
+        let created = Pack::create(&path, info(), &cursors()).unwrap();
+
+        let mut pack = Pack::open(&path).unwrap();
+        assert_eq!(pack.manifest.name, "Test");
+        assert_eq!(pack.manifest.authors, ["Someone"]);
+        assert_eq!(pack.manifest.checksums, created.checksums);
+        assert_eq!(
+            pack.manifest.checksums.keys().collect::<Vec<_>>(),
+            ["arrow/arrow-48.cur", "arrow/arrow.cur", "ring/ring.png"]
+        );
+        assert!(pack.verify().unwrap().is_empty());
+        assert!(pack.extract(None).is_err());
+
+        let arrow = pack.extract(Some("arrow")).unwrap();
+        assert_eq!(
+            fs::read(&arrow.path).unwrap(),
+            testing::read("resample/arrow.cur")
+        );
+        assert_eq!(arrow.variant[0].size, 48);
+        assert_eq!(
+            fs::read(&arrow.variant[0].path).unwrap(),
+            testing::read("resample/arrow-48.cur")
+        );
+
+        let ring = pack.extract(Some("ring")).unwrap();
+        assert_eq!(ring.hotspot, Some([32, 32]));
+        assert_eq!(
+            fs::read(&ring.path).unwrap(),
+            testing::read("resample/ring.png")
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_files_that_dont_match_the_manifest() {
+        let dir = temp_dir("mismatch");
+        let path = dir.join("test.zip");
+        let mut manifest = Pack::create(&path, info(), &cursors()).unwrap();
+        let arrow = testing::read("resample/arrow.cur");
+        let arrow_48 = testing::read("resample/arrow-48.cur");
+        let ring = testing::read("resample/ring.png");
+
+        // A file whose contents changed after its checksum was taken.
+        let mut tampered = arrow.clone();
+        tampered[100] ^= 0xff;
+        write(
+            &path,
+            &manifest,
+            &[
+                ("arrow/arrow.cur", &tampered),
+                ("arrow/arrow-48.cur", &arrow_48),
+                ("ring/ring.png", &ring),
+            ],
+        );
+        let mut pack = Pack::open(&path).unwrap();
+        assert!(pack.read_file("arrow/arrow-48.cur").is_ok());
+        assert_eq!(
+            pack.read_file("arrow/arrow.cur").unwrap_err().to_string(),
+            format!(
+                "Invalid cursor pack {}: arrow/arrow.cur doesn't match its checksum",
+                path.display()
+            )
+        );
+        assert!(pack.extract(Some("arrow")).is_err());
+
+        // A file with no checksum, and another that no cursor uses.
+        manifest.checksums.remove("ring/ring.png");
+        write(
+            &path,
+            &manifest,
+            &[
+                ("arrow/arrow.cur", &arrow),
+                ("arrow/arrow-48.cur", &arrow_48),
+                ("ring/ring.png", &ring),
+                ("extra.txt", b"extra"),
+            ],
+        );
+        let mut pack = Pack::open(&path).unwrap();
+        assert!(pack.read_file("ring/ring.png").is_err());
+        assert_eq!(
+            pack.verify().unwrap(),
+            [
+                "ring/ring.png has no checksum in the manifest",
+                "extra.txt isn't listed in the manifest",
+                "ring/ring.png isn't listed in the manifest",
+            ]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_paths_outside_of_the_archive() {
+        assert!(is_relative_path("arrow.cur"));
+        assert!(is_relative_path("./arrow/arrow.cur"));
+        assert!(!is_relative_path(""));
+        assert!(!is_relative_path("../x.cur"));
+        assert!(!is_relative_path("arrow/../../x.cur"));
+        assert!(!is_relative_path("/x.cur"));
+        if cfg!(windows) {
+            assert!(!is_relative_path("C:\\x.cur"));
+            assert!(!is_relative_path("\\x.cur"));
+        }
+    }
+
+    #[test]
+    fn refuses_to_open_packs_with_paths_outside_of_the_archive() {
+        let dir = temp_dir("outside");
+        let path = dir.join("test.zip");
+        let mut manifest = Pack::create(&path, info(), &cursors()).unwrap();
+        manifest.cursor[0].path = "../x.cur".to_string();
+        write(&path, &manifest, &[]);
+
+        assert_eq!(
+            Pack::open(&path).err().unwrap().to_string(),
+            format!(
+                "Invalid cursor pack {}: the cursor \"arrow\" has an invalid path, ../x.cur",
+                path.display()
+            )
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,271 @@
+//! Cursor pools: a name that stands for one of several cursors, picked at random, in turn, or by the day.
+//!
+//! A pool can be used wherever a rule names a cursor. Which of its cursors is used is decided when the rule is
+//! entered (for `random`), or by the time (for `rotate` and `daily`), so the cursor stays the same while the
+//! pointer stays in one place. Pools with a `seed` always pick the same way, which makes replays reproducible.
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use schemars::JsonSchema;
+
+use crate::spanned::Spanned;
+use crate::switching;
+
+/// How long each cursor of a `rotate` pool is used, in minutes, when the configuration doesn't say.
+const DEFAULT_EVERY: u64 = 60;
+
+/// How a pool picks its cursor.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Pick {
+    /// Pick one at random each time a rule that uses the pool starts being used.
+    #[default]
+    Random,
+
+    /// Use each cursor in turn, for `every` minutes.
+    Rotate,
+
+    /// Pick one for each day, the same way all day (and on every computer with the same `seed`).
+    Daily,
+}
+
+/// A `[[cursor_pool]]` table of the configuration.
+#[derive(Deserialize, Serialize, Debug, Clone, JsonSchema)]
+pub struct PoolConfig {
+    /// The name rules use for the pool. It can't be the name of a cursor.
+    #[schemars(with = "String")]
+    pub name: Spanned<String>,
+
+    /// The names of the cursors to pick from.
+    #[schemars(with = "Vec<String>")]
+    pub cursors: Vec<Spanned<String>>,
+
+    /// How the cursor is picked: `random` (the default), `rotate`, or `daily`.
+    #[serde(default)]
+    pub pick: Pick,
+
+    /// How many minutes each cursor of a `rotate` pool is used for. Defaults to 60.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub every: Option<u64>,
+
+    /// Seeds the random choices, so they're the same every time. Without one, `random` pools pick differently
+    /// each time the cursor changer starts, and `daily` pools use 0.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+/// A pool of cursors (or of anything else, as `T`), ready to pick from.
+#[derive(Debug)]
+pub struct Pool<T> {
+    /// The pool's name, for the log.
+    pub name: String,
+    items: Vec<T>,
+    pick: Pick,
+    every: chrono::Duration,
+    seed: Option<u64>,
+
+    /// The random number generator for `random` pools, which is seeded on the first pick if there's no `seed`.
+    rng: Option<StdRng>,
+
+    /// What the pool picked last, which a `random` pool keeps until its rule is entered again.
+    current: Option<T>,
+}
+
+impl<T: Copy> Pool<T> {
+    /// Make a pool from its configuration and its `items`, in the order they're configured.
+    pub fn new(config: &PoolConfig, items: Vec<T>) -> Pool<T> {
+        // A rotation can't be shorter than a minute.
+        let every = config.every.unwrap_or(DEFAULT_EVERY).max(1);
+
+        Pool {
+            name: config.name.get_ref().clone(),
+            items,
+            pick: config.pick,
+            every: switching::milliseconds(every.saturating_mul(60_000)),
+            seed: config.seed,
+            rng: config.seed.map(StdRng::seed_from_u64),
+            current: None,
+        }
+    }
+
+    /// Pick an item at `now`. `entered` says whether the rule using the pool has just started being used,
+    /// so a `random` pool picks again. Returns `None` if the pool is empty.
+    pub fn pick(&mut self, entered: bool, now: NaiveDateTime) -> Option<T> {
+        if self.items.is_empty() {
+            return None;
+        }
+
+        let index = match self.pick {
+            Pick::Random => {
+                if let (false, Some(current)) = (entered, self.current) {
+                    return Some(current);
+                }
+
+                // Unseeded pools are seeded by the time of the first pick, which is as good as random while
+                // running, and still the same when a trace is replayed.
+                let rng = self.rng.get_or_insert_with(|| {
+                    let nanos = (now - epoch()).num_nanoseconds().unwrap_or_default();
+                    StdRng::seed_from_u64(nanos as u64)
+                });
+                rng.gen_range(0..self.items.len())
+            }
+            Pick::Rotate => {
+                let turns = (now - epoch()).num_milliseconds() / self.every.num_milliseconds();
+                let offset = self.seed.unwrap_or(0) % self.items.len() as u64;
+                (turns.rem_euclid(self.items.len() as i64) as u64 + offset) as usize
+                    % self.items.len()
+            }
+            Pick::Daily => {
+                let day = now.date().num_days_from_ce() as u64;
+                let mut rng = StdRng::seed_from_u64(self.seed.unwrap_or(0) ^ day);
+                rng.gen_range(0..self.items.len())
+            }
+        };
+
+        let item = self.items[index];
+        self.current = Some(item);
+        Some(item)
+    }
+
+    /// What the pool picked last, if it's picked anything.
+    pub fn current(&self) -> Option<T> {
+        self.current
+    }
+}
+
+/// Midnight at the start of 1970, which rotations are counted from.
+fn epoch() -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(1970, 1, 1)
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::testing::time;
+
+    /// A pool of three letters, configured by `settings` (TOML).
+    fn pool(settings: &str) -> Pool<char> {
+        let text = format!(
+            "name = \"letters\"\ncursors = [\"a\", \"b\", \"c\"]\n{}",
+            settings
+        );
+        let config: PoolConfig = toml::from_str(&text).unwrap();
+        Pool::new(&config, vec!['a', 'b', 'c'])
+    }
+
+    /// Pick `count` times, a minute apart, entering the rule each time.
+    fn picks(pool: &mut Pool<char>, count: i64) -> String {
+        (0..count)
+            .map(|minute| {
+                pool.pick(true, time(0) + Duration::minutes(minute))
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn seeded_random_pools_pick_the_same_way_every_time() {
+        let first = picks(&mut pool("seed = 7"), 20);
+
+        assert_eq!(picks(&mut pool("seed = 7"), 20), first);
+        assert!(
+            first.contains('a') && first.contains('b') && first.contains('c'),
+            "{}",
+            first
+        );
+        assert_ne!(picks(&mut pool("seed = 8"), 20), first);
+    }
+
+    #[test]
+    fn unseeded_random_pools_pick_the_same_way_from_the_same_time() {
+        assert_eq!(picks(&mut pool(""), 20), picks(&mut pool(""), 20));
+    }
+
+    #[test]
+    fn random_pools_keep_their_pick_until_entered_again() {
+        let mut pool = pool("seed = 7");
+        let picked = pool.pick(true, time(0));
+
+        for minute in 1..20 {
+            assert_eq!(
+                pool.pick(false, time(0) + Duration::minutes(minute)),
+                picked
+            );
+        }
+        assert_eq!(pool.current(), picked);
+    }
+
+    #[test]
+    fn rotating_pools_take_turns_and_wrap_around() {
+        let mut rotating = pool("pick = \"rotate\"\nevery = 1");
+        let turns = picks(&mut rotating, 7);
+        assert!(
+            ["abcabca", "bcabcab", "cabcabc"].contains(&turns.as_str()),
+            "{}",
+            turns
+        );
+
+        // Each turn lasts the whole minute, whether the rule is entered or not.
+        let start = time(0) + Duration::minutes(1);
+        let turn = rotating.pick(true, start);
+        assert_eq!(rotating.pick(false, start + Duration::seconds(59)), turn);
+
+        // A seed moves the rotation along.
+        let seeded = picks(&mut pool("pick = \"rotate\"\nevery = 1\nseed = 1"), 7);
+        assert_eq!(seeded, turns[1..].to_string() + &turns[1..2]);
+
+        // Times before 1970 take their turns the same way.
+        let mut rotating = pool("pick = \"rotate\"\nevery = 1");
+        let before = epoch() - Duration::minutes(3);
+        let turns: String = (0..6)
+            .map(|minute| {
+                rotating
+                    .pick(true, before + Duration::minutes(minute))
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(turns, "abcabc");
+    }
+
+    #[test]
+    fn daily_pools_pick_the_same_way_all_day() {
+        let mut daily = pool("pick = \"daily\"\nseed = 3");
+        let midnight = time(0).date().and_hms_opt(0, 0, 0).unwrap();
+        let picked = daily.pick(true, midnight);
+
+        for hour in 1..24 {
+            assert_eq!(daily.pick(true, midnight + Duration::hours(hour)), picked);
+        }
+        assert_eq!(
+            daily.pick(true, midnight + Duration::milliseconds(86_399_999)),
+            picked
+        );
+        assert_eq!(
+            pool("pick = \"daily\"\nseed = 3").pick(false, midnight),
+            picked
+        );
+
+        let month: String = (0..30)
+            .map(|day| daily.pick(true, midnight + Duration::days(day)).unwrap())
+            .collect();
+        assert!(
+            month.contains('a') && month.contains('b') && month.contains('c'),
+            "{}",
+            month
+        );
+    }
+
+    #[test]
+    fn empty_pools_pick_nothing() {
+        let config: PoolConfig = toml::from_str("name = \"empty\"\ncursors = []").unwrap();
+        let mut pool: Pool<char> = Pool::new(&config, Vec::new());
+
+        assert_eq!(pool.pick(true, time(0)), None);
+        assert_eq!(pool.current(), None);
+    }
+}
@@ -0,0 +1,243 @@
+//! Rendering previews of cursors, as PNG contact sheets or as colored text for the terminal.
+
+use crate::cur::CursorImage;
+use crate::cursor_data::CursorData;
+use crate::image::Image;
+
+/// Space between (and around) the images of a contact sheet.
+const PADDING: u32 = 4;
+
+/// Contact sheets start a new row after this many images.
+const COLUMNS: usize = 8;
+
+/// The size of the checkerboard squares drawn behind the images, so that transparency is visible.
+const CHECKER_SIZE: u32 = 4;
+const CHECKER_COLORS: [[u8; 4]; 2] = [[255, 255, 255, 255], [204, 204, 204, 255]];
+
+/// The color used to mark the hotspot.
+const HOTSPOT_COLOR: [u8; 4] = [255, 0, 255, 255];
+
+/// How far the arms of the hotspot marker reach from its center.
+const HOTSPOT_RADIUS: i32 = 2;
+
+/// Terminal previews are resampled to be at most this many pixels (and characters) wide.
+pub const ANSI_WIDTH: u32 = 32;
+
+/// Get the images to show in a preview of `cursor`: every size of a static cursor,
+/// or the image closest to `size` from each frame of an animated one.
+pub fn preview_images(cursor: &CursorData, size: u32) -> Vec<&CursorImage> {
+    match cursor {
+        CursorData::Static(cursor) => {
+            let mut images: Vec<&CursorImage> = cursor.images.iter().collect();
+            images.sort_by_key(|image| image.image.width.max(image.image.height));
+            images
+        }
+        CursorData::Animated(_) => cursor
+            .frames()
+            .into_iter()
+            .filter_map(|frame| frame.best_image(size))
+            .collect(),
+    }
+}
+
+/// Lay out `images` in a grid on a checkerboard background, marking the hotspot of each one.
+pub fn contact_sheet(images: &[&CursorImage]) -> Image {
+    let columns = images.len().clamp(1, COLUMNS);
+    let rows = images.len().div_ceil(columns).max(1);
+
+    let cell_width = images
+        .iter()
+        .map(|image| image.image.width)
+        .max()
+        .unwrap_or(0);
+    let cell_height = images
+        .iter()
+        .map(|image| image.image.height)
+        .max()
+        .unwrap_or(0);
+
+    let mut sheet = Image::new(
+        PADDING + columns as u32 * (cell_width + PADDING),
+        PADDING + rows as u32 * (cell_height + PADDING),
+    );
+
+    for (i, cursor) in images.iter().enumerate() {
+        let x = PADDING + (i % columns) as u32 * (cell_width + PADDING);
+        let y = PADDING + (i / columns) as u32 * (cell_height + PADDING);
+
+        let mut cell = checkerboard(cursor.image.width, cursor.image.height);
+        cell.draw(&cursor.image, 0, 0);
+        mark_hotspot(&mut cell, cursor.hotspot);
+
+        sheet.draw(&cell, x as i32, y as i32);
+    }
+
+    sheet
+}
+
+/// Render an image for a terminal that supports 24-bit color, using half-block characters
+/// so that each character shows two pixels, one above the other.
+/// Transparent pixels are left as the terminal's background, and the hotspot is marked.
+pub fn ansi(cursor: &CursorImage) -> String {
+    let cursor = if cursor.image.width > ANSI_WIDTH {
+        cursor.scale_to(ANSI_WIDTH)
+    } else {
+        cursor.clone()
+    };
+
+    // A cursor file may put its hotspot anywhere, even outside of the image.
+    let mut image = cursor.image;
+    let (x, y) = cursor.hotspot;
+    if x < image.width && y < image.height {
+        image.set_pixel(x, y, HOTSPOT_COLOR);
+    }
+
+    let visible = |pixel: [u8; 4]| pixel[3] >= 128;
+
+    let mut text = String::new();
+    for y in (0..image.height).step_by(2) {
+        for x in 0..image.width {
+            let top = image.pixel(x, y);
+            let bottom = if y + 1 < image.height {
+                image.pixel(x, y + 1)
+            } else {
+                [0, 0, 0, 0]
+            };
+
+            match (visible(top), visible(bottom)) {
+                (false, false) => text.push(' '),
+                (true, false) => text.push_str(&format!(
+                    "\x1b[38;2;{};{};{}m▀\x1b[0m",
+                    top[0], top[1], top[2]
+                )),
+                (false, true) => text.push_str(&format!(
+                    "\x1b[38;2;{};{};{}m▄\x1b[0m",
+                    bottom[0], bottom[1], bottom[2]
+                )),
+                (true, true) => text.push_str(&format!(
+                    "\x1b[38;2;{};{};{};48;2;{};{};{}m▀\x1b[0m",
+                    top[0], top[1], top[2], bottom[0], bottom[1], bottom[2]
+                )),
+            }
+        }
+        text.push('\n');
+    }
+
+    text
+}
+
+fn checkerboard(width: u32, height: u32) -> Image {
+    let mut image = Image::new(width, height);
+
+    for y in 0..height {
+        for x in 0..width {
+            let color = CHECKER_COLORS[((x / CHECKER_SIZE + y / CHECKER_SIZE) % 2) as usize];
+            image.set_pixel(x, y, color);
+        }
+    }
+
+    image
+}
+
+/// Draw a small cross centered on the hotspot.
+fn mark_hotspot(image: &mut Image, hotspot: (u32, u32)) {
+    let (x, y) = (hotspot.0 as i32, hotspot.1 as i32);
+
+    for offset in -HOTSPOT_RADIUS..=HOTSPOT_RADIUS {
+        for &(px, py) in &[(x + offset, y), (x, y + offset)] {
+            if px >= 0 && py >= 0 && (px as u32) < image.width && (py as u32) < image.height {
+                image.set_pixel(px as u32, py as u32, HOTSPOT_COLOR);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cur::CursorFile;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const GREEN: [u8; 4] = [0, 255, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+    const BLACK: [u8; 4] = [0, 0, 0, 255];
+    const TRANSPARENT: [u8; 4] = [0, 0, 0, 0];
+
+    fn filled(width: u32, height: u32, color: [u8; 4], hotspot: (u32, u32)) -> CursorImage {
+        let mut image = Image::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                image.set_pixel(x, y, color);
+            }
+        }
+        CursorImage { image, hotspot }
+    }
+
+    #[test]
+    fn lays_out_contact_sheets_in_rows() {
+        let small = filled(8, 8, BLACK, (0, 0));
+        let large = filled(16, 12, TRANSPARENT, (3, 5));
+        let mut images = vec![&small; 9];
+        images.push(&large);
+
+        // Eight columns and two rows of 16x12 cells, with padding between and around them.
+        let sheet = contact_sheet(&images);
+        assert_eq!((sheet.width, sheet.height), (164, 36));
+        assert_eq!(sheet.pixel(0, 0), TRANSPARENT);
+
+        // The first image is at the top left, with the hotspot marker cut off at its edges.
+        assert_eq!(sheet.pixel(4, 4), HOTSPOT_COLOR);
+        assert_eq!(sheet.pixel(6, 4), HOTSPOT_COLOR);
+        assert_eq!(sheet.pixel(3, 4), TRANSPARENT);
+        assert_eq!(sheet.pixel(9, 9), BLACK);
+
+        // The last image starts the second row, and its transparency shows the checkerboard.
+        assert_eq!(sheet.pixel(24 + 3, 20 + 5), HOTSPOT_COLOR);
+        assert_eq!(sheet.pixel(24 + 7, 20 + 1), CHECKER_COLORS[1]);
+        assert_eq!(sheet.pixel(24 + 1, 20 + 1), CHECKER_COLORS[0]);
+
+        let sheet = contact_sheet(&[&small]);
+        assert_eq!((sheet.width, sheet.height), (16, 16));
+    }
+
+    #[test]
+    fn renders_two_pixels_per_character() {
+        let mut image = Image::new(2, 3);
+        image.set_pixel(0, 0, RED);
+        image.set_pixel(1, 1, BLUE);
+        image.set_pixel(0, 2, GREEN);
+        let cursor = CursorImage {
+            image,
+            hotspot: (1, 0),
+        };
+
+        assert_eq!(
+            ansi(&cursor),
+            "\x1b[38;2;255;0;0m▀\x1b[0m\x1b[38;2;255;0;255;48;2;0;0;255m▀\x1b[0m\n\
+             \x1b[38;2;0;255;0m▀\x1b[0m \n"
+        );
+
+        // Large images are made smaller to fit.
+        let text = ansi(&filled(64, 64, BLACK, (0, 0)));
+        assert_eq!(text.lines().count(), ANSI_WIDTH as usize / 2);
+        assert!(text
+            .lines()
+            .all(|line| line.matches('▀').count() == ANSI_WIDTH as usize));
+    }
+
+    #[test]
+    fn allows_hotspots_outside_of_the_image() {
+        let cursor = CursorFile {
+            images: vec![filled(8, 8, BLACK, (100, 100))],
+        };
+        let cursor = CursorFile::decode(&cursor.encode().unwrap()).unwrap();
+        assert_eq!(cursor.images[0].hotspot, (100, 100));
+
+        let text = ansi(&cursor.images[0]);
+        assert!(!text.contains("255;0;255"));
+        assert_eq!(text.lines().count(), 4);
+
+        let sheet = contact_sheet(&[&cursor.images[0]]);
+        assert_eq!(sheet.pixel(4, 4), BLACK);
+    }
+}
@@ -0,0 +1,229 @@
+//! A ring that spreads out from the pointer and fades away when the cursor changes, so it's easy to see where
+//! the pointer is after moving onto another application.
+//!
+//! The pulse is generated as an animated cursor of the new cursor with the ring around its hotspot, which is shown
+//! once before the new cursor takes its place.
+
+use schemars::JsonSchema;
+
+use crate::ani::AnimatedCursor;
+use crate::cur::{self, CursorImage};
+use crate::effects::Color;
+use crate::frames::{self, placed, Generator};
+use crate::image::Image;
+use crate::Result;
+
+/// How a pulse looks. Every part is optional, so `pulse = {}` gives the usual pulse.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, JsonSchema)]
+pub struct Pulse {
+    /// How long the ring takes to spread out, in milliseconds. Defaults to 400.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<u64>,
+
+    /// How wide the ring gets, as a multiple of the cursor size. Defaults to 3.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<f32>,
+
+    /// The ring's color, like "#ff8000". Defaults to a translucent white.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<Color>,
+}
+
+impl Pulse {
+    /// Generate the pulse around `cursor`, which is shown at `size` pixels.
+    pub fn animate(&self, cursor: &CursorImage, size: u32) -> Result<AnimatedCursor> {
+        // The ring can't grow past the largest cursor there can be.
+        let diameter = (size as f32 * self.size.unwrap_or(3.0)).round() as u32;
+        let ring = Ring {
+            cursor,
+            diameter: diameter.clamp(size, cur::MAX_SIZE),
+            color: self.color.map_or([255, 255, 255, 192], |color| color.0),
+        };
+
+        frames::generate(&ring, self.duration.unwrap_or(400))
+    }
+}
+
+/// A ring that spreads out from a cursor's hotspot, drawn under the cursor.
+pub struct Ring<'a> {
+    pub cursor: &'a CursorImage,
+
+    /// How wide the ring is at the end, in pixels.
+    pub diameter: u32,
+
+    pub color: [u8; 4],
+}
+
+impl Generator for Ring<'_> {
+    /// A square canvas that fits the cursor and the whole ring around its hotspot, since cursors are loaded
+    /// at a square size. It's cut off at the largest size a cursor can be.
+    fn canvas(&self) -> CursorImage {
+        let radius = self.diameter.div_ceil(2);
+        let (x, y) = self.cursor.hotspot;
+        let left = radius.saturating_sub(x);
+        let top = radius.saturating_sub(y);
+        let width = (left + x + radius).max(left + self.cursor.image.width);
+        let height = (top + y + radius).max(top + self.cursor.image.height);
+        let side = width.max(height).min(cur::MAX_SIZE);
+
+        CursorImage {
+            image: Image::new(side, side),
+            hotspot: (left + x, top + y),
+        }
+    }
+
+    fn draw(&self, canvas: &CursorImage, progress: f32) -> Image {
+        let mut image = Image::new(canvas.image.width, canvas.image.height);
+
+        // The ring slows down as it spreads out, and fades away as it goes. Its outside edge ends up at the
+        // diameter, so the canvas doesn't cut it off.
+        let eased = 1.0 - (1.0 - progress) * (1.0 - progress);
+        let thickness = (self.diameter as f32 / 16.0).max(1.5);
+        let radius = (self.diameter as f32 / 2.0 - thickness / 2.0 - 0.5) * eased;
+        let alpha = f32::from(self.color[3]) * (1.0 - progress);
+
+        let (cx, cy) = (canvas.hotspot.0 as f32 + 0.5, canvas.hotspot.1 as f32 + 0.5);
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let distance =
+                    ((x as f32 + 0.5 - cx).powi(2) + (y as f32 + 0.5 - cy).powi(2)).sqrt();
+
+                // How much of the pixel the ring covers, smoothing its edges.
+                let coverage = (thickness / 2.0 + 0.5 - (distance - radius).abs()).clamp(0.0, 1.0);
+                if coverage > 0.0 {
+                    let [r, g, b, _] = self.color;
+                    image.set_pixel(x, y, [r, g, b, (alpha * coverage).round() as u8]);
+                }
+            }
+        }
+
+        image.draw(&placed(canvas, Some(self.cursor), 1.0), 0, 0);
+        image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cur::CursorFile;
+    use crate::testing;
+
+    /// The 32-pixel arrow, at `size` pixels.
+    fn arrow(size: u32) -> CursorImage {
+        let cursor = CursorFile::decode(&testing::read("resample/arrow.cur")).unwrap();
+        cursor.images[0].scale_to(size)
+    }
+
+    fn sizes(animation: &AnimatedCursor) -> Vec<(u32, u32, (u32, u32))> {
+        animation
+            .frames
+            .iter()
+            .flat_map(|frame| &frame.images)
+            .map(|image| (image.image.width, image.image.height, image.hotspot))
+            .collect()
+    }
+
+    /// How many pixels of `image` aren't fully transparent.
+    fn coverage(image: &Image) -> usize {
+        image.pixels.chunks(4).filter(|pixel| pixel[3] > 0).count()
+    }
+
+    #[test]
+    fn pulses_on_a_square_canvas_around_the_hotspot() {
+        let animation = Pulse::default().animate(&arrow(32), 32).unwrap();
+
+        // A 96-pixel ring around the hotspot at (1, 1), and the arrow.
+        assert_eq!(sizes(&animation), vec![(96, 96, (48, 48)); 12]);
+    }
+
+    #[test]
+    fn pulses_fit_in_a_cursor() {
+        let huge = Pulse {
+            size: Some(100.0),
+            ..Pulse::default()
+        };
+        let animation = huge.animate(&arrow(32), 32).unwrap();
+        assert_eq!(sizes(&animation), vec![(256, 256, (128, 128)); 12]);
+        assert!(animation.encode().is_ok());
+
+        // Hotspots at the far corner of the cursor.
+        let corner = |size: u32| CursorImage {
+            hotspot: (size - 1, size - 1),
+            ..arrow(size)
+        };
+        let animation = Pulse::default().animate(&corner(48), 48).unwrap();
+        assert_eq!(sizes(&animation), vec![(144, 144, (72, 72)); 12]);
+
+        let animation = huge.animate(&corner(256), 256).unwrap();
+        assert_eq!(sizes(&animation), vec![(256, 256, (255, 255)); 12]);
+        assert!(animation.encode().is_ok());
+    }
+
+    #[test]
+    fn the_ring_spreads_out_and_fades() {
+        // A cursor with nothing to show, so that only the ring is drawn.
+        let blank = CursorImage {
+            image: Image::new(1, 1),
+            hotspot: (0, 0),
+        };
+        let pulse = Pulse {
+            color: Some(Color([255, 0, 0, 255])),
+            ..Pulse::default()
+        };
+        let animation = pulse.animate(&blank, 32).unwrap();
+
+        let images: Vec<&Image> = animation
+            .frames
+            .iter()
+            .map(|frame| &frame.images[0].image)
+            .collect();
+        let covered: Vec<usize> = images.iter().map(|image| coverage(image)).collect();
+        let alphas: Vec<u8> = images
+            .iter()
+            .map(|image| image.pixels.chunks(4).map(|pixel| pixel[3]).max().unwrap())
+            .collect();
+
+        assert!(
+            covered.windows(2).all(|pair| pair[0] < pair[1]),
+            "{:?}",
+            covered
+        );
+        assert!(
+            alphas.windows(2).all(|pair| pair[0] > pair[1]),
+            "{:?}",
+            alphas
+        );
+
+        // The ring never reaches the edge of the canvas.
+        let last = images.last().unwrap();
+        assert!((0..last.width).all(|x| last.pixel(x, 0)[3] == 0 && last.pixel(0, x)[3] == 0));
+    }
+
+    #[test]
+    fn an_empty_table_is_the_usual_pulse() {
+        #[derive(Deserialize)]
+        struct Config {
+            pulse: Pulse,
+        }
+        let config: Config = toml::from_str("pulse = {}").unwrap();
+        assert_eq!(config.pulse, Pulse::default());
+
+        let cursor = arrow(32);
+        let animation = config.pulse.animate(&cursor, 32).unwrap();
+        let usual = Pulse {
+            duration: Some(400),
+            size: Some(3.0),
+            color: Some(Color([255, 255, 255, 192])),
+        };
+        let expected = usual.animate(&cursor, 32).unwrap();
+
+        assert_eq!(sizes(&animation), sizes(&expected));
+        assert_eq!(animation.duration(), expected.duration());
+        for (frame, expected) in animation.frames.iter().zip(&expected.frames) {
+            assert_eq!(
+                frame.images[0].image.pixels,
+                expected.images[0].image.pixels
+            );
+        }
+    }
+}
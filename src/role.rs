@@ -0,0 +1,164 @@
+//! The roles a cursor can play, like the normal pointer or the text selection I-beam.
+//!
+//! Windows keeps a separate system cursor for each role; a `[[cursor]]` can give a file for each one.
+
+use std::convert::TryFrom;
+
+use crate::diagnostic;
+
+/// A system cursor role, named after the settings in the Windows mouse control panel.
+// Roles are used as table keys, which TOML can't deserialize straight into an enum.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(try_from = "String")]
+pub enum CursorRole {
+    Pointer,
+    Help,
+    Working,
+    Busy,
+    Precision,
+    Text,
+    Unavailable,
+    VerticalResize,
+    HorizontalResize,
+    DiagonalResize1,
+    DiagonalResize2,
+    Move,
+    Alternate,
+    Link,
+}
+
+impl TryFrom<String> for CursorRole {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<CursorRole, String> {
+        CursorRole::ALL
+            .iter()
+            .copied()
+            .find(|role| role.name() == name)
+            .ok_or_else(|| {
+                let names: Vec<&str> = CursorRole::ALL.iter().map(|role| role.name()).collect();
+                match diagnostic::did_you_mean(&name, names.iter().copied()) {
+                    Some(suggestion) => format!("unknown cursor role \"{}\", {}", name, suggestion),
+                    None => format!(
+                        "unknown cursor role \"{}\", expected one of: {}",
+                        name,
+                        names.join(", ")
+                    ),
+                }
+            })
+    }
+}
+
+impl serde::Serialize for CursorRole {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+/// The value names Windows uses for each role under `HKCU\Control Panel\Cursors`,
+/// in the order that a scheme under `HKCU\Control Panel\Cursors\Schemes` lists its files.
+///
+/// `NWPen`, `Pin`, and `Person` can't be set with `SetSystemCursor`, so they have no role.
+const SCHEME_ORDER: [(&str, Option<CursorRole>); 17] = [
+    ("Arrow", Some(CursorRole::Pointer)),
+    ("Help", Some(CursorRole::Help)),
+    ("AppStarting", Some(CursorRole::Working)),
+    ("Wait", Some(CursorRole::Busy)),
+    ("Crosshair", Some(CursorRole::Precision)),
+    ("IBeam", Some(CursorRole::Text)),
+    ("NWPen", None),
+    ("No", Some(CursorRole::Unavailable)),
+    ("SizeNS", Some(CursorRole::VerticalResize)),
+    ("SizeWE", Some(CursorRole::HorizontalResize)),
+    ("SizeNWSE", Some(CursorRole::DiagonalResize1)),
+    ("SizeNESW", Some(CursorRole::DiagonalResize2)),
+    ("SizeAll", Some(CursorRole::Move)),
+    ("UpArrow", Some(CursorRole::Alternate)),
+    ("Hand", Some(CursorRole::Link)),
+    ("Pin", None),
+    ("Person", None),
+];
+
+impl CursorRole {
+    /// Every role, in the order Windows lists them.
+    pub const ALL: [CursorRole; 14] = [
+        CursorRole::Pointer,
+        CursorRole::Help,
+        CursorRole::Working,
+        CursorRole::Busy,
+        CursorRole::Precision,
+        CursorRole::Text,
+        CursorRole::Unavailable,
+        CursorRole::VerticalResize,
+        CursorRole::HorizontalResize,
+        CursorRole::DiagonalResize1,
+        CursorRole::DiagonalResize2,
+        CursorRole::Move,
+        CursorRole::Alternate,
+        CursorRole::Link,
+    ];
+
+    /// The name used for this role in the configuration file.
+    pub fn name(self) -> &'static str {
+        match self {
+            CursorRole::Pointer => "pointer",
+            CursorRole::Help => "help",
+            CursorRole::Working => "working",
+            CursorRole::Busy => "busy",
+            CursorRole::Precision => "precision",
+            CursorRole::Text => "text",
+            CursorRole::Unavailable => "unavailable",
+            CursorRole::VerticalResize => "vertical-resize",
+            CursorRole::HorizontalResize => "horizontal-resize",
+            CursorRole::DiagonalResize1 => "diagonal-resize-1",
+            CursorRole::DiagonalResize2 => "diagonal-resize-2",
+            CursorRole::Move => "move",
+            CursorRole::Alternate => "alternate",
+            CursorRole::Link => "link",
+        }
+    }
+
+    /// The `OCR_*` identifier of the system cursor for this role.
+    ///
+    /// See: https://docs.microsoft.com/en-us/windows/desktop/api/winuser/nf-winuser-setsystemcursor
+    pub fn system_id(self) -> u32 {
+        match self {
+            CursorRole::Pointer => 32512,          // OCR_NORMAL
+            CursorRole::Help => 32651,             // OCR_HELP
+            CursorRole::Working => 32650,          // OCR_APPSTARTING
+            CursorRole::Busy => 32514,             // OCR_WAIT
+            CursorRole::Precision => 32515,        // OCR_CROSS
+            CursorRole::Text => 32513,             // OCR_IBEAM
+            CursorRole::Unavailable => 32648,      // OCR_NO
+            CursorRole::VerticalResize => 32645,   // OCR_SIZENS
+            CursorRole::HorizontalResize => 32644, // OCR_SIZEWE
+            CursorRole::DiagonalResize1 => 32642,  // OCR_SIZENWSE
+            CursorRole::DiagonalResize2 => 32643,  // OCR_SIZENESW
+            CursorRole::Move => 32646,             // OCR_SIZEALL
+            CursorRole::Alternate => 32516,        // OCR_UP
+            CursorRole::Link => 32649,             // OCR_HAND
+        }
+    }
+
+    /// Find the role for a value name under `HKCU\Control Panel\Cursors` (like `IBeam`), ignoring case.
+    /// Returns `None` for names that aren't roles, including the ones that can't be set.
+    pub fn from_registry_name(name: &str) -> Option<CursorRole> {
+        SCHEME_ORDER
+            .iter()
+            .find(|(registry_name, _)| registry_name.eq_ignore_ascii_case(name))
+            .and_then(|(_, role)| *role)
+    }
+
+    /// Check whether `name` is one of the cursor values under `HKCU\Control Panel\Cursors`, ignoring case,
+    /// whether or not it has a role.
+    pub fn is_registry_name(name: &str) -> bool {
+        SCHEME_ORDER
+            .iter()
+            .any(|(registry_name, _)| registry_name.eq_ignore_ascii_case(name))
+    }
+
+    /// Get the registry name and role for each position in a scheme's list of files.
+    pub fn scheme_order() -> impl Iterator<Item = (&'static str, Option<CursorRole>)> {
+        SCHEME_ORDER.iter().copied()
+    }
+}
@@ -0,0 +1,95 @@
+//! Values that remember where they are in the configuration file, for pointing out mistakes.
+//!
+//! Only TOML files give values their place. JSON and YAML files are read with `without_spans`, and their
+//! values have no span.
+
+use std::cell::Cell;
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+thread_local! {
+    /// Whether values are being read from a format that can't give them a span.
+    static WITHOUT_SPANS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// A value, and the range of bytes it was read from.
+#[derive(Clone)]
+pub struct Spanned<T> {
+    span: Option<(usize, usize)>,
+    value: T,
+}
+
+impl<T> Spanned<T> {
+    /// Where the value is in the file, if it was read from TOML.
+    pub fn span(&self) -> Option<(usize, usize)> {
+        self.span
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.value
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+/// Read values for `Spanned` without asking for their spans, for deserializers other than TOML's.
+pub fn without_spans<R, F: FnOnce() -> R>(read: F) -> R {
+    let outer = WITHOUT_SPANS.with(|without_spans| without_spans.replace(true));
+    let result = read();
+    WITHOUT_SPANS.with(|without_spans| without_spans.set(outer));
+    result
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Spanned<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Spanned<T>, D::Error> {
+        if WITHOUT_SPANS.with(Cell::get) {
+            return T::deserialize(deserializer).map(|value| Spanned { span: None, value });
+        }
+
+        let spanned = toml::Spanned::<T>::deserialize(deserializer)?;
+        Ok(Spanned {
+            span: Some(spanned.span()),
+            value: spanned.into_inner(),
+        })
+    }
+}
+
+impl<T: Serialize> Serialize for Spanned<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(serializer)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Spanned<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Named {
+        name: Spanned<String>,
+    }
+
+    #[test]
+    fn only_toml_gives_spans() {
+        let named: Named = toml::from_str("name = \"dog\"").unwrap();
+        assert_eq!(named.name.get_ref(), "dog");
+        assert_eq!(named.name.span(), Some((7, 12)));
+
+        let named: Named = without_spans(|| serde_json::from_str(r#"{"name": "dog"}"#)).unwrap();
+        assert_eq!(named.name.get_ref(), "dog");
+        assert_eq!(named.name.span(), None);
+
+        // Reading TOML again afterwards gives spans again.
+        let named: Named = toml::from_str("name = 'cat'").unwrap();
+        assert_eq!(named.name.span(), Some((7, 12)));
+    }
+}
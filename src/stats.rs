@@ -0,0 +1,453 @@
+//! Usage statistics: how long the pointer spends over each application and with each cursor, and how often
+//! the cursor is switched, added up for each day.
+//!
+//! The running cursor changer reports what's under the pointer on every tick, and the time until the next
+//! tick is counted towards it. Statistics are kept in a CSV file with one row per day and thing counted:
+//!
+//! ```text
+//! date,kind,name,value
+//! 2024-05-01,application,C:\Program Files\Microsoft VS Code\Code.exe,5400000
+//! 2024-05-01,cursor,big,5400000
+//! 2024-05-01,switches,,12
+//! ```
+//!
+//! Times are in milliseconds.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use schemars::JsonSchema;
+
+use crate::error::{Error, WithPath};
+use crate::Result;
+
+/// The name of the statistics file, when the configuration doesn't give one.
+pub const FILE_NAME: &str = "cursor-stats.csv";
+
+/// The name used for time spent with the system's own cursors.
+pub const SYSTEM_CURSOR: &str = "(system)";
+
+/// Gaps between ticks longer than this (like while the computer is asleep) aren't counted.
+const MAX_GAP_MILLISECONDS: i64 = 10_000;
+
+/// How often the statistics are saved while running.
+const SAVE_INTERVAL_SECONDS: i64 = 60;
+
+const HEADER: &str = "date,kind,name,value";
+
+/// The `[stats]` table of the configuration.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, JsonSchema)]
+pub struct StatsConfig {
+    /// Record usage statistics while running.
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// The statistics file, relative to the configuration file that sets it.
+    /// Defaults to `cursor-stats.csv` next to the configuration file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+}
+
+impl StatsConfig {
+    /// Get the statistics file, which is in `dir` unless the configuration says otherwise.
+    pub fn path(&self, dir: &Path) -> PathBuf {
+        match &self.file {
+            Some(file) => PathBuf::from(file),
+            None => dir.join(FILE_NAME),
+        }
+    }
+}
+
+/// What was counted on one day.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Day {
+    /// Milliseconds the pointer spent over each application, by its executable's path.
+    pub applications: BTreeMap<String, i64>,
+
+    /// Milliseconds each cursor was in use, by its name (or `SYSTEM_CURSOR`).
+    pub cursors: BTreeMap<String, i64>,
+
+    /// How many times the cursor was switched.
+    pub switches: i64,
+}
+
+impl Day {
+    /// Add another day's counts to this one's.
+    pub fn add(&mut self, other: &Day) {
+        for (application, time) in &other.applications {
+            *self.applications.entry(application.clone()).or_default() += time;
+        }
+        for (cursor, time) in &other.cursors {
+            *self.cursors.entry(cursor.clone()).or_default() += time;
+        }
+        self.switches += other.switches;
+    }
+
+    /// The total time that was counted, in milliseconds.
+    pub fn tracked(&self) -> i64 {
+        self.cursors.values().sum()
+    }
+}
+
+/// What was under the pointer at some moment.
+#[derive(Clone, Debug)]
+struct Observation {
+    time: NaiveDateTime,
+    application: Option<String>,
+    cursor: String,
+}
+
+/// Statistics for every day that has any.
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    pub days: BTreeMap<NaiveDate, Day>,
+
+    /// The previous observation, whose application and cursor the time since then is counted towards.
+    last: Option<Observation>,
+}
+
+impl Stats {
+    /// Record that at `time` (local time), the pointer was over `application` (if it's over a window)
+    /// with `cursor` in use (`None` for the system's cursors).
+    pub fn observe(
+        &mut self,
+        time: NaiveDateTime,
+        application: Option<&str>,
+        cursor: Option<&str>,
+    ) {
+        let observation = Observation {
+            time,
+            application: application.map(str::to_string),
+            cursor: cursor.unwrap_or(SYSTEM_CURSOR).to_string(),
+        };
+
+        if let Some(last) = self.last.take() {
+            let elapsed = (time - last.time).num_milliseconds();
+            if elapsed > 0 && elapsed <= MAX_GAP_MILLISECONDS {
+                // Time is counted on the day it started, even if it runs past midnight.
+                let day = self.days.entry(last.time.date()).or_default();
+                if let Some(application) = &last.application {
+                    *day.applications.entry(application.clone()).or_default() += elapsed;
+                }
+                *day.cursors.entry(last.cursor.clone()).or_default() += elapsed;
+            }
+
+            if last.cursor != observation.cursor {
+                self.days.entry(time.date()).or_default().switches += 1;
+            }
+        }
+
+        self.last = Some(observation);
+    }
+
+    /// Add up the days from `first` to `last` (inclusive).
+    pub fn total(&self, first: NaiveDate, last: NaiveDate) -> Day {
+        let mut total = Day::default();
+        for day in self.days.range(first..=last).map(|(_, day)| day) {
+            total.add(day);
+        }
+        total
+    }
+
+    /// Read statistics from CSV text, as written by `to_csv`.
+    pub fn parse(text: &str) -> Result<Stats> {
+        let mut stats = Stats::default();
+
+        for (number, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || (number == 0 && line == HEADER) {
+                continue;
+            }
+
+            let invalid = |reason: &str| Error::InvalidStats {
+                line: number + 1,
+                reason: reason.to_string(),
+            };
+
+            let fields = split_csv(line);
+            let (date, kind, name, value) = match fields.as_slice() {
+                [date, kind, name, value] => (date, kind, name, value),
+                _ => return Err(invalid("expected 4 fields")),
+            };
+
+            let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .map_err(|_| invalid("the date isn't written like 2024-05-01"))?;
+            let value: i64 = value
+                .parse()
+                .map_err(|_| invalid("the value isn't a whole number"))?;
+
+            let day = stats.days.entry(date).or_default();
+            match kind.as_str() {
+                "application" => *day.applications.entry(name.clone()).or_default() += value,
+                "cursor" => *day.cursors.entry(name.clone()).or_default() += value,
+                "switches" => day.switches += value,
+                _ => return Err(invalid("the kind isn't application, cursor, or switches")),
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// Write these statistics as CSV text.
+    pub fn to_csv(&self) -> String {
+        let mut text = format!("{}\n", HEADER);
+
+        for (date, day) in &self.days {
+            let mut row = |kind: &str, name: &str, value: i64| {
+                text.push_str(&format!(
+                    "{},{},{},{}\n",
+                    date,
+                    kind,
+                    quote_csv(name),
+                    value
+                ));
+            };
+
+            for (application, time) in &day.applications {
+                row("application", application, *time);
+            }
+            for (cursor, time) in &day.cursors {
+                row("cursor", cursor, *time);
+            }
+            if day.switches > 0 {
+                row("switches", "", day.switches);
+            }
+        }
+
+        text
+    }
+
+    /// Read statistics from a file. A file that doesn't exist has no statistics.
+    pub fn load(path: &Path) -> Result<Stats> {
+        if !path.exists() {
+            return Ok(Stats::default());
+        }
+
+        Stats::parse(&fs::read_to_string(path).with_path(path)?)
+    }
+}
+
+/// Statistics that are being recorded by the running cursor changer, and regularly saved to a file.
+pub struct Recorder {
+    path: PathBuf,
+
+    /// Statistics from the file, and everything recorded since.
+    stats: Stats,
+
+    /// When the statistics were last saved.
+    saved: Option<NaiveDateTime>,
+}
+
+impl Recorder {
+    /// Continue recording the statistics in the file at `path`.
+    pub fn open(path: PathBuf) -> Result<Recorder> {
+        let stats = Stats::load(&path)?;
+
+        Ok(Recorder {
+            path,
+            stats,
+            saved: None,
+        })
+    }
+
+    /// Record an observation (see `Stats::observe`), saving the statistics if they haven't been saved in a while.
+    pub fn observe(
+        &mut self,
+        time: NaiveDateTime,
+        application: Option<&str>,
+        cursor: Option<&str>,
+    ) -> Result<()> {
+        self.stats.observe(time, application, cursor);
+
+        match self.saved {
+            Some(saved) if time - saved < Duration::seconds(SAVE_INTERVAL_SECONDS) => Ok(()),
+            _ => {
+                self.saved = Some(time);
+                self.save()
+            }
+        }
+    }
+
+    /// Write the statistics to the file.
+    pub fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).with_path(dir)?;
+        }
+
+        // Write a new file and then replace the old one, so that it isn't lost if writing fails partway.
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        fs::write(&temporary, self.stats.to_csv()).with_path(&temporary)?;
+        fs::rename(&temporary, &self.path).with_path(&self.path)?;
+
+        Ok(())
+    }
+}
+
+/// Format a time in milliseconds like "3h 05m", "12m 30s", or "8s".
+pub fn format_duration(milliseconds: i64) -> String {
+    let seconds = milliseconds / 1000;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+    if hours > 0 {
+        format!("{}h {:02}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {:02}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+/// Quote a CSV field if it has commas, quotes, or line breaks in it.
+fn quote_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Split a line of CSV into its fields, unquoting any quoted ones.
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().unwrap();
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            _ => field.push(c),
+        }
+    }
+
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = r"C:\Program Files\Microsoft VS Code\Code.exe";
+    const SHELL: &str = r"C:\Windows\System32\WindowsPowerShell\v1.0\powershell.exe";
+
+    fn may(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, day).unwrap()
+    }
+
+    /// A time on 1 May 2024.
+    fn at(hour: u32, minute: u32, second: u32, milli: u32) -> NaiveDateTime {
+        may(1)
+            .and_hms_milli_opt(hour, minute, second, milli)
+            .unwrap()
+    }
+
+    fn totals(map: &BTreeMap<String, i64>) -> Vec<(&str, i64)> {
+        map.iter()
+            .map(|(name, time)| (name.as_str(), *time))
+            .collect()
+    }
+
+    #[test]
+    fn counts_time_towards_what_came_before() {
+        let mut stats = Stats::default();
+        stats.observe(at(9, 0, 0, 0), Some(CODE), Some("big"));
+        stats.observe(at(9, 0, 1, 500), Some(CODE), Some("big"));
+        stats.observe(at(9, 0, 4, 0), Some(SHELL), None);
+        stats.observe(at(9, 0, 5, 0), None, Some("big"));
+        stats.observe(at(9, 0, 7, 0), None, Some("big"));
+
+        let day = &stats.days[&may(1)];
+        assert_eq!(totals(&day.applications), [(CODE, 4000), (SHELL, 1000)]);
+        assert_eq!(totals(&day.cursors), [(SYSTEM_CURSOR, 1000), ("big", 6000)]);
+        assert_eq!(day.switches, 2);
+        assert_eq!(day.tracked(), 7000);
+    }
+
+    #[test]
+    fn zero_length_spans_count_switches_but_no_time() {
+        let mut stats = Stats::default();
+        stats.observe(at(9, 0, 0, 0), Some(CODE), Some("big"));
+        stats.observe(at(9, 0, 0, 0), Some(SHELL), None);
+        stats.observe(at(9, 0, 0, 0), Some(CODE), Some("big"));
+        stats.observe(at(9, 0, 1, 0), Some(CODE), Some("big"));
+
+        let day = &stats.days[&may(1)];
+        assert_eq!(totals(&day.applications), [(CODE, 1000)]);
+        assert_eq!(totals(&day.cursors), [("big", 1000)]);
+        assert_eq!(day.switches, 2);
+    }
+
+    #[test]
+    fn clock_jumps_arent_counted() {
+        let mut stats = Stats::default();
+        stats.observe(at(9, 0, 0, 0), Some(CODE), Some("big"));
+        stats.observe(at(9, 0, 10, 0), Some(CODE), Some("big"));
+
+        // Asleep for a minute, and then the clock goes back an hour.
+        stats.observe(at(9, 1, 10, 1), Some(CODE), Some("big"));
+        stats.observe(at(8, 1, 10, 1), Some(CODE), Some("big"));
+        stats.observe(at(8, 1, 12, 1), Some(CODE), Some("big"));
+
+        // A gap of just over the limit isn't counted either.
+        stats.observe(at(8, 1, 22, 2), Some(CODE), Some("big"));
+
+        let day = &stats.days[&may(1)];
+        assert_eq!(totals(&day.applications), [(CODE, 12_000)]);
+        assert_eq!(day.tracked(), 12_000);
+        assert_eq!(day.switches, 0);
+    }
+
+    #[test]
+    fn time_past_midnight_counts_on_the_day_it_started() {
+        let mut stats = Stats::default();
+        stats.observe(at(23, 59, 58, 0), Some(CODE), Some("big"));
+        let next_day = may(2).and_hms_opt(0, 0, 3).unwrap();
+        stats.observe(next_day, Some(CODE), None);
+        stats.observe(next_day + Duration::seconds(1), Some(CODE), None);
+
+        assert_eq!(totals(&stats.days[&may(1)].cursors), [("big", 5000)]);
+        assert_eq!(stats.days[&may(1)].switches, 0);
+        assert_eq!(
+            totals(&stats.days[&may(2)].cursors),
+            [(SYSTEM_CURSOR, 1000)]
+        );
+        assert_eq!(stats.days[&may(2)].switches, 1);
+
+        let total = stats.total(may(1), may(2));
+        assert_eq!(totals(&total.applications), [(CODE, 6000)]);
+        assert_eq!(total.tracked(), 6000);
+        assert_eq!(total.switches, 1);
+        assert_eq!(stats.total(may(2), may(30)).tracked(), 1000);
+    }
+
+    #[test]
+    fn round_trips_through_csv() {
+        let mut stats = Stats::default();
+        stats.observe(
+            at(9, 0, 0, 0),
+            Some(r#"C:\Odd, "Quoted"\app.exe"#),
+            Some("big"),
+        );
+        stats.observe(at(9, 0, 2, 0), Some(CODE), None);
+        stats.observe(at(9, 0, 3, 0), Some(CODE), None);
+
+        let csv = stats.to_csv();
+        assert_eq!(
+            csv,
+            "date,kind,name,value\n\
+             2024-05-01,application,\"C:\\Odd, \"\"Quoted\"\"\\app.exe\",2000\n\
+             2024-05-01,application,C:\\Program Files\\Microsoft VS Code\\Code.exe,1000\n\
+             2024-05-01,cursor,(system),1000\n\
+             2024-05-01,cursor,big,2000\n\
+             2024-05-01,switches,,1\n"
+        );
+        assert_eq!(Stats::parse(&csv).unwrap().days, stats.days);
+    }
+}
@@ -0,0 +1,183 @@
+//! Waiting a moment before switching cursors, so that moving the pointer quickly across the edge of a window,
+//! or over a small window in the way, doesn't make the cursor flicker back and forth.
+//!
+//! A new cursor is only used once the pointer has wanted it for the `debounce` time, and the system cursors
+//! are only restored once the pointer has wanted them for the `reset_delay`. Applications can set their own.
+//! A `transition` can also be played in between the two cursors, as described in `transition`,
+//! and a `pulse` around the pointer when a cursor is activated, as described in `pulse`.
+
+use chrono::{Duration, NaiveDateTime};
+use schemars::JsonSchema;
+
+use crate::pulse::Pulse;
+use crate::transition::Transition;
+
+/// The `[switching]` table of the configuration.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, JsonSchema)]
+pub struct SwitchingConfig {
+    /// How long, in milliseconds, the pointer must stay where another cursor is wanted before switching to it.
+    /// Defaults to 0, switching straight away.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debounce: Option<u64>,
+
+    /// How long, in milliseconds, the pointer must stay where no custom cursor is wanted before
+    /// the system cursors are restored. Defaults to 0, restoring them straight away.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reset_delay: Option<u64>,
+
+    /// How to move from one cursor to the next. Without one, the new cursor is used straight away.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transition: Option<Transition>,
+
+    /// The pulse to show around the pointer when a cursor is activated. Without one, there's no pulse.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pulse: Option<Pulse>,
+}
+
+impl SwitchingConfig {
+    /// Get how long to wait before switching to another cursor.
+    pub fn debounce(&self) -> Duration {
+        milliseconds(self.debounce.unwrap_or(0))
+    }
+
+    /// Get how long to wait before restoring the system cursors.
+    pub fn reset_delay(&self) -> Duration {
+        milliseconds(self.reset_delay.unwrap_or(0))
+    }
+}
+
+/// Convert a time in milliseconds from the configuration.
+pub fn milliseconds(time: u64) -> Duration {
+    Duration::milliseconds(time.min(i64::MAX as u64) as i64)
+}
+
+/// Holds back a switch until the same thing has been wanted for long enough.
+#[derive(Debug)]
+pub struct Debouncer<T> {
+    /// What's wanted but not switched to yet, and since when.
+    pending: Option<(T, NaiveDateTime)>,
+}
+
+impl<T> Default for Debouncer<T> {
+    fn default() -> Debouncer<T> {
+        Debouncer { pending: None }
+    }
+}
+
+impl<T: PartialEq> Debouncer<T> {
+    /// Decide whether to switch from `current` to `wanted` at `now`: once `wanted` has been wanted for `delay`
+    /// without anything else being wanted in between. Returns `true` if `wanted` is already `current`.
+    pub fn ready(&mut self, current: &T, wanted: T, delay: Duration, now: NaiveDateTime) -> bool {
+        if wanted == *current {
+            self.pending = None;
+            return true;
+        }
+
+        let since = match &self.pending {
+            // If the clock went back, start waiting again rather than wait for it to catch up.
+            Some((pending, since)) if *pending == wanted && *since <= now => *since,
+            _ => {
+                self.pending = Some((wanted, now));
+                now
+            }
+        };
+
+        if now - since >= delay {
+            self.pending = None;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::testing::{self, over, time};
+    use crate::trace::{self, Event};
+
+    fn wait(milliseconds: i64) -> Duration {
+        Duration::milliseconds(milliseconds)
+    }
+
+    #[test]
+    fn switches_once_wanted_for_the_delay() {
+        let mut debouncer = Debouncer::default();
+
+        assert!(!debouncer.ready(&None, Some(1), wait(100), time(0)));
+        assert!(!debouncer.ready(&None, Some(1), wait(100), time(99)));
+        assert!(debouncer.ready(&None, Some(1), wait(100), time(100)));
+
+        // Switching is done, so the next switch waits from the start.
+        assert!(!debouncer.ready(&Some(1), None, wait(100), time(100)));
+    }
+
+    #[test]
+    fn switches_straight_away_without_a_delay() {
+        let mut debouncer = Debouncer::default();
+
+        assert!(debouncer.ready(&None, Some(1), wait(0), time(0)));
+        assert!(debouncer.ready(&Some(1), Some(1), wait(100), time(0)));
+    }
+
+    #[test]
+    fn wanting_something_else_starts_the_wait_again() {
+        let mut debouncer = Debouncer::default();
+
+        assert!(!debouncer.ready(&None, Some(1), wait(100), time(0)));
+        assert!(!debouncer.ready(&None, Some(2), wait(100), time(50)));
+        assert!(!debouncer.ready(&None, Some(1), wait(100), time(100)));
+        assert!(!debouncer.ready(&None, Some(1), wait(100), time(199)));
+        assert!(debouncer.ready(&None, Some(1), wait(100), time(200)));
+
+        // Wanting what's current in between starts it again too.
+        assert!(!debouncer.ready(&Some(1), Some(2), wait(100), time(300)));
+        assert!(debouncer.ready(&Some(1), Some(1), wait(100), time(350)));
+        assert!(!debouncer.ready(&Some(1), Some(2), wait(100), time(400)));
+        assert!(debouncer.ready(&Some(1), Some(2), wait(100), time(500)));
+    }
+
+    #[test]
+    fn the_clock_going_back_starts_the_wait_again() {
+        let mut debouncer = Debouncer::default();
+
+        assert!(!debouncer.ready(&None, Some(1), wait(100), time(1000)));
+        assert!(!debouncer.ready(&None, Some(1), wait(100), time(500)));
+        assert!(!debouncer.ready(&None, Some(1), wait(100), time(599)));
+        assert!(debouncer.ready(&None, Some(1), wait(100), time(600)));
+    }
+
+    #[test]
+    fn waits_for_the_debounce_and_the_reset_delay() {
+        let mut config = Config::from_file(testing::path("replay/cursor.toml")).unwrap();
+        config.switching = Some(SwitchingConfig {
+            debounce: Some(100),
+            reset_delay: Some(300),
+            ..SwitchingConfig::default()
+        });
+
+        let notepad = Some(r"C:\Windows\notepad.exe");
+        let explorer = Some(r"C:\Windows\explorer.exe");
+        let ticks = [
+            over(0, explorer),
+            over(10, notepad),
+            over(109, notepad),
+            over(110, notepad),
+            over(200, explorer),
+            over(499, explorer),
+            over(500, explorer),
+        ];
+        let events: Vec<Event> = ticks.iter().cloned().map(Event::Tick).collect();
+
+        let replay = trace::replay(config, &events).unwrap();
+        let arrow = Event::SetCursor {
+            cursor: "arrow".to_string(),
+        };
+        assert_eq!(
+            replay.actual,
+            [(Some(time(110)), arrow), (Some(time(500)), Event::Restore)]
+        );
+    }
+}
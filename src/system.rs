@@ -1,21 +1,145 @@
 use std::ffi::OsStr;
 use std::iter::once;
 use std::os::windows::ffi::OsStrExt;
+use std::path::PathBuf;
 use std::ptr::null_mut;
 use winapi::shared::minwindef::{DWORD, UINT};
 use winapi::shared::windef::HCURSOR;
 
-use crate::CursorHandle;
+use crate::backend::{Backend, Observation, Pointer, Window};
+use crate::condition::Held;
+use crate::info::{CursorPosition, Process};
+use crate::overlay::Overlay;
+use crate::role::CursorRole;
+use crate::trail::Drawing;
+use crate::{Cursor, CursorFiles, CursorHandle, Result};
+
+/// The backend that reads the pointer and sets the cursors with the Windows API.
+#[derive(Debug, Default)]
+pub struct SystemBackend {
+    /// The window the trail is drawn in, once there's been a trail to draw.
+    overlay: Option<Overlay>,
+
+    /// Set once drawing the trail has failed, so it isn't tried (and logged) on every tick after.
+    trail_failed: bool,
+}
+
+impl SystemBackend {
+    fn draw_trail(&mut self, drawing: &Drawing) -> Result<()> {
+        if self.overlay.is_none() {
+            self.overlay = Some(Overlay::create()?);
+        }
+        if let Some(overlay) = &mut self.overlay {
+            overlay.show(drawing)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Backend for SystemBackend {
+    type Handle = CursorHandle;
+
+    fn observe(&mut self) -> Observation {
+        let time = chrono::Local::now().naive_local();
+        let held = held_keys();
+
+        let position = match CursorPosition::try_read() {
+            Some(position) => position,
+            None => {
+                return Observation {
+                    time,
+                    pointer: None,
+                    window: None,
+                    held,
+                }
+            }
+        };
+
+        let pointer = Pointer {
+            position: position.coordinates(),
+            cursor_size: cursor_size_for_dpi(position.dpi()),
+        };
+
+        // Get the process of the window under the cursor (if any), and the full path to its executable.
+        let window = Process::from_position(&position).map(|process| {
+            let (executable, error) = match process.executable_path() {
+                Ok(executable) => (Some(executable), None),
+                Err(e) => (None, Some(e.to_string())),
+            };
+
+            Window {
+                id: process.window_id(),
+                executable,
+                error,
+            }
+        });
+
+        Observation {
+            time,
+            pointer: Some(pointer),
+            window,
+            held,
+        }
+    }
+
+    fn load(&mut self, files: &CursorFiles, path: &str, size: u32) -> Result<CursorHandle> {
+        // Windows can only load .cur and .ani files, at their own scale.
+        let file = if files.needs_converting(path) {
+            files.convert(path, size)?
+        } else {
+            PathBuf::from(path)
+        };
+
+        Ok(get_cursor(&file.to_string_lossy(), size))
+    }
+
+    fn destroy(&mut self, handle: &CursorHandle) {
+        destroy_cursor(handle);
+    }
+
+    fn set_cursor(&mut self, cursor: &Cursor<CursorHandle>) {
+        set_system_cursor(&cursor.handle, &cursor.role_handles);
+    }
+
+    fn restore_cursors(&mut self) {
+        restore_original_cursors();
+    }
+
+    fn show_trail(&mut self, trail: Option<&Drawing>) {
+        if self.trail_failed {
+            return;
+        }
+
+        match trail {
+            Some(drawing) => {
+                if let Err(e) = self.draw_trail(drawing) {
+                    error!("Failed to draw the trail, so it won't be shown: {}", e);
+                    self.trail_failed = true;
+                }
+            }
+            None => {
+                if let Some(overlay) = &mut self.overlay {
+                    overlay.hide();
+                }
+            }
+        }
+    }
+}
 
 
 // We have to encode text to wide format for Windows
 #[cfg(windows)]
-fn win32_string(value: &str) -> Vec<u16> {
+pub fn win32_string(value: &str) -> Vec<u16> {
     OsStr::new(value).encode_wide().chain(once(0)).collect()
 }
 
+/// Load a cursor from a .cur or .ani file.
+///
+/// `size` is the desired width and height in pixels; for files that contain
+/// several images, Windows picks the one closest to that size and scales it if needed.
 #[cfg(windows)]
-pub fn get_cursor(path: &str) -> CursorHandle {
+pub fn get_cursor(path: &str, size: u32) -> CursorHandle {
     use winapi::um::winuser::{
         LoadImageW, IMAGE_CURSOR, LR_DEFAULTCOLOR, LR_LOADFROMFILE,
     };
@@ -28,8 +152,8 @@ pub fn get_cursor(path: &str) -> CursorHandle {
             null_mut(),
             wide.as_ptr(),
             IMAGE_CURSOR,
-            0,
-            0,
+            size as i32,
+            size as i32,
             LR_DEFAULTCOLOR | LR_LOADFROMFILE,
         ) as HCURSOR
     };
@@ -37,34 +161,100 @@ pub fn get_cursor(path: &str) -> CursorHandle {
     CursorHandle(c)
 }
 
-/// Set all system cursors to a specific cursor.
+/// Free a cursor handle that was created by `get_cursor`.
+/// See: https://docs.microsoft.com/en-us/windows/desktop/api/winuser/nf-winuser-destroycursor
+#[cfg(windows)]
+pub fn destroy_cursor(cursor: &CursorHandle) {
+    use winapi::um::winuser::DestroyCursor;
+
+    unsafe { DestroyCursor(cursor.0) };
+}
+
+/// Attach to the console of the process that started us (e.g. a command prompt), if there is one.
+///
+/// See: https://docs.microsoft.com/en-us/windows/console/attachconsole
+#[cfg(windows)]
+pub fn attach_console() {
+    use winapi::um::wincon::{AttachConsole, ATTACH_PARENT_PROCESS};
+
+    unsafe { AttachConsole(ATTACH_PARENT_PROCESS) };
+}
+
+/// Opt in to per-monitor (v2) DPI awareness, so that positions and sizes
+/// are reported in physical pixels and Windows doesn't bitmap-stretch our cursors.
+///
+/// See: https://docs.microsoft.com/en-us/windows/desktop/hidpi/dpi-awareness-context
+#[cfg(windows)]
+pub fn enable_dpi_awareness() {
+    use winapi::shared::windef::DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2;
+    use winapi::um::winuser::SetProcessDpiAwarenessContext;
+
+    unsafe { SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2) };
+}
+
+/// Get the size, in pixels, that Windows uses for cursors at the given DPI.
+#[cfg(windows)]
+pub fn cursor_size_for_dpi(dpi: u32) -> u32 {
+    use winapi::um::winuser::{GetSystemMetricsForDpi, SM_CXCURSOR};
+
+    let size = unsafe { GetSystemMetricsForDpi(SM_CXCURSOR, dpi) };
+
+    // Fall back to scaling the 96 DPI default of 32 pixels if the call failed.
+    if size > 0 {
+        size as u32
+    } else {
+        32 * dpi / 96
+    }
+}
+
+/// Get the modifier keys and mouse buttons that are held down right now.
+#[cfg(windows)]
+pub fn held_keys() -> Held {
+    use winapi::um::winuser::{
+        GetAsyncKeyState, GetSystemMetrics, SM_SWAPBUTTON, VK_CONTROL, VK_LBUTTON, VK_LWIN,
+        VK_MBUTTON, VK_MENU, VK_RBUTTON, VK_RWIN, VK_SHIFT, VK_XBUTTON1, VK_XBUTTON2,
+    };
+
+    // The most significant bit is set while the key is down.
+    let down = |key: i32| unsafe { GetAsyncKeyState(key) } < 0;
+
+    // The key state is of the physical buttons, so they're swapped back if the user swapped them.
+    Held::from_state(
+        [
+            down(VK_SHIFT),
+            down(VK_CONTROL),
+            down(VK_MENU),
+            down(VK_LWIN) || down(VK_RWIN),
+        ],
+        [
+            down(VK_LBUTTON),
+            down(VK_RBUTTON),
+            down(VK_MBUTTON),
+            down(VK_XBUTTON1),
+            down(VK_XBUTTON2),
+        ],
+        unsafe { GetSystemMetrics(SM_SWAPBUTTON) } != 0,
+    )
+}
+
+/// Set the system cursor for every role, using the handle given for that role in `roles`
+/// or `cursor` for roles that aren't listed.
 ///
 /// See: https://stackoverflow.com/a/55098397/451726
 #[cfg(windows)]
-pub fn set_system_cursor(cursor: &CursorHandle) {
+pub fn set_system_cursor(cursor: &CursorHandle, roles: &[(CursorRole, CursorHandle)]) {
     use winapi::um::winuser::SetSystemCursor;
 
     // See: https://docs.microsoft.com/en-us/windows/desktop/api/winuser/nf-winuser-setsystemcursor
-    let cursor_ids: Vec<DWORD> = vec![
-        32650, // OCR_APPSTARTING
-        32512, // OCR_NORMAL
-        32515, // OCR_CROSS
-        32649, // OCR_HAND
-        32651, // OCR_HELP
-        32513, // OCR_IBEAM
-        32648, // OCR_NO
-        32646, // OCR_SIZEALL
-        32643, // OCR_SIZENESW
-        32645, // OCR_SIZENS
-        32642, // OCR_SIZENWSE
-        32644, // OCR_SIZEWE
-        32516, // OCR_UP
-        32514, // OCR_WAIT
-    ];
-
-    for cursor_id in cursor_ids {
-        let copied = copy_cursor(cursor);
-        unsafe { SetSystemCursor(copied.0, cursor_id) };
+    for role in CursorRole::ALL.iter() {
+        let handle = roles
+            .iter()
+            .find(|(r, _)| r == role)
+            .map_or(cursor, |(_, handle)| handle);
+
+        // SetSystemCursor takes ownership of (and destroys) the handle, so give it a copy.
+        let copied = copy_cursor(handle);
+        unsafe { SetSystemCursor(copied.0, role.system_id() as DWORD) };
     }
 }
 
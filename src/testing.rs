@@ -0,0 +1,130 @@
+//! Helpers for the tests: fixtures and golden files, which are kept in `testdata/`.
+//!
+//! A golden file holds what a test expects to produce. Run the tests with `UPDATE_GOLDEN=1` to write the golden
+//! files from what the code produces now, and look over the changes before committing them.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+
+use crate::backend::{Observation, Pointer, Window};
+use crate::condition::Held;
+use crate::cur::CursorFile;
+
+/// The path of `name` in `testdata/`.
+pub fn path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("testdata")
+        .join(name)
+}
+
+/// Read the file `name` from `testdata/`.
+pub fn read(name: &str) -> Vec<u8> {
+    fs::read(path(name)).unwrap_or_else(|e| {
+        panic!(
+            "Failed to read testdata/{} ({}). Run the tests with UPDATE_GOLDEN=1 to write golden files.",
+            name, e
+        )
+    })
+}
+
+fn updating() -> bool {
+    env::var_os("UPDATE_GOLDEN").is_some()
+}
+
+fn update(name: &str, bytes: &[u8]) {
+    let path = path(name);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).unwrap();
+    }
+    fs::write(path, bytes).unwrap();
+}
+
+/// Check that `actual` is the same as the golden text file `name`.
+pub fn check_text(name: &str, actual: &str) {
+    if updating() {
+        return update(name, actual.as_bytes());
+    }
+
+    let expected = String::from_utf8(read(name)).unwrap();
+    assert!(
+        expected == actual,
+        "The text differs from testdata/{}. Run the tests with UPDATE_GOLDEN=1 to update it.",
+        name
+    );
+}
+
+/// Check that the images of `actual` have the same sizes, hotspots, and pixels as those of the golden .cur
+/// file `name`.
+pub fn check_cursor(name: &str, actual: &CursorFile) {
+    if updating() {
+        return update(name, &actual.encode().unwrap());
+    }
+
+    let expected = CursorFile::decode(&read(name)).unwrap();
+    assert_eq!(
+        expected.images.len(),
+        actual.images.len(),
+        "The number of images differs from testdata/{}.",
+        name
+    );
+
+    for (n, (expected, actual)) in expected.images.iter().zip(&actual.images).enumerate() {
+        let size = |image: &crate::image::Image| (image.width, image.height);
+        assert_eq!(
+            size(&expected.image),
+            size(&actual.image),
+            "The size of image {} differs from testdata/{}.",
+            n,
+            name
+        );
+        assert_eq!(
+            expected.hotspot, actual.hotspot,
+            "The hotspot of image {} differs from testdata/{}.",
+            n, name
+        );
+
+        for y in 0..expected.image.height {
+            for x in 0..expected.image.width {
+                assert_eq!(
+                    expected.image.pixel(x, y),
+                    actual.image.pixel(x, y),
+                    "Pixel ({}, {}) of image {} differs from testdata/{}.",
+                    x,
+                    y,
+                    n,
+                    name
+                );
+            }
+        }
+    }
+}
+
+/// The time `milliseconds` after noon on 1 May 2024.
+pub fn time(milliseconds: i64) -> NaiveDateTime {
+    let noon = NaiveDate::from_ymd_opt(2024, 5, 1)
+        .unwrap()
+        .and_hms_opt(12, 0, 0)
+        .unwrap();
+    noon + Duration::milliseconds(milliseconds)
+}
+
+/// What the backend sees `milliseconds` after noon, with the pointer at (640, 400) over a window of `executable`,
+/// or over no window.
+pub fn over(milliseconds: i64, executable: Option<&str>) -> Observation {
+    Observation {
+        time: time(milliseconds),
+        pointer: Some(Pointer {
+            position: (640, 400),
+            cursor_size: 32,
+        }),
+        window: executable.map(|executable| Window {
+            id: 1,
+            executable: Some(executable.to_string()),
+            error: None,
+        }),
+        held: Held::default(),
+    }
+}
@@ -0,0 +1,410 @@
+//! Recording a session of the cursor changer, and replaying it without Windows.
+//!
+//! A trace is a file with one JSON object per line, each one an event: what the backend saw on a tick,
+//! an instruction from a hotkey or another process, or a cursor being set or the system cursors restored.
+//!
+//! ```text
+//! {"event":"tick","time":"2024-05-01T12:00:00.005","pointer":{"position":[640,400],"cursor_size":32},"window":{"id":1234,"executable":"C:\\Windows\\notepad.exe"}}
+//! {"event":"set_cursor","cursor":"big"}
+//! {"event":"control","control":"profile work"}
+//! ```
+//!
+//! Replaying a trace feeds its ticks and instructions to a cursor changer with a `FakeBackend`, and checks that
+//! it sets and restores the cursors the same way, at the same times, as when the trace was recorded.
+
+use std::collections::VecDeque;
+use std::fs;
+#[cfg(any(windows, test))]
+use std::fs::File;
+#[cfg(any(windows, test))]
+use std::io::{self, LineWriter, Write};
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+
+use crate::backend::{Backend, Observation};
+use crate::condition::Held;
+use crate::config::Config;
+use crate::control::Control;
+use crate::error::{Error, WithPath};
+use crate::trail::Drawing;
+use crate::{Cursor, CursorChanger, CursorFiles, Result};
+
+/// Something that happened while a trace was recorded.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// The backend was asked what's under the pointer.
+    Tick(Observation),
+
+    /// An instruction was carried out, written as it's sent between processes.
+    Control { control: String },
+
+    /// A cursor was used in place of the system cursors.
+    SetCursor { cursor: String },
+
+    /// The system cursors were restored.
+    Restore,
+}
+
+impl Event {
+    fn describe(&self) -> String {
+        match self {
+            Event::Tick(_) => "a tick".to_string(),
+            Event::Control { control } => format!("the instruction \"{}\"", control),
+            Event::SetCursor { cursor } => format!("setting the cursor \"{}\"", cursor),
+            Event::Restore => "restoring the system cursors".to_string(),
+        }
+    }
+}
+
+/// Read the events from a trace file.
+pub fn load(path: &Path) -> Result<Vec<Event>> {
+    let text = fs::read_to_string(path).with_path(path)?;
+
+    let mut events = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let event = serde_json::from_str(line).map_err(|e| Error::InvalidTrace {
+            line: number + 1,
+            reason: e.to_string(),
+        })?;
+        events.push(event);
+    }
+
+    Ok(events)
+}
+
+/// A backend that writes everything that passes through it to a trace file.
+#[cfg(any(windows, test))]
+pub struct Recording<B> {
+    backend: B,
+
+    /// The trace file, until writing to it fails.
+    file: Option<LineWriter<File>>,
+}
+
+#[cfg(any(windows, test))]
+impl<B: Backend> Recording<B> {
+    /// Start recording a new trace (replacing any file that's already at `path`) of what `backend` does.
+    pub fn create(path: &Path, backend: B) -> io::Result<Recording<B>> {
+        // Lines are written as they're finished, so the trace is complete even if the app crashes.
+        let file = LineWriter::new(File::create(path)?);
+
+        Ok(Recording {
+            backend,
+            file: Some(file),
+        })
+    }
+
+    fn record(&mut self, event: &Event) {
+        if let Some(file) = &mut self.file {
+            let written = serde_json::to_string(event)
+                .map_err(io::Error::from)
+                .and_then(|line| writeln!(file, "{}", line));
+
+            // Stop recording rather than leave a trace with events missing from it.
+            if let Err(e) = written {
+                error!("Failed to write to the trace file, so recording has stopped: {}", e);
+                self.file = None;
+            }
+        }
+    }
+}
+
+#[cfg(any(windows, test))]
+impl<B: Backend> Backend for Recording<B> {
+    type Handle = B::Handle;
+
+    fn observe(&mut self) -> Observation {
+        let observation = self.backend.observe();
+        self.record(&Event::Tick(observation.clone()));
+        observation
+    }
+
+    fn load(&mut self, files: &CursorFiles, path: &str, size: u32) -> Result<B::Handle> {
+        self.backend.load(files, path, size)
+    }
+
+    fn destroy(&mut self, handle: &B::Handle) {
+        self.backend.destroy(handle)
+    }
+
+    fn set_cursor(&mut self, cursor: &Cursor<B::Handle>) {
+        self.backend.set_cursor(cursor);
+        self.record(&Event::SetCursor {
+            cursor: cursor.name().to_string(),
+        });
+    }
+
+    fn restore_cursors(&mut self) {
+        self.backend.restore_cursors();
+        self.record(&Event::Restore);
+    }
+
+    fn show_trail(&mut self, trail: Option<&Drawing>) {
+        self.backend.show_trail(trail)
+    }
+
+    fn control(&mut self, control: &Control) {
+        self.backend.control(control);
+        self.record(&Event::Control {
+            control: control.encode(),
+        });
+    }
+}
+
+/// A cursor "loaded" by the `FakeBackend`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FakeCursor {
+    pub path: String,
+    pub size: u32,
+}
+
+/// A backend that reports what it's been given to observe, and remembers the cursors it's asked to set,
+/// without touching the system.
+#[derive(Debug, Default)]
+pub struct FakeBackend {
+    /// What the next ticks will see, in order. Once these run out, the last one is seen again.
+    pub observations: VecDeque<Observation>,
+
+    /// Every cursor set and restore, with the time of the tick it happened on.
+    pub calls: Vec<(Option<NaiveDateTime>, Event)>,
+
+    /// The trail that's shown, if any.
+    pub trail: Option<Drawing>,
+
+    /// The last observation that was seen.
+    last: Option<Observation>,
+}
+
+impl FakeBackend {
+    /// Make a backend that will see `observations`, one on each tick.
+    pub fn new(observations: impl IntoIterator<Item = Observation>) -> FakeBackend {
+        FakeBackend {
+            observations: observations.into_iter().collect(),
+            ..FakeBackend::default()
+        }
+    }
+
+    fn time(&self) -> Option<NaiveDateTime> {
+        self.last.as_ref().map(|observation| observation.time)
+    }
+}
+
+impl Backend for FakeBackend {
+    type Handle = FakeCursor;
+
+    fn observe(&mut self) -> Observation {
+        if let Some(observation) = self.observations.pop_front() {
+            self.last = Some(observation);
+        }
+
+        self.last.clone().unwrap_or_else(|| Observation {
+            time: NaiveDateTime::default(),
+            pointer: None,
+            window: None,
+            held: Held::default(),
+        })
+    }
+
+    fn load(&mut self, _files: &CursorFiles, path: &str, size: u32) -> Result<FakeCursor> {
+        Ok(FakeCursor {
+            path: path.to_string(),
+            size,
+        })
+    }
+
+    fn destroy(&mut self, _handle: &FakeCursor) {}
+
+    fn set_cursor(&mut self, cursor: &Cursor<FakeCursor>) {
+        let event = Event::SetCursor {
+            cursor: cursor.name().to_string(),
+        };
+        self.calls.push((self.time(), event));
+    }
+
+    fn restore_cursors(&mut self) {
+        self.calls.push((self.time(), Event::Restore));
+    }
+
+    fn show_trail(&mut self, trail: Option<&Drawing>) {
+        self.trail = trail.cloned();
+    }
+}
+
+/// What happened when a trace was replayed.
+pub struct Replay {
+    /// How many ticks were replayed.
+    pub ticks: usize,
+
+    /// The cursor sets and restores in the trace, with the time of the tick they happened on.
+    pub expected: Vec<(Option<NaiveDateTime>, Event)>,
+
+    /// The cursor sets and restores made while replaying.
+    pub actual: Vec<(Option<NaiveDateTime>, Event)>,
+}
+
+impl Replay {
+    /// Describe the first place where the replay differs from the trace, if it does.
+    pub fn first_difference(&self) -> Option<String> {
+        let describe = |call: Option<&(Option<NaiveDateTime>, Event)>| match call {
+            Some((Some(time), event)) => format!("{} at {}", event.describe(), time),
+            Some((None, event)) => event.describe(),
+            None => "nothing more".to_string(),
+        };
+
+        let count = self.expected.len().max(self.actual.len());
+        (0..count)
+            .find(|&n| self.expected.get(n) != self.actual.get(n))
+            .map(|n| {
+                format!(
+                    "change {}: the trace has {}, but the replay has {}",
+                    n + 1,
+                    describe(self.expected.get(n)),
+                    describe(self.actual.get(n))
+                )
+            })
+    }
+}
+
+/// Replay a trace through a cursor changer that's set up as `config` says.
+///
+/// Usage statistics and the history aren't saved while replaying.
+pub fn replay(mut config: Config, events: &[Event]) -> Result<Replay> {
+    config.stats = None;
+    config.history = None;
+
+    // The cursor changer looks at the pointer once when it starts, to find the size to load cursors at.
+    let (first, events) = match events.split_first() {
+        Some((Event::Tick(observation), events)) => (observation.clone(), events),
+        _ => {
+            return Err(Error::InvalidTrace {
+                line: 1,
+                reason: "a trace starts with a tick".to_string(),
+            })
+        }
+    };
+
+    let mut changer = CursorChanger::from_config(config, FakeBackend::new(vec![first]))?;
+
+    let mut ticks = 0;
+    let mut time = None;
+    let mut expected = Vec::new();
+    for (number, event) in events.iter().enumerate() {
+        match event {
+            Event::Tick(observation) => {
+                time = Some(observation.time);
+                ticks += 1;
+                changer.backend.observations.push_back(observation.clone());
+                changer.tick();
+            }
+            Event::Control { control } => match Control::decode(control) {
+                // Saving happens outside of the replay, so it's left out.
+                Some(Control::SaveStats) | Some(Control::SaveHistory) => {}
+                Some(control) => changer.handle_control(control),
+                None => {
+                    return Err(Error::InvalidTrace {
+                        line: number + 2,
+                        reason: format!("unknown instruction \"{}\"", control),
+                    })
+                }
+            },
+            Event::SetCursor { .. } | Event::Restore => expected.push((time, event.clone())),
+        }
+    }
+
+    Ok(Replay {
+        ticks,
+        expected,
+        actual: changer.backend.calls,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::process;
+
+    use super::*;
+    use crate::testing;
+
+    fn config() -> Config {
+        Config::from_file(testing::path("replay/cursor.toml")).unwrap()
+    }
+
+    fn trace() -> Vec<Event> {
+        load(&testing::path("replay/trace.jsonl")).unwrap()
+    }
+
+    #[test]
+    fn replays_the_checked_in_trace() {
+        let replay = replay(config(), &trace()).unwrap();
+
+        assert_eq!(replay.ticks, 7);
+        assert_eq!(replay.first_difference(), None);
+
+        let calls: Vec<String> = replay
+            .actual
+            .iter()
+            .map(|(time, event)| format!("{} {}", time.unwrap().format("%S%.3f"), event.describe()))
+            .collect();
+        assert_eq!(
+            calls,
+            [
+                "00.100 setting the cursor \"arrow\"",
+                "00.300 setting the cursor \"hand\"",
+                "00.400 restoring the system cursors",
+                "00.500 setting the cursor \"hand\"",
+                "00.700 setting the cursor \"arrow\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_where_a_replay_differs() {
+        let mut events = trace();
+        let restore = events
+            .iter()
+            .position(|event| *event == Event::Restore)
+            .unwrap();
+        events.remove(restore);
+
+        let replay = replay(config(), &events).unwrap();
+        assert_eq!(
+            replay.first_difference().unwrap(),
+            "change 3: the trace has setting the cursor \"hand\" at 2024-05-01 12:00:00.500, \
+             but the replay has restoring the system cursors at 2024-05-01 12:00:00.400"
+        );
+    }
+
+    #[test]
+    fn records_what_it_replays() {
+        let events = trace();
+        let observations = events.iter().filter_map(|event| match event {
+            Event::Tick(observation) => Some(observation.clone()),
+            _ => None,
+        });
+
+        let path = env::temp_dir().join(format!("cursor-changer-trace-{}.jsonl", process::id()));
+        let backend = Recording::create(&path, FakeBackend::new(observations)).unwrap();
+        let mut changer = CursorChanger::from_config(config(), backend).unwrap();
+        for event in &events[1..] {
+            match event {
+                Event::Tick(_) => changer.tick(),
+                Event::Control { control } => {
+                    changer.handle_control(Control::decode(control).unwrap())
+                }
+                Event::SetCursor { .. } | Event::Restore => {}
+            }
+        }
+        drop(changer);
+
+        let recorded = load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(recorded, events);
+    }
+}
@@ -0,0 +1,258 @@
+//! A trail of fading echoes of the cursor behind the moving pointer, which makes it easier to find on big screens.
+//!
+//! The trail is only shown over applications that ask for it. Each tick, the pointer's position is added to its
+//! `Motion`, and the echoes are placed where the pointer was a little while ago. They're drawn into one image here,
+//! which the backend shows on top of everything else (on Windows, in a click-through layered window).
+
+use std::collections::VecDeque;
+
+use chrono::{Duration, NaiveDateTime};
+use schemars::JsonSchema;
+
+use crate::cur::CursorImage;
+use crate::image::Image;
+use crate::switching;
+
+/// How often the trail is drawn at most, in milliseconds: about as often as the screen is redrawn.
+const FRAME_MILLIS: u64 = 15;
+
+/// Echoes closer than this many pixels to the pointer, or to the echo after them, are left out,
+/// so that a pointer that's barely moving doesn't leave a smudge behind it.
+const MIN_DISTANCE: i32 = 4;
+
+/// Echoes further than this many pixels from the pointer are left out, so that the image the trail is drawn in
+/// stays small, even when the pointer is flung across several monitors.
+const MAX_DISTANCE: i32 = 512;
+
+/// The `[trail]` table of the configuration, which sets how the trail looks.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, JsonSchema)]
+pub struct TrailConfig {
+    /// How many echoes follow the pointer. Defaults to 6.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub echoes: Option<u32>,
+
+    /// How far behind each other the echoes are, in milliseconds. Defaults to 30.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spacing: Option<u64>,
+
+    /// How opaque the nearest echo is, from 0 to 1. The others fade out from there. Defaults to 0.5.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opacity: Option<f32>,
+}
+
+impl TrailConfig {
+    fn count(&self) -> u32 {
+        self.echoes.unwrap_or(6)
+    }
+
+    fn spacing(&self) -> Duration {
+        switching::milliseconds(self.spacing.unwrap_or(30))
+    }
+
+    /// How far back the pointer's motion needs to be remembered.
+    pub fn span(&self) -> Duration {
+        self.spacing() * self.count() as i32
+    }
+
+    /// Place the echoes behind the pointer at `now`, furthest (and faintest) first.
+    /// The trail ends before the first echo that's too far from the pointer.
+    pub fn echoes(&self, motion: &Motion, now: NaiveDateTime) -> Vec<Echo> {
+        let count = self.count();
+        let opacity = self.opacity.unwrap_or(0.5).clamp(0.0, 1.0);
+
+        let mut echoes: Vec<Echo> = Vec::new();
+        let pointer = match motion.position_at(now) {
+            Some(position) => position,
+            None => return echoes,
+        };
+        let mut after = pointer;
+        for n in 1..=count {
+            let position = match motion.position_at(now - self.spacing() * n as i32) {
+                Some(position) => position,
+                None => break,
+            };
+            if distance(position, pointer) > MAX_DISTANCE {
+                break;
+            }
+
+            if distance(position, after) >= MIN_DISTANCE {
+                echoes.push(Echo {
+                    position,
+                    opacity: opacity * (count + 1 - n) as f32 / count as f32,
+                });
+                after = position;
+            }
+        }
+
+        echoes.reverse();
+        echoes
+    }
+}
+
+/// How long to wait between drawing the trail and drawing it again.
+pub fn frame_time() -> Duration {
+    switching::milliseconds(FRAME_MILLIS)
+}
+
+/// The larger of the horizontal and vertical distances between two points.
+fn distance(a: (i32, i32), b: (i32, i32)) -> i32 {
+    (a.0 - b.0).abs().max((a.1 - b.1).abs())
+}
+
+/// Where the pointer has been recently.
+#[derive(Debug, Default)]
+pub struct Motion {
+    /// Positions, oldest first, with the time each one was seen.
+    samples: VecDeque<(NaiveDateTime, (i32, i32))>,
+}
+
+impl Motion {
+    /// Add the pointer's `position` at `time`, forgetting where it was more than `span` before.
+    pub fn record(&mut self, time: NaiveDateTime, position: (i32, i32), span: Duration) {
+        // If the clock went back, the old positions can't be placed in time any more.
+        if self.samples.back().is_some_and(|(last, _)| *last > time) {
+            self.samples.clear();
+        }
+        self.samples.push_back((time, position));
+
+        // Keep the newest sample from before the span, so positions just inside it can still be worked out.
+        while self.samples.len() > 1 && self.samples[1].0 <= time - span {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Work out where the pointer was at `time`, between the positions seen around it.
+    /// Returns `None` if that's from before the oldest position that's remembered.
+    pub fn position_at(&self, time: NaiveDateTime) -> Option<(i32, i32)> {
+        let after = self.samples.iter().position(|(seen, _)| *seen >= time);
+        let (before, after) = match after {
+            Some(0) => {
+                let (seen, position) = self.samples[0];
+                return if seen == time { Some(position) } else { None };
+            }
+            Some(after) => (self.samples[after - 1], self.samples[after]),
+            // The pointer hasn't been seen since, so it's still where it was last seen.
+            None => return self.samples.back().map(|(_, position)| *position),
+        };
+
+        let gap = (after.0 - before.0).num_microseconds().unwrap_or(i64::MAX) as f64;
+        let part = (time - before.0).num_microseconds().unwrap_or(0) as f64 / gap;
+        let between = |from: i32, to: i32| from + ((to - from) as f64 * part).round() as i32;
+        Some((
+            between((before.1).0, (after.1).0),
+            between((before.1).1, (after.1).1),
+        ))
+    }
+}
+
+/// One copy of the cursor in the trail.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Echo {
+    /// Where the pointer was, in screen coordinates.
+    pub position: (i32, i32),
+
+    /// From 0 (invisible) to 1.
+    pub opacity: f32,
+}
+
+/// A trail, drawn into one image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Drawing {
+    /// Where the image's top-left corner goes, in screen coordinates.
+    pub origin: (i32, i32),
+
+    pub image: Image,
+}
+
+/// Draw `echoes` of `cursor`, each with its hotspot on the echo's position.
+/// Returns `None` if there are no echoes.
+pub fn draw(echoes: &[Echo], cursor: &CursorImage) -> Option<Drawing> {
+    let corner = |echo: &Echo| {
+        (
+            echo.position.0 - cursor.hotspot.0 as i32,
+            echo.position.1 - cursor.hotspot.1 as i32,
+        )
+    };
+
+    let left = echoes.iter().map(|echo| corner(echo).0).min()?;
+    let top = echoes.iter().map(|echo| corner(echo).1).min()?;
+    let right = echoes.iter().map(|echo| corner(echo).0).max()? + cursor.image.width as i32;
+    let bottom = echoes.iter().map(|echo| corner(echo).1).max()? + cursor.image.height as i32;
+
+    let mut image = Image::new((right - left) as u32, (bottom - top) as u32);
+    for echo in echoes {
+        let (x, y) = corner(echo);
+        image.draw_faded(&cursor.image, x - left, y - top, echo.opacity);
+    }
+
+    Some(Drawing {
+        origin: (left, top),
+        image,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::time;
+
+    /// The pointer's motion, moving `speed` pixels to the right every 10 milliseconds from (0, 0), until `now`.
+    fn moving(speed: i32, now: i64) -> Motion {
+        let mut motion = Motion::default();
+        for tick in 0..=now / 10 {
+            let position = (speed * tick as i32, 0);
+            motion.record(time(tick * 10), position, Duration::seconds(1));
+        }
+        motion
+    }
+
+    fn positions(echoes: &[Echo]) -> Vec<(i32, i32)> {
+        echoes.iter().map(|echo| echo.position).collect()
+    }
+
+    #[test]
+    fn places_echoes_behind_the_pointer() {
+        let trail = TrailConfig {
+            echoes: Some(3),
+            spacing: Some(30),
+            opacity: Some(0.6),
+        };
+        let echoes = trail.echoes(&moving(5, 200), time(200));
+
+        assert_eq!(positions(&echoes), [(55, 0), (70, 0), (85, 0)]);
+        for (echo, opacity) in echoes.iter().zip([0.2, 0.4, 0.6]) {
+            assert!((echo.opacity - opacity).abs() < 1e-6, "{:?}", echoes);
+        }
+    }
+
+    #[test]
+    fn leaves_out_echoes_that_are_too_close() {
+        let trail = TrailConfig::default();
+
+        assert!(trail.echoes(&moving(0, 200), time(200)).is_empty());
+        assert_eq!(
+            positions(&trail.echoes(&moving(1, 200), time(200))),
+            [(2, 0), (8, 0), (14, 0)]
+        );
+    }
+
+    #[test]
+    fn doesnt_reach_too_far_behind_the_pointer() {
+        let trail = TrailConfig::default();
+        let echoes = trail.echoes(&moving(100, 200), time(200));
+
+        // The pointer is at 2000, so the echo at 1400 and those before it are left out.
+        assert_eq!(positions(&echoes), [(1700, 0)]);
+
+        let echoes = trail.echoes(&moving(40, 200), time(200));
+        assert_eq!(positions(&echoes), [(320, 0), (440, 0), (560, 0), (680, 0)]);
+
+        let cursor = CursorImage {
+            image: Image::new(32, 32),
+            hotspot: (0, 0),
+        };
+        let drawing = draw(&echoes, &cursor).unwrap();
+        assert_eq!(drawing.origin, (320, 0));
+        assert_eq!((drawing.image.width, drawing.image.height), (392, 32));
+    }
+}
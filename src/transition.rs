@@ -0,0 +1,346 @@
+//! Short animations shown when switching between cursors, instead of swapping one for the other instantly.
+//!
+//! A transition is generated as an animated cursor, which is shown for one loop of its animation before
+//! the new cursor takes its place. The frames are made here from the two cursors' images; restoring the
+//! system cursors transitions to (or from) a blank cursor, since their images aren't known.
+
+use schemars::JsonSchema;
+
+use crate::ani::AnimatedCursor;
+use crate::cur::{self, CursorImage};
+use crate::cursor_data::CursorData;
+use crate::frames::{self, placed, Generator};
+use crate::image::Image;
+use crate::Result;
+
+/// How to move from one cursor to the next.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, JsonSchema)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Transition {
+    /// Blend the previous cursor into the new one, over `duration` milliseconds.
+    Fade {
+        #[serde(default = "default_duration")]
+        duration: u64,
+    },
+
+    /// Show the new cursor `scale` times larger, and shrink it to its usual size over `duration` milliseconds.
+    /// When restoring the system cursors, the previous cursor shrinks away instead.
+    /// The cursor never starts out larger than the largest cursor Windows can show (256 pixels).
+    Pop {
+        #[serde(default = "default_duration")]
+        duration: u64,
+        #[serde(default = "default_scale")]
+        scale: f32,
+    },
+
+    /// Play an animation (an .ani file, or a .gif or animated .png image) once, before the new cursor.
+    Animation {
+        path: String,
+
+        /// The "hot" pixel, for animations made from images. Defaults to the top-left corner.
+        hotspot: Option<[u32; 2]>,
+    },
+}
+
+fn default_duration() -> u64 {
+    150
+}
+
+fn default_scale() -> f32 {
+    1.5
+}
+
+impl Transition {
+    /// Get a mutable reference to the path of any file this transition reads, so it can be resolved.
+    pub fn path_mut(&mut self) -> Option<&mut String> {
+        match self {
+            Transition::Animation { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Generate the animation from the cursor image `from` to `to`, at `size` pixels.
+    /// Either of them is `None` for the system cursors.
+    pub fn animate(
+        &self,
+        from: Option<&CursorImage>,
+        to: Option<&CursorImage>,
+        size: u32,
+    ) -> Result<AnimatedCursor> {
+        let (duration, frame) = match self {
+            Transition::Fade { duration } => (*duration, Frame::Fade),
+            Transition::Pop { duration, scale } => (*duration, Frame::Pop(pop_scale(*scale, to))),
+            Transition::Animation { path, hotspot } => {
+                let hotspot = hotspot.map_or((0, 0), |[x, y]| (x, y));
+                return Ok(match CursorData::open(path, hotspot)?.resampled(&[size]) {
+                    CursorData::Animated(animation) => animation,
+                    CursorData::Static(cursor) => AnimatedCursor::new(vec![cursor], &[], &[])?,
+                });
+            }
+        };
+
+        let blend = Blend {
+            frame,
+            from,
+            to,
+            size,
+        };
+        frames::generate(&blend, duration)
+    }
+}
+
+/// Limit how much larger than `to` a pop starts out, so its frames fit in a cursor.
+fn pop_scale(scale: f32, to: Option<&CursorImage>) -> f32 {
+    match to {
+        Some(to) => {
+            let largest = to.image.width.max(to.image.height).max(1);
+            scale.min(cur::MAX_SIZE as f32 / largest as f32)
+        }
+        None => scale,
+    }
+}
+
+/// The kinds of frame that are drawn, rather than read from a file.
+#[derive(Clone, Copy)]
+enum Frame {
+    Fade,
+    Pop(f32),
+}
+
+/// A generated transition from one cursor image to another.
+struct Blend<'a> {
+    frame: Frame,
+    from: Option<&'a CursorImage>,
+    to: Option<&'a CursorImage>,
+
+    /// The size of the canvas if there are no images to size it by.
+    size: u32,
+}
+
+impl Generator for Blend<'_> {
+    /// Both images are lined up by their hotspots, on a canvas the size of the new cursor (or the old one),
+    /// which is made larger if the cursor grows.
+    fn canvas(&self) -> CursorImage {
+        let grow = match (self.frame, self.to) {
+            (Frame::Pop(scale), Some(_)) => scale.max(1.0),
+            _ => 1.0,
+        };
+        let grown = |value: u32| ((value as f32 * grow).ceil() as u32).min(cur::MAX_SIZE);
+
+        self.to.or(self.from).map_or(
+            CursorImage {
+                image: Image::new(self.size, self.size),
+                hotspot: (0, 0),
+            },
+            |cursor| CursorImage {
+                image: Image::new(grown(cursor.image.width), grown(cursor.image.height)),
+                hotspot: (grown(cursor.hotspot.0), grown(cursor.hotspot.1)),
+            },
+        )
+    }
+
+    fn draw(&self, canvas: &CursorImage, progress: f32) -> Image {
+        let (from, to) = (self.from, self.to);
+        match self.frame {
+            Frame::Fade => {
+                let from = placed(canvas, from, 1.0);
+                let to = placed(canvas, to, 1.0);
+                mix(&from, &to, progress)
+            }
+            Frame::Pop(scale) => match to {
+                // Ease out, so the cursor settles gently into its usual size.
+                Some(to) => {
+                    let eased = 1.0 - (1.0 - progress) * (1.0 - progress);
+                    placed(canvas, Some(to), scale + (1.0 - scale) * eased)
+                }
+                None => placed(canvas, from, 1.0 - progress),
+            },
+        }
+    }
+}
+
+/// Blend two images of the same size: `amount` of the way from `from` to `to`.
+fn mix(from: &Image, to: &Image, amount: f32) -> Image {
+    let mut result = Image::new(from.width, from.height);
+
+    for ((pixel, a), b) in result
+        .pixels
+        .chunks_mut(4)
+        .zip(from.pixels.chunks(4))
+        .zip(to.pixels.chunks(4))
+    {
+        // Mix premultiplied colors, so a pixel fading in from transparent doesn't start out dark.
+        let alpha = |p: &[u8]| f32::from(p[3]) / 255.0;
+        let (a_weight, b_weight) = (alpha(a) * (1.0 - amount), alpha(b) * amount);
+        let total = a_weight + b_weight;
+        if total <= 0.0 {
+            continue;
+        }
+
+        for c in 0..3 {
+            let mixed = (f32::from(a[c]) * a_weight + f32::from(b[c]) * b_weight) / total;
+            pixel[c] = mixed.round().clamp(0.0, 255.0) as u8;
+        }
+        pixel[3] = (total * 255.0).round().clamp(0.0, 255.0) as u8;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ani;
+    use crate::config::Config;
+    use crate::cur::CursorFile;
+    use crate::testing::{self, over};
+    use crate::trace::{self, Event};
+
+    /// The 32-pixel arrow, at `size` pixels.
+    fn arrow(size: u32) -> CursorImage {
+        let cursor = CursorFile::decode(&testing::read("resample/arrow.cur")).unwrap();
+        cursor.images[0].scale_to(size)
+    }
+
+    fn sizes(animation: &AnimatedCursor) -> Vec<(u32, u32, (u32, u32))> {
+        animation
+            .frames
+            .iter()
+            .flat_map(|frame| &frame.images)
+            .map(|image| (image.image.width, image.image.height, image.hotspot))
+            .collect()
+    }
+
+    fn rates(animation: &AnimatedCursor) -> Vec<u32> {
+        animation.steps.iter().map(|step| step.jiffies).collect()
+    }
+
+    /// How many pixels of `image` aren't fully transparent.
+    fn coverage(image: &Image) -> usize {
+        image.pixels.chunks(4).filter(|pixel| pixel[3] > 0).count()
+    }
+
+    #[test]
+    fn fades_on_a_canvas_the_size_of_the_new_cursor() {
+        let (from, to) = (arrow(32), arrow(48));
+        let fade = Transition::Fade { duration: 150 };
+        let animation = fade.animate(Some(&from), Some(&to), 32).unwrap();
+
+        assert_eq!(sizes(&animation), vec![(48, 48, to.hotspot); 4]);
+        assert_eq!(rates(&animation), [ani::jiffies_from_millis(37); 4]);
+    }
+
+    #[test]
+    fn makes_a_frame_every_two_jiffies_up_to_a_limit() {
+        let cursor = arrow(32);
+        let frames = |duration: u64| {
+            let fade = Transition::Fade { duration };
+            let animation = fade.animate(None, Some(&cursor), 32).unwrap();
+            (animation.frames.len(), animation.duration())
+        };
+
+        assert_eq!(frames(0), (1, 1));
+        assert_eq!(frames(33), (1, 2));
+        assert_eq!(frames(100), (3, 6));
+        assert_eq!(frames(10_000), (15, 15 * 40));
+    }
+
+    #[test]
+    fn pops_from_a_larger_size() {
+        let to = arrow(32);
+        let pop = Transition::Pop {
+            duration: 150,
+            scale: 1.5,
+        };
+        let animation = pop.animate(None, Some(&to), 32).unwrap();
+
+        assert_eq!(sizes(&animation), vec![(48, 48, (2, 2)); 4]);
+        let covered: Vec<usize> = animation
+            .frames
+            .iter()
+            .map(|frame| coverage(&frame.images[0].image))
+            .collect();
+        assert!(
+            covered.windows(2).all(|pair| pair[0] > pair[1]),
+            "{:?}",
+            covered
+        );
+    }
+
+    #[test]
+    fn pops_of_large_cursors_fit_in_a_cursor() {
+        let to = arrow(256);
+        let pop = Transition::Pop {
+            duration: 150,
+            scale: 1.5,
+        };
+        let animation = pop.animate(None, Some(&to), 256).unwrap();
+
+        assert_eq!(sizes(&animation), vec![(256, 256, to.hotspot); 4]);
+        assert!(animation.encode().is_ok());
+    }
+
+    #[test]
+    fn restoring_shrinks_the_previous_cursor_away() {
+        let from = arrow(32);
+        let pop = Transition::Pop {
+            duration: 100,
+            scale: 1.5,
+        };
+        let animation = pop.animate(Some(&from), None, 32).unwrap();
+
+        assert_eq!(sizes(&animation), vec![(32, 32, from.hotspot); 3]);
+        let last = &animation.frames[2].images[0].image;
+        assert!(coverage(last) < coverage(&from.image) / 4);
+    }
+
+    #[test]
+    fn transitions_between_system_cursors_are_blank() {
+        let fade = Transition::Fade { duration: 100 };
+        let animation = fade.animate(None, None, 24).unwrap();
+
+        assert_eq!(sizes(&animation), vec![(24, 24, (0, 0)); 3]);
+        assert!(animation
+            .frames
+            .iter()
+            .all(|frame| coverage(&frame.images[0].image) == 0));
+    }
+
+    #[test]
+    fn animations_are_read_at_the_cursor_size() {
+        let animation = Transition::Animation {
+            path: testing::path("resample/ring.png").display().to_string(),
+            hotspot: Some([32, 32]),
+        };
+        let animation = animation.animate(None, None, 48).unwrap();
+
+        assert_eq!(sizes(&animation), [(48, 48, (24, 24))]);
+    }
+
+    #[test]
+    fn shows_a_pop_of_a_large_cursor() {
+        let mut config = Config::from_file(testing::path("replay/cursor.toml")).unwrap();
+        config.application[1].transition = Some(Transition::Pop {
+            duration: 100,
+            scale: 2.0,
+        });
+
+        let notepad = Some(r"C:\Windows\notepad.exe");
+        let mut ticks = vec![over(0, None), over(10, notepad), over(210, notepad)];
+        for tick in &mut ticks {
+            tick.pointer.as_mut().unwrap().cursor_size = 256;
+        }
+        let events: Vec<Event> = ticks.into_iter().map(Event::Tick).collect();
+
+        let replay = trace::replay(config, &events).unwrap();
+        let names: Vec<&str> = replay
+            .actual
+            .iter()
+            .map(|(_, event)| match event {
+                Event::SetCursor { cursor } => cursor.as_str(),
+                _ => "restore",
+            })
+            .collect();
+        assert_eq!(names, ["transition to arrow", "arrow"]);
+    }
+}
@@ -9,20 +9,41 @@ use std::iter::once;
 use std::mem;
 use std::os::windows::ffi::OsStrExt;
 use std::ptr::null_mut;
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use std::time::Duration;
 
 
+use winapi::shared::minwindef::{DWORD, LPARAM, LRESULT, UINT, WPARAM};
 use winapi::shared::windef::HWND;
 use winapi::um::libloaderapi::GetModuleHandleW;
 use winapi::um::winuser::{
-    CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, RegisterClassW,
-    TranslateMessage,
+    CreateWindowExW, DefWindowProcW, DispatchMessageW, FindWindowW, GetMessageW, RegisterClassW,
+    RegisterHotKey, SendMessageW, TranslateMessage,
 
 };
 use winapi::um::winuser::{
-    CS_HREDRAW, CS_OWNDC, CS_VREDRAW, CW_USEDEFAULT, MSG, WNDCLASSW, WS_OVERLAPPEDWINDOW,
-    WS_VISIBLE,
+    COPYDATASTRUCT, CS_HREDRAW, CS_OWNDC, CS_VREDRAW, CW_USEDEFAULT, MOD_NOREPEAT, MSG,
+    WM_COPYDATA, WM_HOTKEY, WNDCLASSW, WS_OVERLAPPEDWINDOW, WS_VISIBLE,
 };
 
+use crate::control::{Control, Request};
+use crate::error::Error;
+use crate::hotkey::Hotkey;
+
+/// The window's class name, which other processes use to find the running cursor changer.
+const CLASS_NAME: &str = "cursor_changer";
+
+/// Marks `WM_COPYDATA` messages that hold an encoded `Control`.
+const CONTROL_MESSAGE: usize = 0x4343_5743;
+
+/// How long another process's instruction may take to carry out before it's answered anyway.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where instructions received by the window are sent.
+/// The window procedure is called by Windows, so it can't be given this any other way.
+static CONTROLS: Mutex<Option<Sender<Request>>> = Mutex::new(None);
+
 // ----------------------------------------------------
 
 // We have to encode text to wide format for Windows
@@ -50,7 +71,7 @@ fn create_window(name: &str, title: &str) -> Result<Window, IoError> {
         // Create "class" for window, using WNDCLASSW struct (different from Window our struct)
         let wnd_class = WNDCLASSW {
             style: CS_OWNDC | CS_HREDRAW | CS_VREDRAW, // Style
-            lpfnWndProc: Some(DefWindowProcW), // The callbackfunction for any window event that can occur in our window!!! Here you could react to events like WM_SIZE or WM_QUIT.
+            lpfnWndProc: Some(window_proc), // The callbackfunction for any window event that can occur in our window!!! Here you could react to events like WM_SIZE or WM_QUIT.
             hInstance: hinstance, // The instance handle for our application which we can retrieve by calling GetModuleHandleW.
             lpszClassName: name.as_ptr(), // Our class name which needs to be a UTF-16 string (defined earlier before unsafe). as_ptr() (Rust's own function) returns a raw pointer to the slice's buffer
             cbClsExtra: 0,
@@ -89,10 +110,53 @@ fn create_window(name: &str, title: &str) -> Result<Window, IoError> {
     }
 }
 
+// Handle messages sent straight to the window, which don't go through the message queue.
+// Other processes send instructions with `WM_COPYDATA`, which is answered with 1 if the instruction was understood,
+// once it has been carried out.
+#[cfg(windows)]
+unsafe extern "system" fn window_proc(
+    hwnd: HWND,
+    message: UINT,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if message == WM_COPYDATA {
+        let data = &*(lparam as *const COPYDATASTRUCT);
+        if data.dwData != CONTROL_MESSAGE || data.lpData.is_null() {
+            return 0;
+        }
+
+        let bytes = std::slice::from_raw_parts(data.lpData as *const u8, data.cbData as usize);
+        return match std::str::from_utf8(bytes).ok().and_then(Control::decode) {
+            Some(control) => {
+                let (done, finished) = mpsc::channel();
+                send_to_changer(Request {
+                    control,
+                    done: Some(done),
+                });
+                let _ = finished.recv_timeout(REPLY_TIMEOUT);
+                1
+            }
+            None => 0,
+        };
+    }
+
+    DefWindowProcW(hwnd, message, wparam, lparam)
+}
+
+/// Pass an instruction on to the cursor-checking thread.
+#[cfg(windows)]
+fn send_to_changer(request: Request) {
+    if let Some(controls) = CONTROLS.lock().unwrap().as_ref() {
+        // This only fails if the thread has already stopped, in which case there's nothing to do.
+        let _ = controls.send(request);
+    }
+}
+
 #[cfg(windows)]
 // Create message handling function with which to link to hook window to Windows messaging system
 // More info: https://msdn.microsoft.com/en-us/library/windows/desktop/ms644927(v=vs.85).aspx
-fn handle_message(window: &mut Window) -> bool {
+fn handle_message(window: &mut Window, hotkeys: &[(Hotkey, Control)]) -> bool {
     use winapi::um::winuser::WM_CLOSE;
 
     unsafe {
@@ -106,6 +170,16 @@ fn handle_message(window: &mut Window) -> bool {
             match message.message {
                 // If we receive the close message, return false to break render loop
                 WM_CLOSE => false,
+                // Hotkeys are registered with their index in `hotkeys` as their ID.
+                WM_HOTKEY => {
+                    if let Some((_, control)) = hotkeys.get(message.wParam) {
+                        send_to_changer(Request {
+                            control: control.clone(),
+                            done: None,
+                        });
+                    }
+                    true
+                }
                 // Not handling any other messages
                 _ => true,
             }
@@ -116,13 +190,68 @@ fn handle_message(window: &mut Window) -> bool {
 }
 
 
+/// Create the app's window and handle its messages until it's closed.
+/// Instructions from `hotkeys` and from other processes are sent to `controls`.
 #[cfg(windows)]
-pub fn create_window_and_block() {
-    let mut window = create_window("cursor_changer", "Window Cursor Changer").unwrap();
+pub fn create_window_and_block(hotkeys: &[(Hotkey, Control)], controls: Sender<Request>) {
+    *CONTROLS.lock().unwrap() = Some(controls);
+
+    let mut window = create_window(CLASS_NAME, "Window Cursor Changer").unwrap();
+
+    for (id, (hotkey, _)) in hotkeys.iter().enumerate() {
+        let registered = unsafe {
+            RegisterHotKey(
+                window.handle,
+                id as i32,
+                (hotkey.modifiers | MOD_NOREPEAT as u32) as UINT,
+                hotkey.key as UINT,
+            )
+        };
+        if registered == 0 {
+            error!(
+                "Failed to register the hotkey {}; another app may be using it.",
+                hotkey
+            );
+        }
+    }
 
     loop {
-        if !handle_message(&mut window) {
+        if !handle_message(&mut window, hotkeys) {
             break;
         }
     }
+}
+
+/// Send an instruction to the running cursor changer.
+#[cfg(windows)]
+pub fn send_control(control: &Control) -> crate::Result<()> {
+    let class = win32_string(CLASS_NAME);
+    let message = control.encode();
+
+    let handled = unsafe {
+        let window = FindWindowW(class.as_ptr(), std::ptr::null());
+        if window.is_null() {
+            return Err(Error::NotRunning);
+        }
+
+        let mut data = COPYDATASTRUCT {
+            dwData: CONTROL_MESSAGE,
+            cbData: message.len() as DWORD,
+            lpData: message.as_ptr() as *mut _,
+        };
+        SendMessageW(
+            window,
+            WM_COPYDATA,
+            0,
+            &mut data as *mut COPYDATASTRUCT as LPARAM,
+        )
+    };
+
+    if handled == 0 {
+        return Err(Error::InvalidArgument {
+            reason: format!("the cursor changer didn't understand \"{}\"", message),
+        });
+    }
+
+    Ok(())
 }
\ No newline at end of file
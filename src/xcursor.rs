@@ -0,0 +1,572 @@
+//! Reading X11 cursor (Xcursor) files and themes, as used by Linux desktops.
+//!
+//! An Xcursor file holds a table of contents pointing at image chunks, each of which has a "nominal" size.
+//! Several images with the same nominal size make up an animation, shown in the order they're listed.
+//!
+//! A theme is a directory with a `cursors` subdirectory holding one file per cursor name,
+//! like `left_ptr` or `xterm`. Many names are aliases (often symbolic links) for the same cursor.
+//!
+//! See: https://www.x.org/releases/current/doc/man/man3/Xcursor.3.xhtml
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::ani::{self, AnimatedCursor};
+use crate::cur::{self, CursorFile, CursorImage};
+use crate::cursor_data::CursorData;
+use crate::error::{Error, WithPath};
+use crate::image::Image;
+use crate::role::CursorRole;
+use crate::Result;
+
+/// The first bytes of every Xcursor file.
+pub const MAGIC: &[u8] = b"Xcur";
+
+/// The table of contents type of image chunks.
+const IMAGE_TYPE: u32 = 0xfffd_0002;
+
+/// The size of an image chunk's header.
+const IMAGE_HEADER_SIZE: usize = 36;
+
+/// The cursor names that can be used for each role, most common first.
+/// Themes include some names as hashes of the cursor's bitmap, which a few toolkits look up instead.
+const ROLE_NAMES: [(CursorRole, &[&str]); 14] = [
+    (
+        CursorRole::Pointer,
+        &["left_ptr", "default", "arrow", "top_left_arrow"],
+    ),
+    (
+        CursorRole::Help,
+        &[
+            "question_arrow",
+            "help",
+            "whats_this",
+            "left_ptr_help",
+            "5c6cd98b3f3ebcb1f9c7f1c204630408",
+            "d9ce0ab605698f320427677b458ad60b",
+        ],
+    ),
+    (
+        CursorRole::Working,
+        &[
+            "left_ptr_watch",
+            "progress",
+            "half-busy",
+            "3ecb610c1bf2410f44200f48c40d3599",
+            "00000000000000020006000e7e9ffc3f",
+            "08e8e1c95fe2fc01f976f1e063a24ccd",
+        ],
+    ),
+    (CursorRole::Busy, &["watch", "wait"]),
+    (
+        CursorRole::Precision,
+        &[
+            "crosshair",
+            "cross",
+            "tcross",
+            "cross_reverse",
+            "diamond_cross",
+        ],
+    ),
+    (CursorRole::Text, &["xterm", "text", "ibeam"]),
+    (
+        CursorRole::Unavailable,
+        &[
+            "not-allowed",
+            "circle",
+            "crossed_circle",
+            "forbidden",
+            "no-drop",
+            "03b6e0fcb3499374a867c041f52298f0",
+        ],
+    ),
+    (
+        CursorRole::VerticalResize,
+        &[
+            "sb_v_double_arrow",
+            "ns-resize",
+            "v_double_arrow",
+            "size_ver",
+            "row-resize",
+            "double_arrow",
+            "00008160000006810000408080010102",
+        ],
+    ),
+    (
+        CursorRole::HorizontalResize,
+        &[
+            "sb_h_double_arrow",
+            "ew-resize",
+            "h_double_arrow",
+            "size_hor",
+            "col-resize",
+            "028006030e0e7ebffc7f7070c0600140",
+        ],
+    ),
+    (
+        CursorRole::DiagonalResize1,
+        &[
+            "nwse-resize",
+            "size_fdiag",
+            "bd_double_arrow",
+            "top_left_corner",
+            "bottom_right_corner",
+            "c7088f0f3e6c8088236ef8e1e3e70000",
+        ],
+    ),
+    (
+        CursorRole::DiagonalResize2,
+        &[
+            "nesw-resize",
+            "size_bdiag",
+            "fd_double_arrow",
+            "top_right_corner",
+            "bottom_left_corner",
+            "fcf1c3c7cd4491d801f1e1c78f100000",
+        ],
+    ),
+    (
+        CursorRole::Move,
+        &[
+            "fleur",
+            "move",
+            "all-scroll",
+            "size_all",
+            "4498f0e0c1937ffe01fd06f973665830",
+            "9081237383d90e509aa00f00170e968f",
+        ],
+    ),
+    (
+        CursorRole::Alternate,
+        &["up_arrow", "center_ptr", "sb_up_arrow"],
+    ),
+    (
+        CursorRole::Link,
+        &[
+            "hand2",
+            "pointer",
+            "hand1",
+            "hand",
+            "pointing_hand",
+            "e29285e634086352946a0e7090d73106",
+            "9d800788f1b08800ae810202380a0822",
+        ],
+    ),
+];
+
+/// A single image from an Xcursor file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct XcursorImage {
+    /// The size this image was drawn for. The image itself may be a little larger or smaller.
+    pub nominal_size: u32,
+
+    pub image: CursorImage,
+
+    /// How long this image is shown for in an animation, in milliseconds.
+    pub delay: u32,
+}
+
+/// Read an Xcursor file, converting it to a static or animated cursor.
+pub fn open<P: AsRef<Path>>(path: P) -> Result<CursorData> {
+    to_cursor_data(decode(&fs::read(&path).with_path(&path)?)?)
+}
+
+/// Check whether the file at `path` is an Xcursor file, which Windows can't load without converting it.
+pub fn is_xcursor_file<P: AsRef<Path>>(path: P) -> bool {
+    let mut magic = [0; 4];
+
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok()
+        && magic == MAGIC
+}
+
+/// Decode every image of an Xcursor file, in the order they're listed.
+pub fn decode(bytes: &[u8]) -> Result<Vec<XcursorImage>> {
+    if !bytes.starts_with(MAGIC) {
+        return Err(invalid("not an Xcursor file"));
+    }
+
+    let header_size = u32_at(bytes, 4)? as usize;
+    let count = u32_at(bytes, 12)? as usize;
+
+    let mut images = Vec::new();
+    for i in 0..count {
+        let entry = header_size + i * 12;
+        if u32_at(bytes, entry)? != IMAGE_TYPE {
+            // Comments and other chunks aren't needed.
+            continue;
+        }
+
+        let position = u32_at(bytes, entry + 8)? as usize;
+        images.push(decode_image(bytes, position)?);
+    }
+
+    if images.is_empty() {
+        return Err(invalid("the file contains no images"));
+    }
+
+    Ok(images)
+}
+
+fn decode_image(bytes: &[u8], position: usize) -> Result<XcursorImage> {
+    let field = |index: usize| u32_at(bytes, position + index * 4);
+
+    if field(1)? != IMAGE_TYPE {
+        return Err(invalid("the table of contents points at the wrong chunk"));
+    }
+
+    let nominal_size = field(2)?;
+    let (width, height) = (field(4)?, field(5)?);
+    let hotspot = (field(6)?, field(7)?);
+    let delay = field(8)?;
+
+    if width == 0 || height == 0 || width > 0x7fff || height > 0x7fff {
+        return Err(invalid("an image has an invalid size"));
+    }
+    if hotspot.0 >= width || hotspot.1 >= height {
+        return Err(invalid("an image's hotspot is outside of it"));
+    }
+
+    let start = position + IMAGE_HEADER_SIZE;
+    let data = bytes
+        .get(start..start + (width * height * 4) as usize)
+        .ok_or_else(|| invalid("image data is out of bounds"))?;
+
+    // Pixels are premultiplied ARGB, stored as little-endian 32-bit values.
+    let mut image = Image::new(width, height);
+    for (pixel, argb) in image.pixels.chunks_mut(4).zip(data.chunks(4)) {
+        let alpha = argb[3];
+        let unpremultiply = |c: u8| match alpha {
+            0 => 0,
+            _ => ((u32::from(c) * 255 + u32::from(alpha) / 2) / u32::from(alpha)).min(255) as u8,
+        };
+        pixel.copy_from_slice(&[
+            unpremultiply(argb[2]),
+            unpremultiply(argb[1]),
+            unpremultiply(argb[0]),
+            alpha,
+        ]);
+    }
+
+    Ok(XcursorImage {
+        nominal_size,
+        image: CursorImage { image, hotspot },
+        delay,
+    })
+}
+
+/// Combine the images of an Xcursor file into a cursor, with one image per nominal size.
+/// If any size has several images, the cursor is animated.
+///
+/// Sizes that are too large for a .cur file are left out, as are sizes with fewer frames than the others.
+pub fn to_cursor_data(images: Vec<XcursorImage>) -> Result<CursorData> {
+    let mut sizes: BTreeMap<u32, Vec<XcursorImage>> = BTreeMap::new();
+    for image in images {
+        let size = &image.image.image;
+        if size.width <= cur::MAX_SIZE && size.height <= cur::MAX_SIZE {
+            sizes.entry(image.nominal_size).or_default().push(image);
+        }
+    }
+
+    let frame_count = sizes.values().map(Vec::len).max().unwrap_or(0);
+    let sizes: Vec<Vec<XcursorImage>> = sizes
+        .into_values()
+        .filter(|frames| frames.len() == frame_count)
+        .collect();
+
+    if sizes.is_empty() {
+        return Err(invalid(&format!(
+            "every image is larger than {} pixels",
+            cur::MAX_SIZE
+        )));
+    }
+
+    let frames: Vec<CursorFile> = (0..frame_count)
+        .map(|frame| CursorFile {
+            images: sizes
+                .iter()
+                .map(|frames| frames[frame].image.clone())
+                .collect(),
+        })
+        .collect();
+
+    if frame_count == 1 {
+        return Ok(CursorData::Static(frames.into_iter().next().unwrap()));
+    }
+
+    let rates: Vec<u32> = sizes[0]
+        .iter()
+        .map(|image| ani::jiffies_from_millis(u64::from(image.delay)))
+        .collect();
+
+    Ok(CursorData::Animated(AnimatedCursor::new(
+        frames,
+        &[],
+        &rates,
+    )?))
+}
+
+/// Find the directory holding a theme's cursor files: either `dir` itself, or its `cursors` subdirectory.
+pub fn cursors_dir(dir: &Path) -> PathBuf {
+    let cursors = dir.join("cursors");
+    if cursors.is_dir() {
+        cursors
+    } else {
+        dir.to_path_buf()
+    }
+}
+
+/// Get a theme's name from its `index.theme` file, if it has one.
+pub fn theme_name(dir: &Path) -> Option<String> {
+    let index = fs::read_to_string(dir.join("index.theme")).ok()?;
+
+    index
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .find(|(key, _)| key.trim() == "Name")
+        .map(|(_, name)| name.trim().to_string())
+}
+
+/// Find the cursor file for each role in a theme's cursors directory.
+pub fn theme_files(cursors_dir: &Path) -> BTreeMap<CursorRole, PathBuf> {
+    ROLE_NAMES
+        .iter()
+        .filter_map(|(role, names)| {
+            names
+                .iter()
+                .map(|name| cursors_dir.join(name))
+                .find(|path| path.is_file())
+                .map(|path| (*role, path))
+        })
+        .collect()
+}
+
+/// Read a little-endian `u32`, failing if it's past the end of the file.
+fn u32_at(bytes: &[u8], offset: usize) -> Result<u32> {
+    cur::u32_at(bytes, offset).map_err(|_| invalid("unexpected end of data"))
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::InvalidXcursor {
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    /// The type of comment chunks, which are skipped.
+    const COMMENT_TYPE: u32 = 0xfffe_0001;
+
+    /// An image for `xcursor`: its nominal size, its width (and height), its hotspot, and its delay.
+    type Fixture = (u32, u32, (u32, u32), u32);
+
+    /// Build an Xcursor file holding a comment and then `images`, each filled with one premultiplied
+    /// ARGB color.
+    fn xcursor(images: &[Fixture]) -> Vec<u8> {
+        let push = |bytes: &mut Vec<u8>, value: u32| bytes.extend_from_slice(&value.to_le_bytes());
+        let comment = b"made for the tests";
+
+        let mut bytes = MAGIC.to_vec();
+        push(&mut bytes, 16);
+        push(&mut bytes, 0x1_0000);
+        push(&mut bytes, images.len() as u32 + 1);
+
+        // The table of contents, and then the chunks.
+        let mut position = 16 + 12 * (images.len() as u32 + 1);
+        push(&mut bytes, COMMENT_TYPE);
+        push(&mut bytes, 1);
+        push(&mut bytes, position);
+        position += 20 + comment.len() as u32;
+        for &(nominal_size, width, _, _) in images {
+            push(&mut bytes, IMAGE_TYPE);
+            push(&mut bytes, nominal_size);
+            push(&mut bytes, position);
+            position += IMAGE_HEADER_SIZE as u32 + width * width * 4;
+        }
+
+        for value in [20, COMMENT_TYPE, 1, 1, comment.len() as u32] {
+            push(&mut bytes, value);
+        }
+        bytes.extend_from_slice(comment);
+
+        for &(nominal_size, width, hotspot, delay) in images {
+            for value in [
+                IMAGE_HEADER_SIZE as u32,
+                IMAGE_TYPE,
+                nominal_size,
+                1,
+                width,
+                width,
+                hotspot.0,
+                hotspot.1,
+                delay,
+            ] {
+                push(&mut bytes, value);
+            }
+            for _ in 0..width * width {
+                // Blue, green, red, and alpha.
+                bytes.extend_from_slice(&[0x40, 0x20, 0x10, 0x80]);
+            }
+        }
+
+        bytes
+    }
+
+    fn sizes(cursor: &CursorFile) -> Vec<(u32, (u32, u32))> {
+        cursor
+            .images
+            .iter()
+            .map(|image| (image.image.width, image.hotspot))
+            .collect()
+    }
+
+    #[test]
+    fn decodes_each_nominal_size() {
+        let images = decode(&xcursor(&[(24, 24, (4, 2), 0), (32, 30, (5, 3), 0)])).unwrap();
+
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[0].nominal_size, 24);
+        assert_eq!(images[1].nominal_size, 32);
+        assert_eq!(images[1].image.image.height, 30);
+
+        // Pixels are no longer premultiplied.
+        assert_eq!(images[0].image.image.pixel(3, 7), [32, 64, 128, 128]);
+
+        match to_cursor_data(images).unwrap() {
+            CursorData::Static(cursor) => {
+                assert_eq!(sizes(&cursor), [(24, (4, 2)), (30, (5, 3))])
+            }
+            CursorData::Animated(_) => panic!("expected a static cursor"),
+        }
+    }
+
+    #[test]
+    fn animates_sizes_with_several_images() {
+        let images = decode(&xcursor(&[
+            (24, 24, (0, 0), 50),
+            (24, 24, (1, 1), 100),
+            (24, 24, (2, 2), 50),
+            (32, 32, (0, 0), 50),
+            (32, 32, (1, 1), 100),
+            (32, 32, (2, 2), 50),
+        ]))
+        .unwrap();
+
+        let animation = match to_cursor_data(images).unwrap() {
+            CursorData::Animated(animation) => animation,
+            CursorData::Static(_) => panic!("expected an animated cursor"),
+        };
+        assert_eq!(animation.frames.len(), 3);
+        assert_eq!(sizes(&animation.frames[1]), [(24, (1, 1)), (32, (1, 1))]);
+        assert_eq!(
+            animation
+                .steps
+                .iter()
+                .map(|step| (step.frame, step.jiffies))
+                .collect::<Vec<_>>(),
+            [
+                (0, ani::jiffies_from_millis(50)),
+                (1, ani::jiffies_from_millis(100)),
+                (2, ani::jiffies_from_millis(50)),
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_out_sizes_with_fewer_frames_and_large_images() {
+        let images = decode(&xcursor(&[
+            (24, 24, (0, 0), 50),
+            (32, 32, (0, 0), 50),
+            (32, 32, (1, 1), 50),
+            (48, 48, (0, 0), 50),
+            (48, 48, (1, 1), 50),
+            (300, 300, (0, 0), 50),
+            (300, 300, (1, 1), 50),
+            (300, 300, (2, 2), 50),
+        ]))
+        .unwrap();
+
+        match to_cursor_data(images).unwrap() {
+            CursorData::Animated(animation) => {
+                assert_eq!(animation.frames.len(), 2);
+                assert_eq!(sizes(&animation.frames[1]), [(32, (1, 1)), (48, (1, 1))]);
+            }
+            CursorData::Static(_) => panic!("expected an animated cursor"),
+        }
+
+        let images = decode(&xcursor(&[(300, 300, (0, 0), 0)])).unwrap();
+        assert!(to_cursor_data(images).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        let is_invalid = |bytes: &[u8]| matches!(decode(bytes), Err(Error::InvalidXcursor { .. }));
+        let file = xcursor(&[(24, 24, (4, 2), 0)]);
+
+        // Every way of cutting the file short.
+        for length in 0..file.len() {
+            assert!(is_invalid(&file[..length]), "{} bytes", length);
+        }
+
+        // A file with only a comment.
+        let mut only_comment = file.clone();
+        only_comment[12..16].copy_from_slice(&1u32.to_le_bytes());
+        assert!(is_invalid(&only_comment));
+
+        let changed = |offset: usize, value: u32| {
+            let mut bytes = file.clone();
+            bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+            bytes
+        };
+        let image = 16 + 12 * 2 + 20 + b"made for the tests".len();
+
+        // The header size, the number of chunks, and the position of the image are out of bounds.
+        assert!(is_invalid(&changed(4, u32::MAX)));
+        assert!(is_invalid(&changed(12, u32::MAX)));
+        assert!(is_invalid(&changed(16 + 12 + 8, u32::MAX)));
+
+        // The table of contents points at the comment.
+        assert!(is_invalid(&changed(16 + 12 + 8, 16 + 12 * 2)));
+
+        // The image's size or hotspot is wrong.
+        assert!(is_invalid(&changed(image + 16, 0)));
+        assert!(is_invalid(&changed(image + 16, 0x8000)));
+        assert!(is_invalid(&changed(image + 16, 25)));
+        assert!(is_invalid(&changed(image + 24, 24)));
+
+        assert!(is_invalid(b"RIFF"));
+    }
+
+    #[test]
+    fn finds_the_files_of_a_theme() {
+        let dir = env::temp_dir().join(format!("cursor-changer-xcursor-{}", process::id()));
+        let cursors = dir.join("cursors");
+        fs::create_dir_all(cursors.join("watch")).unwrap();
+        for name in ["arrow", "left_ptr", "pointer", "xterm", "unknown"] {
+            fs::write(cursors.join(name), b"").unwrap();
+        }
+        fs::write(dir.join("index.theme"), "[Icon Theme]\nName = Test Theme\n").unwrap();
+
+        let found_dir = cursors_dir(&dir);
+        let name = theme_name(&dir);
+        let files = theme_files(&found_dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(found_dir, cursors);
+        assert_eq!(name.as_deref(), Some("Test Theme"));
+        assert_eq!(
+            files.into_iter().collect::<Vec<_>>(),
+            [
+                (CursorRole::Pointer, cursors.join("left_ptr")),
+                (CursorRole::Text, cursors.join("xterm")),
+                (CursorRole::Link, cursors.join("pointer")),
+            ]
+        );
+    }
+}